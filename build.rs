@@ -0,0 +1,32 @@
+use std::env;
+use std::process::Command;
+
+fn main() {
+    println!("cargo::rerun-if-changed=build.rs");
+    println!("cargo::rerun-if-env-changed=RUSTC");
+
+    // Only dynamic library plugins need to identify the build of the host
+    if env::var_os("CARGO_FEATURE_DYLIB").is_none() {
+        return;
+    }
+
+    // Dynamic library plugins are only compatible with hosts built by the same compiler
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+    let version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .unwrap_or_default();
+    println!("cargo::rustc-env=BINER_RUSTC_VERSION={}", version.trim());
+
+    // ... and with the same features, which may change the layout of shared types
+    let mut features = env::vars()
+        .filter_map(|(key, _)| {
+            let feature = key.strip_prefix("CARGO_FEATURE_")?;
+            Some(feature.to_lowercase().replace('_', "-"))
+        })
+        .collect::<Vec<_>>();
+    features.sort_unstable();
+    println!("cargo::rustc-env=BINER_FEATURES={}", features.join(","));
+}
@@ -0,0 +1,568 @@
+use crate::{
+    FnPluginConstructor, PluginManifest, PluginRegistry, RegisterPluginError, SimplePluginManifest,
+};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::{fs, io};
+use thiserror::Error;
+
+/// The file format of a plugin manifest file, determined by the file's extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ManifestFormat {
+    /// A TOML manifest file with the `toml` extension.
+    Toml,
+    /// A JSON manifest file with the `json` extension.
+    Json,
+}
+
+impl ManifestFormat {
+    /// Get the format of a manifest file from the extension of its path, if it is a manifest file.
+    #[must_use]
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(Self::Toml),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    /// Deserialize a value from the contents of a manifest file in this format. Useful for
+    /// implementing [`ManifestFile`] for manifests implementing [`serde::Deserialize`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem if the contents could not be deserialized.
+    pub fn deserialize<T>(self, contents: &str) -> Result<T, String>
+    where
+        T: DeserializeOwned,
+    {
+        match self {
+            Self::Toml => toml::from_str(contents).map_err(|e| e.to_string()),
+            Self::Json => serde_json::from_str(contents).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// A [`PluginManifest`] that can be read from a plugin manifest file by [`PluginDiscovery`].
+///
+/// # Examples
+///
+/// ```rust
+/// use biner::{ManifestFile, ManifestFormat, PluginManifest};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct MyManifest {
+///     id: u32,
+///     version: String,
+/// }
+///
+/// impl PluginManifest for MyManifest {
+///     type PluginId = u32;
+///
+///     fn id(&self) -> u32 {
+///         self.id
+///     }
+/// }
+///
+/// impl ManifestFile for MyManifest {
+///     fn parse(contents: &str, format: ManifestFormat) -> Result<Self, String> {
+///         format.deserialize(contents)
+///     }
+/// }
+/// ```
+pub trait ManifestFile: PluginManifest + Sized {
+    /// Parse a manifest from the contents of a manifest file in the given format.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem if the contents are not a valid manifest.
+    fn parse(contents: &str, format: ManifestFormat) -> Result<Self, String>;
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SimpleManifestFile {
+    id: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+/// Reads manifest files with an `id`, and an optional `description` and list of `dependencies`.
+/// The plugin ids are interned as `'static` strings, so each distinct id is allocated once for the
+/// rest of the program, however often manifests declaring it are read.
+impl ManifestFile for SimplePluginManifest {
+    fn parse(contents: &str, format: ManifestFormat) -> Result<Self, String> {
+        let file = format.deserialize::<SimpleManifestFile>(contents)?;
+        Ok(Self::with_dependencies(
+            intern(file.id),
+            file.description,
+            file.dependencies.into_iter().map(intern).collect(),
+        ))
+    }
+}
+
+/// Get the `'static` copy of a plugin id read from a manifest file, leaking the id only if it was
+/// never read before.
+fn intern(id: String) -> &'static str {
+    static IDS: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+    let mut ids = IDS.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(&interned) = ids.get(id.as_str()) {
+        return interned;
+    }
+    let interned = Box::leak(id.into_boxed_str());
+    ids.insert(interned);
+    interned
+}
+
+/// A problem found by [`PluginDiscovery`] while scanning for or registering plugin manifests. The
+/// problem only affects the reported file; the rest of the manifests are still discovered.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DiscoveryIssue<Id> {
+    /// A plugin directory or manifest file could not be read.
+    #[error("failed to read `{}`: {reason}", .path.display())]
+    Unreadable {
+        /// Path of the directory or file.
+        path: PathBuf,
+        /// Description of the I/O error.
+        reason: String,
+    },
+    /// A manifest file could not be parsed.
+    #[error("malformed plugin manifest `{}`: {reason}", .path.display())]
+    Malformed {
+        /// Path of the manifest file.
+        path: PathBuf,
+        /// Description of the parsing error.
+        reason: String,
+    },
+    /// A manifest file declares the same plugin id as another manifest file in the same directory,
+    /// and was ignored.
+    #[error(
+        "plugin manifest `{}` duplicates plugin `{id}` of `{}`",
+        .path.display(),
+        .first.display()
+    )]
+    Duplicate {
+        /// Plugin id declared by both manifests.
+        id: Id,
+        /// Path of the ignored manifest file.
+        path: PathBuf,
+        /// Path of the manifest file that was used instead.
+        first: PathBuf,
+    },
+    /// A manifest file was shadowed by a manifest file for the same plugin id in a directory with
+    /// higher precedence, and was ignored.
+    #[error(
+        "plugin manifest `{}` for plugin `{id}` is shadowed by `{}`",
+        .path.display(),
+        .by.display()
+    )]
+    Shadowed {
+        /// Plugin id declared by both manifests.
+        id: Id,
+        /// Path of the ignored manifest file.
+        path: PathBuf,
+        /// Path of the manifest file that was used instead.
+        by: PathBuf,
+    },
+    /// The plugin of a manifest file could not be registered.
+    #[error("failed to register plugin manifest `{}`", .path.display())]
+    Register {
+        /// Path of the manifest file.
+        path: PathBuf,
+        /// The error returned by [`PluginRegistry::register`].
+        source: RegisterPluginError<Id>,
+    },
+    /// The dynamic library found for a manifest file could not be registered.
+    #[cfg(feature = "dylib")]
+    #[error("failed to register plugin library `{}`: {reason}", .path.display())]
+    Library {
+        /// Path of the dynamic library.
+        path: PathBuf,
+        /// Description of the problem.
+        reason: String,
+    },
+}
+
+/// A plugin manifest read from a manifest file by [`PluginDiscovery::scan`].
+#[derive(Debug, Clone)]
+pub struct DiscoveredManifest<Manifest> {
+    /// The parsed manifest.
+    pub manifest: Manifest,
+    /// Path of the manifest file.
+    pub path: PathBuf,
+}
+
+/// The outcome of [`PluginDiscovery::register`].
+#[derive(Debug, Clone)]
+pub struct DiscoveryReport<Id> {
+    /// Ids of the plugins registered, in the order they were registered.
+    pub registered: Vec<Id>,
+    /// Problems found while discovering and registering the plugins.
+    pub issues: Vec<DiscoveryIssue<Id>>,
+}
+
+/// Discovers plugins from manifest files in plugin directories and registers them with a
+/// [`PluginRegistry`].
+///
+/// Each file with a `toml` or `json` extension in a plugin directory is parsed as a manifest with
+/// [`ManifestFile::parse`]. Directories are listed in order of precedence: a manifest for a plugin
+/// id shadows the manifests for the same id in all directories added after it, so user plugin
+/// directories should be added before system plugin directories. Directories that do not exist are
+/// skipped.
+///
+/// Manifests are registered with the constructor the host supplied for their plugin id with
+/// [`PluginDiscovery::constructor`]. Manifests without a constructor are registered without one,
+/// and can still be loaded with [`PluginRegistry::load_with`].
+///
+/// # Examples
+///
+/// ```rust
+/// use biner::{Plugin, PluginDiscovery, PluginRegistry};
+///
+/// struct MyPlugin;
+///
+/// impl Plugin for MyPlugin {}
+///
+/// let mut plugins: PluginRegistry = PluginRegistry::new();
+/// let report = PluginDiscovery::new()
+///     .directory("/home/user/.my_app/plugins")
+///     .directory("/usr/share/my_app/plugins")
+///     .constructor("my_plugin", || Box::new(MyPlugin))
+///     .register(&mut plugins);
+/// for issue in &report.issues {
+///     eprintln!("{issue}");
+/// }
+/// ```
+pub struct PluginDiscovery<Manifest = SimplePluginManifest, Context = ()>
+where
+    Manifest: PluginManifest,
+{
+    directories: Vec<PathBuf>,
+    constructors: HashMap<Manifest::PluginId, FnPluginConstructor<Manifest::PluginId, Context>>,
+}
+
+impl<Manifest, Context> PluginDiscovery<Manifest, Context>
+where
+    Manifest: ManifestFile,
+{
+    /// Create a plugin discovery without any plugin directories.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            directories: Vec::new(),
+            constructors: HashMap::new(),
+        }
+    }
+
+    /// Add a plugin directory with a lower precedence than all the directories already added.
+    #[must_use]
+    pub fn directory(mut self, path: impl Into<PathBuf>) -> Self {
+        self.directories.push(path.into());
+        self
+    }
+
+    /// Supply the constructor for the plugin with the given id, used when a manifest for the
+    /// plugin is discovered.
+    #[must_use]
+    pub fn constructor(
+        mut self,
+        id: Manifest::PluginId,
+        ctor: FnPluginConstructor<Manifest::PluginId, Context>,
+    ) -> Self {
+        self.constructors.insert(id, ctor);
+        self
+    }
+
+    /// Get the plugin directories, in order of precedence.
+    #[must_use]
+    pub fn directories(&self) -> &[PathBuf] {
+        &self.directories
+    }
+
+    /// Scan the plugin directories for manifest files without registering them. Returns the
+    /// manifests that were not shadowed or duplicated, in order of precedence, along with all the
+    /// problems found. Files within a directory are read in order of their file names.
+    #[must_use]
+    pub fn scan(
+        &self,
+    ) -> (
+        Vec<DiscoveredManifest<Manifest>>,
+        Vec<DiscoveryIssue<Manifest::PluginId>>,
+    ) {
+        let mut discovered = Vec::<DiscoveredManifest<Manifest>>::new();
+        let mut issues = Vec::new();
+        // Index of the discovered manifest and of its directory for each plugin id
+        let mut found = HashMap::<_, (usize, usize)>::new();
+        for (layer, directory) in self.directories.iter().enumerate() {
+            let mut files = match fs::read_dir(directory) {
+                Ok(entries) => entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_file())
+                    .filter_map(|path| Some((ManifestFormat::from_path(&path)?, path)))
+                    .collect::<Vec<_>>(),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => {
+                    issues.push(DiscoveryIssue::Unreadable {
+                        path: directory.clone(),
+                        reason: error.to_string(),
+                    });
+                    continue;
+                }
+            };
+            files.sort_unstable_by(|(_, a), (_, b)| a.cmp(b));
+
+            for (format, path) in files {
+                let manifest = match fs::read_to_string(&path) {
+                    Ok(contents) => Manifest::parse(&contents, format),
+                    Err(error) => {
+                        issues.push(DiscoveryIssue::Unreadable {
+                            path,
+                            reason: error.to_string(),
+                        });
+                        continue;
+                    }
+                };
+                let manifest = match manifest {
+                    Ok(manifest) => manifest,
+                    Err(reason) => {
+                        issues.push(DiscoveryIssue::Malformed { path, reason });
+                        continue;
+                    }
+                };
+
+                let id = manifest.id();
+                match found.get(&id) {
+                    Some(&(index, found_layer)) if found_layer == layer => {
+                        issues.push(DiscoveryIssue::Duplicate {
+                            id,
+                            path,
+                            first: discovered[index].path.clone(),
+                        });
+                    }
+                    Some(&(index, _)) => {
+                        issues.push(DiscoveryIssue::Shadowed {
+                            id,
+                            path,
+                            by: discovered[index].path.clone(),
+                        });
+                    }
+                    None => {
+                        found.insert(id, (discovered.len(), layer));
+                        discovered.push(DiscoveredManifest { manifest, path });
+                    }
+                }
+            }
+        }
+        (discovered, issues)
+    }
+
+    /// Scan the plugin directories with [`PluginDiscovery::scan`] and register all the discovered
+    /// manifests with [`PluginRegistry::register`]. Manifests that fail to register are reported
+    /// without stopping the registration of the others.
+    pub fn register(
+        &self,
+        registry: &mut PluginRegistry<Manifest, Context>,
+    ) -> DiscoveryReport<Manifest::PluginId> {
+        let (discovered, mut issues) = self.scan();
+        let mut registered = Vec::new();
+        for DiscoveredManifest { manifest, path } in discovered {
+            let ctor = self.constructors.get(&manifest.id()).copied();
+            match registry.register(manifest, ctor) {
+                Ok(id) => registered.push(id),
+                Err(source) => issues.push(DiscoveryIssue::Register { path, source }),
+            }
+        }
+        DiscoveryReport { registered, issues }
+    }
+}
+
+#[cfg(feature = "dylib")]
+impl<Manifest, Context> PluginDiscovery<Manifest, Context>
+where
+    Manifest: ManifestFile,
+{
+    /// Like [`PluginDiscovery::register`], but manifests without a constructor supplied by the host
+    /// are matched to a dynamic library next to the manifest file, named after the manifest file
+    /// with the platform's library naming convention. For example, the library for
+    /// `plugins/my_plugin.toml` is `plugins/libmy_plugin.so` on Linux.
+    ///
+    /// The library is registered with [`PluginRegistry::register_library`] instead of the
+    /// manifest, and must register the plugin with a manifest equal to the manifest file's, so that
+    /// its description and dependencies are the ones the file declares. Otherwise, the plugin is
+    /// removed again and the mismatch is reported. Manifests without a constructor or library are
+    /// registered without a constructor.
+    ///
+    /// # Safety
+    ///
+    /// Every library found must satisfy the safety requirements of
+    /// [`PluginRegistry::register_library`].
+    pub unsafe fn register_with_libraries(
+        &self,
+        registry: &mut PluginRegistry<Manifest, Context>,
+    ) -> DiscoveryReport<Manifest::PluginId>
+    where
+        Manifest: PartialEq,
+    {
+        let (discovered, mut issues) = self.scan();
+        let mut registered = Vec::new();
+        for DiscoveredManifest { manifest, path } in discovered {
+            let id = manifest.id();
+            let ctor = self.constructors.get(&id).copied();
+            let library = path
+                .file_stem()
+                .map(|name| path.with_file_name(libloading::library_filename(name)));
+            match library.filter(|library| ctor.is_none() && library.is_file()) {
+                Some(library) => {
+                    // SAFETY: Upheld by the caller
+                    match unsafe { registry.register_library(&library) } {
+                        Ok(ids) if !ids.contains(&id) => {
+                            registered.extend(ids);
+                            issues.push(DiscoveryIssue::Library {
+                                path: library,
+                                reason: format!(
+                                    "the library does not register the plugin of `{}`",
+                                    path.display()
+                                ),
+                            });
+                        }
+                        Ok(ids) if registry.get_manifest(id) != Some(&manifest) => {
+                            registry.unregister(id);
+                            registered.extend(ids.into_iter().filter(|&other| other != id));
+                            issues.push(DiscoveryIssue::Library {
+                                path: library,
+                                reason: format!(
+                                    "the library registers the plugin of `{}` with a different \
+                                     manifest",
+                                    path.display()
+                                ),
+                            });
+                        }
+                        Ok(ids) => registered.extend(ids),
+                        Err(error) => issues.push(DiscoveryIssue::Library {
+                            path: library,
+                            reason: error.to_string(),
+                        }),
+                    }
+                }
+                None => match registry.register(manifest, ctor) {
+                    Ok(id) => registered.push(id),
+                    Err(source) => issues.push(DiscoveryIssue::Register { path, source }),
+                },
+            }
+        }
+        DiscoveryReport { registered, issues }
+    }
+}
+
+impl<Manifest, Context> Default for PluginDiscovery<Manifest, Context>
+where
+    Manifest: ManifestFile,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Manifest, Context> Debug for PluginDiscovery<Manifest, Context>
+where
+    Manifest: PluginManifest,
+    Manifest::PluginId: Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginDiscovery")
+            .field("directories", &self.directories)
+            .field("constructors", &self.constructors.keys())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::TempDir;
+    use crate::{DiscoveryIssue, Plugin, PluginDiscovery, PluginManifest, PluginRegistry};
+    use std::fs;
+    use std::path::PathBuf;
+
+    struct TestPlugin;
+
+    impl Plugin for TestPlugin {}
+
+    fn plugin_dirs(root: &TempDir) -> (PathBuf, PathBuf) {
+        let (user, system) = (root.join("user"), root.join("system"));
+        fs::create_dir_all(&user).unwrap();
+        fs::create_dir_all(&system).unwrap();
+        (user, system)
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn discover_layered_manifests() {
+        let root = TempDir::new("discovery");
+        let (user, system) = plugin_dirs(&root);
+        fs::write(
+            user.join("editor.toml"),
+            "id = \"editor\"\ndescription = \"User editor\"\ndependencies = [\"core\"]\n",
+        )
+        .unwrap();
+        fs::write(user.join("zz_editor.json"), r#"{ "id": "editor" }"#).unwrap();
+        fs::write(user.join("broken.toml"), "id = ").unwrap();
+        fs::write(user.join("notes.txt"), "not a manifest").unwrap();
+        fs::write(
+            system.join("editor.json"),
+            r#"{ "id": "editor", "description": "System editor" }"#,
+        )
+        .unwrap();
+        fs::write(system.join("core.json"), r#"{ "id": "core" }"#).unwrap();
+
+        let mut plugins: PluginRegistry = PluginRegistry::new();
+        let report = PluginDiscovery::new()
+            .directory(&user)
+            .directory(&system)
+            .directory(user.with_file_name("missing"))
+            .constructor("core", || Box::new(TestPlugin))
+            .constructor("editor", || Box::new(TestPlugin))
+            .register(&mut plugins);
+
+        assert_eq!(report.registered, ["editor", "core"]);
+        assert_eq!(
+            plugins.get_manifest("editor").unwrap().description(),
+            "User editor"
+        );
+        assert_eq!(
+            plugins.get_manifest("editor").unwrap().dependencies(),
+            ["core"]
+        );
+        assert_eq!(report.issues.len(), 3);
+        assert!(matches!(
+            &report.issues[0],
+            DiscoveryIssue::Malformed { path, .. } if path.ends_with("broken.toml")
+        ));
+        assert_eq!(
+            report.issues[1],
+            DiscoveryIssue::Duplicate {
+                id: "editor",
+                path: user.join("zz_editor.json"),
+                first: user.join("editor.toml"),
+            }
+        );
+        assert_eq!(
+            report.issues[2],
+            DiscoveryIssue::Shadowed {
+                id: "editor",
+                path: system.join("editor.json"),
+                by: user.join("editor.toml"),
+            }
+        );
+
+        plugins.enable("editor", &mut ()).unwrap();
+        assert!(plugins.is_enabled("core"));
+    }
+}
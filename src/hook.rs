@@ -1,8 +1,31 @@
-use std::any::{Any, TypeId};
+use std::any::{Any, TypeId, type_name};
 use std::collections::HashMap;
-use std::fmt::{Debug, Formatter};
+use std::error::Error as StdError;
+use std::fmt::{Debug, Display, Formatter};
 use std::hash::{BuildHasher, Hash, Hasher, RandomState};
 use std::iter::FusedIterator;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use thiserror::Error;
+
+mod breaker;
+mod dispatch;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod profiling;
+mod service;
+mod snapshot;
+
+pub use breaker::*;
+pub use profiling::*;
+pub use service::*;
+pub use snapshot::*;
+
+use crate::{PluginConfig, PluginResources};
+use breaker::HookBreaker;
+use dispatch::Monitors;
+use profiling::HookProfiler;
+use service::Service;
 
 /// Defines a slot for extension by hooks. A type that implements this trait will be used
 /// as the key for accessing hook objects. Since these slot types are never instantiated, zero-sized
@@ -11,7 +34,7 @@
 /// # Examples
 ///
 /// ```rust
-/// use biner::HookSlot;
+/// use biner::{HookSlot, SlotId};
 ///
 /// trait MyHookTrait: std::any::Any + Send + Sync {
 ///     // ... insert a custom hook interface for the MyHook slot
@@ -21,6 +44,16 @@
 ///
 /// impl HookSlot for MyHook {
 ///     type TraitObject = dyn MyHookTrait; // Indicate the trait object to be used for hooks
+///
+///     // A stable id and signature for slots shared with separately compiled binaries
+///     fn id() -> SlotId {
+///         const ID: SlotId = SlotId::new("my_app::MyHook");
+///         ID
+///     }
+///
+///     fn signature() -> &'static str {
+///         "my_app::MyHookTrait v1"
+///     }
 /// }
 /// ```
 pub trait HookSlot: 'static {
@@ -28,31 +61,480 @@ pub trait HookSlot: 'static {
     /// to be set when implementing this trait for a hook slot.
     type TraitObject: ?Sized + Any + Send + Sync;
 
-    /// Gets the unique identifier for this hook slot, which by default is just the `TypeId` of the
-    /// slot's type, but is valid to give any unique type id.
-    fn id() -> TypeId {
-        TypeId::of::<Self>()
+    /// Gets the unique identifier for this hook slot, which the hook registry uses as the key of
+    /// the slot. By default this is the type name of the slot's type, which is only guaranteed to
+    /// be the same within a single build. Slots shared with separately compiled binaries should
+    /// declare their id explicitly instead, such as with [`hook_slot`][crate::hook_slot], which
+    /// derives it from the module path and name of the slot.
+    ///
+    /// Slots with the same id must use the same [`HookSlot::TraitObject`] and
+    /// [`HookSlot::signature`], which can be checked with [`HookRegistry::verify_slot`].
+    fn id() -> SlotId {
+        SlotId::new(type_name::<Self>())
+    }
+
+    /// Gets a stable description of the slot's [`HookSlot::TraitObject`], such as the path of the
+    /// trait along with a version. Slots sharing an id must declare the same signature, so it
+    /// should change whenever the trait changes incompatibly. [`hook_slot`][crate::hook_slot]
+    /// derives it from the trait object type as written. Empty by default.
+    fn signature() -> &'static str {
+        ""
     }
+
+    /// Gets a human-readable name for this hook slot, used in error messages and diagnostics. By
+    /// default this is the type name of the slot's type.
+    fn name() -> &'static str {
+        type_name::<Self>()
+    }
+
+    /// Gets a human-readable description of this hook slot, which is empty by default.
+    fn description() -> &'static str {
+        ""
+    }
+
+    /// Gets how many hooks across all plugins this slot accepts, which is
+    /// [`SlotCardinality::Many`] by default.
+    fn cardinality() -> SlotCardinality {
+        SlotCardinality::Many
+    }
+}
+
+/// A stable identifier of a [`HookSlot`], returned by [`HookSlot::id`]. The id is a string, such as
+/// the path of the slot, along with a 64-bit FNV-1a hash of that string used for fast lookups. Both
+/// are the same in every build, so slots can be matched between separately compiled binaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SlotId {
+    hash: u64,
+    name: &'static str,
+}
+
+impl SlotId {
+    /// Create a slot id from a string, which should be unique among all slots of a plugin host.
+    #[must_use]
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            hash: fnv1a(name.as_bytes()),
+            name,
+        }
+    }
+
+    /// Get the string the slot id was created from.
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the stable hash of the slot id.
+    #[must_use]
+    pub const fn stable_hash(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// Compute the 64-bit FNV-1a hash of `bytes`, which is the same in every build.
+pub(crate) const fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+        i += 1;
+    }
+    hash
+}
+
+impl Hash for SlotId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl Display for SlotId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+/// The number of hooks across all plugins a [`HookSlot`] accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum SlotCardinality {
+    /// Exactly one enabled plugin must provide a hook for the slot. Registering more than one hook
+    /// is rejected by [`HookRegistry::register`], while a missing hook is reported by
+    /// [`PluginRegistry::validate`][crate::PluginRegistry::validate].
+    ExactlyOne,
+    /// At most one hook may be registered to the slot. Registering more than one hook is rejected
+    /// by [`HookRegistry::register`].
+    AtMostOne,
+    /// Any number of hooks may be registered to the slot.
+    #[default]
+    Many,
+}
+
+impl SlotCardinality {
+    /// Determine whether the slot accepts at most a single hook.
+    #[must_use]
+    pub fn is_single(self) -> bool {
+        matches!(self, Self::ExactlyOne | Self::AtMostOne)
+    }
+}
+
+/// Metadata declared by a [`HookSlot`], as recorded by a [`HookRegistry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SlotInfo {
+    id: SlotId,
+    name: &'static str,
+    description: &'static str,
+    cardinality: SlotCardinality,
+    signature: &'static str,
+    trait_object: TypeId,
+    trait_object_name: &'static str,
+}
+
+impl SlotInfo {
+    /// Get the metadata declared by the hook slot `Slot`.
+    #[must_use]
+    pub fn of<Slot>() -> Self
+    where
+        Slot: HookSlot,
+    {
+        Self {
+            id: Slot::id(),
+            name: Slot::name(),
+            description: Slot::description(),
+            cardinality: Slot::cardinality(),
+            signature: Slot::signature(),
+            trait_object: TypeId::of::<Slot::TraitObject>(),
+            trait_object_name: type_name::<Slot::TraitObject>(),
+        }
+    }
+
+    /// Get the id of the slot. See [`HookSlot::id`].
+    #[must_use]
+    pub fn id(&self) -> SlotId {
+        self.id
+    }
+
+    /// Get the human-readable name of the slot. See [`HookSlot::name`].
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the description of the slot. See [`HookSlot::description`].
+    #[must_use]
+    pub fn description(&self) -> &'static str {
+        self.description
+    }
+
+    /// Get the number of hooks the slot accepts. See [`HookSlot::cardinality`].
+    #[must_use]
+    pub fn cardinality(&self) -> SlotCardinality {
+        self.cardinality
+    }
+
+    /// Get the signature of the slot's trait object. See [`HookSlot::signature`].
+    #[must_use]
+    pub fn signature(&self) -> &'static str {
+        self.signature
+    }
+
+    /// Get the type name of the slot's [`HookSlot::TraitObject`].
+    #[must_use]
+    pub fn trait_object_name(&self) -> &'static str {
+        self.trait_object_name
+    }
+
+    /// Determine whether the hook slot `Slot` has the same id, signature and trait object as this
+    /// slot. The signature is compared first, as it is declared explicitly and so also catches
+    /// mismatches between separately compiled binaries.
+    #[must_use]
+    pub fn matches<Slot>(&self) -> bool
+    where
+        Slot: HookSlot,
+    {
+        self.id == Slot::id()
+            && self.signature == Slot::signature()
+            && self.trait_object == TypeId::of::<Slot::TraitObject>()
+    }
+
+    /// Get the error reporting that the slot described by `other` was used with this slot's id, if
+    /// their signatures or trait objects differ.
+    fn conflict(&self, other: &SlotInfo) -> Option<SlotTypeMismatch> {
+        let matches = self.signature == other.signature && self.trait_object == other.trait_object;
+        (!matches).then_some(SlotTypeMismatch {
+            slot: self.id,
+            expected: self.trait_object_name,
+            expected_signature: self.signature,
+            found: other.trait_object_name,
+            found_signature: other.signature,
+        })
+    }
+
+    /// Get the error reporting that the hook slot `Slot` was used with this slot's id.
+    pub(crate) fn mismatch<Slot>(&self) -> SlotTypeMismatch
+    where
+        Slot: HookSlot,
+    {
+        SlotTypeMismatch {
+            slot: self.id,
+            expected: self.trait_object_name,
+            expected_signature: self.signature,
+            found: type_name::<Slot::TraitObject>(),
+            found_signature: Slot::signature(),
+        }
+    }
+}
+
+/// A hook slot was used with a different [`HookSlot::TraitObject`] or [`HookSlot::signature`] than
+/// the one recorded for its [`SlotId`] by the hook registry, usually because two slot types share
+/// the same id or were built against different versions of the slot's trait.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Error)]
+#[error(
+    "slot `{slot}` holds hooks of type `{expected}` ({expected_signature:?}), but was used with `{found}` ({found_signature:?})"
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SlotTypeMismatch {
+    /// Id of the hook slot.
+    pub slot: SlotId,
+    /// Type name of the trait object recorded for the slot.
+    pub expected: &'static str,
+    /// Signature recorded for the slot.
+    pub expected_signature: &'static str,
+    /// Type name of the trait object the slot was used with.
+    pub found: &'static str,
+    /// Signature of the slot it was used with.
+    pub found_signature: &'static str,
+}
+
+/// An error occurred while registering a hook. It is generic over the type of plugin id used by
+/// the hook registry; see [`HookRegistry`] for more details.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Error)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum RegisterHookError<Id> {
+    /// The plugin already registered a hook with the same name for the slot.
+    #[error("plugin `{plugin}` already registered hook {name:?} for slot `{slot}`")]
+    Duplicate {
+        /// Name of the hook slot.
+        slot: &'static str,
+        /// Plugin id of the plugin registering the hook.
+        plugin: Id,
+        /// Name of the hook.
+        name: Option<Id>,
+    },
+    /// The slot accepts at most one hook across all plugins and already has a hook.
+    #[error(
+        "slot `{slot}` accepts at most one hook, but plugin `{existing}` already registered one before plugin `{plugin}`"
+    )]
+    CardinalityExceeded {
+        /// Name of the hook slot.
+        slot: &'static str,
+        /// Plugin id of the plugin registering the hook.
+        plugin: Id,
+        /// Plugin id of the plugin that already registered a hook for the slot.
+        existing: Id,
+    },
+    /// The slot's id was first used with a different signature or trait object.
+    #[error(transparent)]
+    TypeMismatch(#[from] SlotTypeMismatch),
+}
+
+/// A hook rejected by [`HookRegistry::register`] or [`HookRegistry::register_shared`], handing the
+/// hook back to the caller along with the reason it was rejected.
+pub struct RejectedHook<Id, H> {
+    /// The hook that was not registered.
+    pub hook: H,
+    /// The reason the hook was rejected.
+    pub error: RegisterHookError<Id>,
+}
+
+impl<Id, H> Debug for RejectedHook<Id, H>
+where
+    Id: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RejectedHook")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<Id, H> Display for RejectedHook<Id, H>
+where
+    Id: Display + Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.error, f)
+    }
+}
+
+impl<Id, H> StdError for RejectedHook<Id, H>
+where
+    Id: Display + Debug + 'static,
+{
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// An error occurred while mutably accessing a hook with [`HookRegistry::try_get_first_mut`] or
+/// [`HookRegistry::try_get_exact_mut`]. It is generic over the type of plugin id used by the hook
+/// registry; see [`HookRegistry`] for more details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Error)]
+pub enum HookAccessError<Id> {
+    /// The hook is shared, such as by a [`HookSnapshot`] or by an [`Arc`] returned from a `_shared`
+    /// accessor, so it cannot be borrowed mutably until those are dropped.
+    #[error("hook {name:?} of plugin `{plugin}` for slot `{slot}` is shared")]
+    Shared {
+        /// Id of the hook slot.
+        slot: SlotId,
+        /// Plugin id of the plugin that registered the hook.
+        plugin: Id,
+        /// Name of the hook.
+        name: Option<Id>,
+    },
+    /// The slot's id was first used with a different signature or trait object.
+    #[error(transparent)]
+    TypeMismatch(#[from] SlotTypeMismatch),
 }
 
 type DynHook = dyn Any + Send + Sync;
 
+/// A type-erased handle keeping the dynamic library that plugin code was loaded from mapped.
+pub(crate) type LibraryHandle = Arc<dyn Any + Send + Sync>;
+
+/// A type-erased hook. The erased value is a `Box<Slot::TraitObject>` for hooks owned by the
+/// registry, or an `Arc<Slot::TraitObject>` for hooks registered with
+/// [`HookRegistry::register_shared`], which can be shared with snapshots of the registry.
 struct Hook<Id> {
     plugin: Id,
-    slot: TypeId,
+    slot: SlotId,
     name: Option<Id>,
     ptr: Box<DynHook>,
+    // Clones the erased `Arc` of a shared hook
+    share: Option<fn(&DynHook) -> Box<DynHook>>,
+    // Dropped after the hook, as the hook's code may live in the library
+    library: Option<LibraryHandle>,
 }
 
 impl<Id> Hook<Id> {
-    fn new(plugin: Id, slot: TypeId, name: Option<Id>, ptr: Box<DynHook>) -> Self {
+    fn owned<T>(
+        plugin: Id,
+        slot: SlotId,
+        name: Option<Id>,
+        hook: Box<T>,
+        library: Option<LibraryHandle>,
+    ) -> Self
+    where
+        T: ?Sized + Any + Send + Sync,
+    {
+        Self {
+            plugin,
+            slot,
+            name,
+            ptr: Box::new(hook),
+            share: None,
+            library,
+        }
+    }
+
+    fn shared<T>(
+        plugin: Id,
+        slot: SlotId,
+        name: Option<Id>,
+        hook: Arc<T>,
+        library: Option<LibraryHandle>,
+    ) -> Self
+    where
+        T: ?Sized + Any + Send + Sync,
+    {
         Self {
             plugin,
             slot,
             name,
-            ptr,
+            ptr: Box::new(hook),
+            share: Some(|ptr| Box::new(ptr.downcast_ref::<Arc<T>>().unwrap().clone())),
+            library,
+        }
+    }
+
+    fn get<T>(&self) -> Option<&T>
+    where
+        T: ?Sized + Any,
+    {
+        match self.share {
+            Some(_) => self.ptr.downcast_ref::<Arc<T>>().map(AsRef::as_ref),
+            None => self.ptr.downcast_ref::<Box<T>>().map(AsRef::as_ref),
         }
     }
+
+    fn get_shared<T>(&self) -> Option<Arc<T>>
+    where
+        T: ?Sized + Any,
+    {
+        self.ptr.downcast_ref::<Arc<T>>().cloned()
+    }
+
+    fn get_mut<T>(&mut self) -> Result<Option<&mut T>, HookAccessError<Id>>
+    where
+        Id: Copy,
+        T: ?Sized + Any,
+    {
+        if self.share.is_none() {
+            return Ok(self.ptr.downcast_mut::<Box<T>>().map(AsMut::as_mut));
+        }
+        let Some(hook) = self.ptr.downcast_mut::<Arc<T>>() else {
+            return Ok(None);
+        };
+        if Arc::get_mut(hook).is_none() {
+            return Err(HookAccessError::Shared {
+                slot: self.slot,
+                plugin: self.plugin,
+                name: self.name,
+            });
+        }
+        Ok(Arc::get_mut(hook))
+    }
+
+    /// Get the hook mutably, or `None` while a shared hook has other clones.
+    fn get_unshared_mut<T>(&mut self) -> Option<&mut T>
+    where
+        Id: Copy,
+        T: ?Sized + Any,
+    {
+        self.get_mut().ok().flatten()
+    }
+
+    /// Convert the hook into an [`Arc`], boxing it again if it is owned by the registry.
+    fn into_shared<T>(self) -> Option<Arc<T>>
+    where
+        T: ?Sized + Any,
+    {
+        match self.share {
+            Some(_) => self.ptr.downcast::<Arc<T>>().ok().map(|hook| *hook),
+            None => self
+                .ptr
+                .downcast::<Box<T>>()
+                .ok()
+                .map(|hook| Arc::from(*hook)),
+        }
+    }
+
+    /// Clone a hook registered with [`HookRegistry::register_shared`], sharing the hook itself.
+    fn share(&self) -> Option<Self>
+    where
+        Id: Copy,
+    {
+        let share = self.share?;
+        Some(Self {
+            plugin: self.plugin,
+            slot: self.slot,
+            name: self.name,
+            ptr: share(self.ptr.as_ref()),
+            share: self.share,
+            library: self.library.clone(),
+        })
+    }
 }
 
 impl<Id> PartialEq for Hook<Id>
@@ -90,6 +572,9 @@ fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
     }
 }
 
+/// Hooks of every slot, keyed by slot id and plugin id.
+type HookIndex<Id, S> = HashMap<SlotId, HashMap<Id, Vec<Hook<Id>>, S>, S>;
+
 /// Manages hooks for plugins. Hooks are types implementing hook traits (which can be any `'static`
 /// trait that is [`Send`] and [`Sync`]). These hooks are then attached to "hook slots" by plugins.
 /// Hook slots are a type implementing [`HookSlot`] and the slot types are used as generic type
@@ -97,6 +582,28 @@ fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 /// name discriminator. This means a plugin can register multiple hooks for the same slot as long
 /// as each hook has a different name.
 ///
+/// Hooks registered with [`HookRegistry::register`] are owned by the registry, while hooks
+/// registered with [`HookRegistry::register_shared`] are kept in an [`Arc`]. Besides borrowing
+/// them, the `_shared` accessors such as [`HookRegistry::get_first_shared`] return clones of the
+/// [`Arc`] of shared hooks, which can be held across await points or moved to other threads, and
+/// [`HookSnapshot`]s share them with the registry. Removing a hook, such as when its plugin is
+/// unloaded, only removes it from the registry; existing clones keep the hook alive until they are
+/// dropped. While a shared hook has other clones, it cannot be borrowed mutably: the mutable
+/// accessors such as [`HookRegistry::get_first_mut`] skip it, while
+/// [`HookRegistry::try_get_first_mut`] and [`HookRegistry::try_get_exact_mut`] report
+/// [`HookAccessError::Shared`]. Hooks that must change while shared should use interior mutability
+/// instead.
+///
+/// The registry records the trait object of every slot id when it is first used. Registering a
+/// hook through a slot type that shares the id but not the trait object is rejected with
+/// [`RegisterHookError::TypeMismatch`]. The accessors panic in debug builds when used with such a
+/// slot type and find no hooks otherwise, while [`HookRegistry::verify_slot`] and the `try_`
+/// accessors such as [`HookRegistry::try_get_first`] report a [`SlotTypeMismatch`].
+///
+/// Besides hooks for slots defined by the plugin host, plugins can share functionality with each
+/// other by publishing typed services with [`HookRegistry::provide_service`], which plugins listing
+/// the provider as a dependency look up with [`HookRegistry::service`].
+///
 /// # Generic Arguments
 ///
 /// `Id` is the type used for identifying plugins and hook names. This type should be a type that is
@@ -107,13 +614,31 @@ fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 /// `S` allows you to specify an alternative hasher for the internal indexes of the hooks.
 #[derive(Debug)]
 pub struct HookRegistry<Id = &'static str, S = RandomState> {
-    slot_hooks: HashMap<TypeId, HashMap<Id, Vec<Hook<Id>>, S>, S>,
+    slot_hooks: HookIndex<Id, S>,
+    slots: HashMap<SlotId, SlotInfo, S>,
+    services: HashMap<Id, Vec<Service>, S>,
+    dependencies: HashMap<Id, Vec<Id>, S>,
+    configs: HashMap<Id, PluginConfig, S>,
+    profiler: Option<HookProfiler<Id>>,
+    breaker: Option<HookBreaker<Id>>,
+    caller: Option<Id>,
+    resources: Option<PluginResources>,
+    library: Option<LibraryHandle>,
 }
 
 impl<Id> HookRegistry<Id> {
     pub(crate) fn new() -> Self {
         Self {
             slot_hooks: HashMap::new(),
+            slots: HashMap::new(),
+            services: HashMap::new(),
+            dependencies: HashMap::new(),
+            configs: HashMap::new(),
+            profiler: None,
+            breaker: None,
+            caller: None,
+            resources: None,
+            library: None,
         }
     }
 }
@@ -123,10 +648,106 @@ impl<Id, S> HookRegistry<Id, S>
     Id: Copy + Ord + Hash,
     S: BuildHasher,
 {
-    pub(crate) fn with_hasher(hash_builder: S) -> Self {
+    pub(crate) fn with_hasher(hash_builder: S) -> Self
+    where
+        S: Clone,
+    {
         Self {
-            slot_hooks: HashMap::with_hasher(hash_builder),
+            slot_hooks: HashMap::with_hasher(hash_builder.clone()),
+            slots: HashMap::with_hasher(hash_builder.clone()),
+            services: HashMap::with_hasher(hash_builder.clone()),
+            dependencies: HashMap::with_hasher(hash_builder.clone()),
+            configs: HashMap::with_hasher(hash_builder),
+            profiler: None,
+            breaker: None,
+            caller: None,
+            resources: None,
+            library: None,
+        }
+    }
+
+    /// Call `f` with the registry on behalf of `plugin`, lending it the plugin's `resources` and
+    /// attaching `library` to every hook registered by `f` so that the library stays mapped until
+    /// those hooks are dropped.
+    pub(crate) fn with_plugin<R>(
+        &mut self,
+        plugin: Id,
+        library: Option<LibraryHandle>,
+        resources: &mut PluginResources,
+        f: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let previous_caller = self.caller.replace(plugin);
+        let previous_resources = self.resources.replace(std::mem::take(resources));
+        let previous = std::mem::replace(&mut self.library, library);
+        // Restore the registry even if the plugin panics, as the panic may be caught
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(self)));
+        self.library = previous;
+        *resources = std::mem::replace(&mut self.resources, previous_resources).unwrap_or_default();
+        self.caller = previous_caller;
+        result.unwrap_or_else(|payload| panic::resume_unwind(payload))
+    }
+
+    /// Get the metadata of a hook slot, if the slot has been declared with
+    /// [`HookRegistry::declare_slot`] or any hook has been registered to it.
+    #[must_use]
+    pub fn slot_info<Slot>(&self) -> Option<&SlotInfo>
+    where
+        Slot: HookSlot,
+    {
+        self.slots.get(&Slot::id())
+    }
+
+    /// Verify that the hook slot `Slot` declares the same [`HookSlot::signature`] and uses the
+    /// same [`HookSlot::TraitObject`] as the slot recorded by the registry under the same id. Slots
+    /// unknown to the registry always pass.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SlotTypeMismatch`] if the registry recorded a different signature or trait object
+    /// for the slot's id, in which case the registry's hooks can never be accessed through `Slot`.
+    pub fn verify_slot<Slot>(&self) -> Result<(), SlotTypeMismatch>
+    where
+        Slot: HookSlot,
+    {
+        match self.slots.get(&Slot::id()) {
+            Some(info) if !info.matches::<Slot>() => Err(info.mismatch::<Slot>()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the id of the hook slot `Slot`, panicking in debug builds if the registry recorded a
+    /// different trait object for it.
+    fn checked_id<Slot>(&self) -> SlotId
+    where
+        Slot: HookSlot,
+    {
+        #[cfg(debug_assertions)]
+        if let Err(error) = self.verify_slot::<Slot>() {
+            panic!("{error}");
         }
+        Slot::id()
+    }
+
+    /// Get an iterator over the metadata of all slots that have been declared with
+    /// [`HookRegistry::declare_slot`] or had any hook registered to them.
+    #[must_use]
+    pub fn slots(&self) -> impl FusedIterator<Item = &SlotInfo> {
+        self.slots.values()
+    }
+
+    /// Get an iterator over the metadata of all known slots, each paired with the plugin ids of
+    /// all the hooks registered to the slot.
+    pub(crate) fn slot_providers(&self) -> impl Iterator<Item = (&SlotInfo, Vec<Id>)> {
+        self.slots.iter().map(|(slot, info)| {
+            let plugins = self
+                .slot_hooks
+                .get(slot)
+                .into_iter()
+                .flatten()
+                .flat_map(|(&plugin, hooks)| hooks.iter().map(move |_| plugin))
+                .collect();
+            (info, plugins)
+        })
     }
 
     /// Gets whether any hooks have been added by the specified plugin for a hook slot.
@@ -135,7 +756,7 @@ pub fn exists<Slot>(&self, plugin: Id) -> bool
     where
         Slot: HookSlot,
     {
-        let slot = Slot::id();
+        let slot = self.checked_id::<Slot>();
         self.get_first_hook(plugin, slot).is_some()
     }
 
@@ -145,15 +766,15 @@ pub fn exists_exact<Slot>(&self, plugin: Id, name: Option<Id>) -> bool
     where
         Slot: HookSlot,
     {
-        let slot = Slot::id();
+        let slot = self.checked_id::<Slot>();
         self.get_exact_hook(plugin, slot, name).is_some()
     }
 
-    fn get_first_hook(&self, plugin: Id, slot: TypeId) -> Option<&Hook<Id>> {
+    fn get_first_hook(&self, plugin: Id, slot: SlotId) -> Option<&Hook<Id>> {
         self.slot_hooks.get(&slot)?.get(&plugin)?.first()
     }
 
-    fn get_exact_hook(&self, plugin: Id, slot: TypeId, name: Option<Id>) -> Option<&Hook<Id>> {
+    fn get_exact_hook(&self, plugin: Id, slot: SlotId, name: Option<Id>) -> Option<&Hook<Id>> {
         self.slot_hooks
             .get(&slot)?
             .get(&plugin)?
@@ -161,7 +782,7 @@ fn get_exact_hook(&self, plugin: Id, slot: TypeId, name: Option<Id>) -> Option<&
             .find(|h| h.name == name)
     }
 
-    fn get_first_hook_mut(&mut self, plugin: Id, slot: TypeId) -> Option<&mut Hook<Id>> {
+    fn get_first_hook_mut(&mut self, plugin: Id, slot: SlotId) -> Option<&mut Hook<Id>> {
         self.slot_hooks
             .get_mut(&slot)?
             .get_mut(&plugin)?
@@ -171,7 +792,7 @@ fn get_first_hook_mut(&mut self, plugin: Id, slot: TypeId) -> Option<&mut Hook<I
     fn get_exact_hook_mut(
         &mut self,
         plugin: Id,
-        slot: TypeId,
+        slot: SlotId,
         name: Option<Id>,
     ) -> Option<&mut Hook<Id>> {
         self.slot_hooks
@@ -187,13 +808,8 @@ pub fn get_first<Slot>(&self, plugin: Id) -> Option<&Slot::TraitObject>
     where
         Slot: HookSlot,
     {
-        let slot = Slot::id();
-        Some(
-            self.get_first_hook(plugin, slot)?
-                .ptr
-                .downcast_ref::<Box<Slot::TraitObject>>()?
-                .as_ref(),
-        )
+        let slot = self.checked_id::<Slot>();
+        self.get_first_hook(plugin, slot)?.get()
     }
 
     /// Get the dyn object hook with the specified name added by a plugin for the hook slot.
@@ -202,31 +818,65 @@ pub fn get_exact<Slot>(&self, plugin: Id, name: Option<Id>) -> Option<&Slot::Tra
     where
         Slot: HookSlot,
     {
-        let slot = Slot::id();
-        Some(
-            self.get_exact_hook(plugin, slot, name)?
-                .ptr
-                .downcast_ref::<Box<Slot::TraitObject>>()?
-                .as_ref(),
-        )
+        let slot = self.checked_id::<Slot>();
+        self.get_exact_hook(plugin, slot, name)?.get()
+    }
+
+    /// Get the first dyn object hook added by a plugin for the hook slot, checking the slot's trait
+    /// object like [`HookRegistry::verify_slot`] instead of panicking in debug builds.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SlotTypeMismatch`] if the registry recorded a different trait object for the
+    /// slot's id.
+    pub fn try_get_first<Slot>(
+        &self,
+        plugin: Id,
+    ) -> Result<Option<&Slot::TraitObject>, SlotTypeMismatch>
+    where
+        Slot: HookSlot,
+    {
+        self.verify_slot::<Slot>()?;
+        Ok(self.get_first_hook(plugin, Slot::id()).and_then(Hook::get))
+    }
+
+    /// Get the dyn object hook with the specified name added by a plugin for the hook slot,
+    /// checking the slot's trait object like [`HookRegistry::verify_slot`] instead of panicking in
+    /// debug builds.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SlotTypeMismatch`] if the registry recorded a different trait object for the
+    /// slot's id.
+    pub fn try_get_exact<Slot>(
+        &self,
+        plugin: Id,
+        name: Option<Id>,
+    ) -> Result<Option<&Slot::TraitObject>, SlotTypeMismatch>
+    where
+        Slot: HookSlot,
+    {
+        self.verify_slot::<Slot>()?;
+        Ok(self
+            .get_exact_hook(plugin, Slot::id(), name)
+            .and_then(Hook::get))
     }
 
-    /// Get the first dyn mutable object hook added by a plugin for the hook slot.
+    /// Get the first dyn mutable object hook added by a plugin for the hook slot. Returns `None`
+    /// if the hook is shared, such as by a [`HookSnapshot`]; see
+    /// [`HookRegistry::try_get_first_mut`] to tell both cases apart.
     #[must_use]
     pub fn get_first_mut<Slot>(&mut self, plugin: Id) -> Option<&mut Slot::TraitObject>
     where
         Slot: HookSlot,
     {
-        let slot = Slot::id();
-        Some(
-            self.get_first_hook_mut(plugin, slot)?
-                .ptr
-                .downcast_mut::<Box<Slot::TraitObject>>()?
-                .as_mut(),
-        )
+        let slot = self.checked_id::<Slot>();
+        self.get_first_hook_mut(plugin, slot)?.get_unshared_mut()
     }
 
     /// Get the dyn mutable object hook with the specified name added by a plugin for the hook slot.
+    /// Returns `None` if the hook is shared, such as by a [`HookSnapshot`]; see
+    /// [`HookRegistry::try_get_exact_mut`] to tell both cases apart.
     #[must_use]
     pub fn get_exact_mut<Slot>(
         &mut self,
@@ -236,32 +886,126 @@ pub fn get_exact_mut<Slot>(
     where
         Slot: HookSlot,
     {
-        let slot = Slot::id();
-        Some(
-            self.get_exact_hook_mut(plugin, slot, name)?
-                .ptr
-                .downcast_mut::<Box<Slot::TraitObject>>()?
-                .as_mut(),
-        )
+        let slot = self.checked_id::<Slot>();
+        self.get_exact_hook_mut(plugin, slot, name)?
+            .get_unshared_mut()
+    }
+
+    /// Get the first dyn mutable object hook added by a plugin for the hook slot, checking the
+    /// slot's trait object like [`HookRegistry::verify_slot`] and whether the hook is shared
+    /// instead of panicking.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HookAccessError::Shared`] if the hook is shared, such as by a [`HookSnapshot`],
+    /// and [`HookAccessError::TypeMismatch`] if the registry recorded a different trait object for
+    /// the slot's id.
+    pub fn try_get_first_mut<Slot>(
+        &mut self,
+        plugin: Id,
+    ) -> Result<Option<&mut Slot::TraitObject>, HookAccessError<Id>>
+    where
+        Slot: HookSlot,
+    {
+        self.verify_slot::<Slot>()?;
+        match self.get_first_hook_mut(plugin, Slot::id()) {
+            Some(hook) => hook.get_mut(),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the dyn mutable object hook with the specified name added by a plugin for the hook slot,
+    /// checking the slot's trait object like [`HookRegistry::verify_slot`] and whether the hook is
+    /// shared instead of panicking.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`HookRegistry::try_get_first_mut`].
+    pub fn try_get_exact_mut<Slot>(
+        &mut self,
+        plugin: Id,
+        name: Option<Id>,
+    ) -> Result<Option<&mut Slot::TraitObject>, HookAccessError<Id>>
+    where
+        Slot: HookSlot,
+    {
+        self.verify_slot::<Slot>()?;
+        match self.get_exact_hook_mut(plugin, Slot::id(), name) {
+            Some(hook) => hook.get_mut(),
+            None => Ok(None),
+        }
+    }
+
+    /// Get a shared reference to the first dyn object hook added by a plugin for the hook slot with
+    /// [`HookRegistry::register_shared`]. The returned hook stays alive even if it is removed from
+    /// the registry.
+    #[must_use]
+    pub fn get_first_shared<Slot>(&self, plugin: Id) -> Option<Arc<Slot::TraitObject>>
+    where
+        Slot: HookSlot,
+    {
+        self.plugin_slot_hooks_shared::<Slot>(plugin).next()
+    }
+
+    /// Get a shared reference to the dyn object hook with the specified name added by a plugin for
+    /// the hook slot, if it was registered with [`HookRegistry::register_shared`]. The returned
+    /// hook stays alive even if it is removed from the registry.
+    #[must_use]
+    pub fn get_exact_shared<Slot>(
+        &self,
+        plugin: Id,
+        name: Option<Id>,
+    ) -> Option<Arc<Slot::TraitObject>>
+    where
+        Slot: HookSlot,
+    {
+        let slot = self.checked_id::<Slot>();
+        self.get_exact_hook(plugin, slot, name)?.get_shared()
     }
 
     /// Remove a specific hook from the registry matching the plugin id, name, and hook slot. If a
-    /// matching hook existed, returns the removed dyn object.
-    pub fn remove<Slot>(&mut self, plugin: Id, name: Option<Id>) -> Option<Box<Slot::TraitObject>>
+    /// matching hook existed, returns the removed dyn object. The hook is returned as an [`Arc`],
+    /// as a hook registered with [`HookRegistry::register_shared`] may still be shared by snapshots
+    /// taken before the removal or by the `_shared` accessors.
+    pub fn remove<Slot>(&mut self, plugin: Id, name: Option<Id>) -> Option<Arc<Slot::TraitObject>>
     where
         Slot: HookSlot,
     {
-        let slot = Slot::id();
+        let slot = self.checked_id::<Slot>();
         let plugin_hooks = self.slot_hooks.get_mut(&slot)?;
         let hooks = plugin_hooks.get_mut(&plugin)?;
         let idx = hooks.iter().position(|h| h.name == name)?;
-        Some(
-            *hooks
-                .swap_remove(idx)
-                .ptr
-                .downcast::<Box<Slot::TraitObject>>()
-                .ok()?,
-        )
+        // Hooks of a mismatched slot are left in place, as they cannot be returned
+        hooks[idx].get::<Slot::TraitObject>()?;
+        hooks.swap_remove(idx).into_shared()
+    }
+
+    /// Get the configuration the plugin host set for a plugin with
+    /// [`PluginRegistry::set_config`][crate::PluginRegistry::set_config], if any.
+    #[must_use]
+    pub fn config(&self, plugin: Id) -> Option<&PluginConfig> {
+        self.configs.get(&plugin)
+    }
+
+    pub(crate) fn set_config(&mut self, plugin: Id, config: PluginConfig) {
+        self.configs.insert(plugin, config);
+    }
+
+    pub(crate) fn remove_config(&mut self, plugin: Id) {
+        self.configs.remove(&plugin);
+    }
+
+    /// Get the [resources][crate::PluginResources] of the plugin the registry is passed to, such as
+    /// in [`Plugin::load`][crate::Plugin::load], or `None` outside of it. The plugin host reaches
+    /// them with [`PluginRegistry::resources`][crate::PluginRegistry::resources].
+    #[must_use]
+    pub fn plugin_resources(&mut self) -> Option<&mut PluginResources> {
+        self.resources.as_mut()
+    }
+
+    /// Take the resources given to a registry created with [`HookRegistry::staging`].
+    pub(crate) fn take_resources(&mut self) -> PluginResources {
+        self.resources.take().unwrap_or_default()
     }
 
     /// Remove all hooks added by a plugin.
@@ -269,6 +1013,7 @@ pub fn remove_plugin_hooks(&mut self, plugin: Id) {
         for plugin_hooks in self.slot_hooks.values_mut() {
             plugin_hooks.remove(&plugin);
         }
+        self.reset_breaker(plugin);
     }
 
     /// Shrink the capacities allocated internally by the registry.
@@ -303,19 +1048,35 @@ pub fn plugin_slot_hooks<Slot>(
         Slot: HookSlot,
     {
         self.slot_hooks
-            .get(&Slot::id())
+            .get(&self.checked_id::<Slot>())
             .into_iter()
             .flat_map(move |m| m.get(&plugin))
             .flatten()
-            .filter_map(|h| {
-                h.ptr
-                    .downcast_ref::<Box<Slot::TraitObject>>()
-                    .map(|b| b.as_ref())
-            })
+            .filter_map(Hook::get)
+    }
+
+    /// Get an iterator over shared references to the plugin hooks for the specified slot that were
+    /// registered with [`HookRegistry::register_shared`]. The returned hooks stay alive even if
+    /// they are removed from the registry.
+    #[must_use]
+    pub fn plugin_slot_hooks_shared<Slot>(
+        &self,
+        plugin: Id,
+    ) -> impl FusedIterator<Item = Arc<Slot::TraitObject>>
+    where
+        Slot: HookSlot,
+    {
+        self.slot_hooks
+            .get(&self.checked_id::<Slot>())
+            .into_iter()
+            .flat_map(move |m| m.get(&plugin))
+            .flatten()
+            .filter_map(Hook::get_shared)
     }
 
     /// Get an iterator over the mutable plugin hooks for the specified slot. This is often simply a
-    /// single hook unless unique names are used when registering multiple hooks.
+    /// single hook unless unique names are used when registering multiple hooks. Hooks that are
+    /// shared, such as by a [`HookSnapshot`], are skipped.
     #[must_use]
     pub fn plugin_slot_hooks_mut<Slot>(
         &mut self,
@@ -324,16 +1085,13 @@ pub fn plugin_slot_hooks_mut<Slot>(
     where
         Slot: HookSlot,
     {
+        let slot = self.checked_id::<Slot>();
         self.slot_hooks
-            .get_mut(&Slot::id())
+            .get_mut(&slot)
             .into_iter()
             .flat_map(move |m| m.get_mut(&plugin))
             .flatten()
-            .filter_map(|h| {
-                h.ptr
-                    .downcast_mut::<Box<Slot::TraitObject>>()
-                    .map(|b| b.as_mut())
-            })
+            .filter_map(Hook::get_unshared_mut)
     }
 
     /// Get an iterator over all the hooks from all plugins registered to a slot, including the id
@@ -344,18 +1102,54 @@ pub fn slot_hooks_and_plugin<Slot>(&self) -> impl FusedIterator<Item = (Id, &Slo
         Slot: HookSlot,
     {
         self.slot_hooks
-            .get(&Slot::id())
+            .get(&self.checked_id::<Slot>())
+            .into_iter()
+            .flatten()
+            .flat_map(|m| m.1.iter().filter_map(Hook::get).map(move |h| (*m.0, h)))
+    }
+
+    /// Get an iterator over all the hooks from all plugins registered to a slot like
+    /// [`HookRegistry::slot_hooks_and_plugin`], checking the slot's trait object like
+    /// [`HookRegistry::verify_slot`] instead of panicking in debug builds.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SlotTypeMismatch`] if the registry recorded a different trait object for the
+    /// slot's id.
+    pub fn try_slot_hooks_and_plugin<Slot>(
+        &self,
+    ) -> Result<impl FusedIterator<Item = (Id, &Slot::TraitObject)>, SlotTypeMismatch>
+    where
+        Slot: HookSlot,
+    {
+        self.verify_slot::<Slot>()?;
+        Ok(self.slot_hooks_and_plugin::<Slot>())
+    }
+
+    /// Get an iterator over shared references to all the hooks registered to a slot with
+    /// [`HookRegistry::register_shared`], including the id of the plugin that registered that
+    /// slot. The returned hooks stay alive even if they are removed from the registry.
+    #[must_use]
+    pub fn slot_hooks_and_plugin_shared<Slot>(
+        &self,
+    ) -> impl FusedIterator<Item = (Id, Arc<Slot::TraitObject>)>
+    where
+        Slot: HookSlot,
+    {
+        self.slot_hooks
+            .get(&self.checked_id::<Slot>())
             .into_iter()
             .flatten()
             .flat_map(|m| {
                 m.1.iter()
-                    .filter_map(|h| h.ptr.downcast_ref::<Box<Slot::TraitObject>>())
-                    .map(move |b| (*m.0, b.as_ref()))
+                    .filter_map(Hook::get_shared)
+                    .map(move |h| (*m.0, h))
             })
     }
 
-    /// Get an iterator over all the mutable hooks from all plugins registered to a slot, including \
-    /// the id of the plugin that registered that slot.
+    /// Get an iterator over all the mutable hooks from all plugins registered to a slot, including
+    /// the id of the plugin that registered that slot. Hooks that are shared, such as by a
+    /// [`HookSnapshot`], are skipped.
     #[must_use]
     pub fn slot_hooks_and_plugin_mut<Slot>(
         &mut self,
@@ -363,14 +1157,134 @@ pub fn slot_hooks_and_plugin_mut<Slot>(
     where
         Slot: HookSlot,
     {
+        let slot = self.checked_id::<Slot>();
         self.slot_hooks
-            .get_mut(&Slot::id())
+            .get_mut(&slot)
             .into_iter()
             .flatten()
             .flat_map(|m| {
                 m.1.iter_mut()
-                    .filter_map(|h| h.ptr.downcast_mut::<Box<Slot::TraitObject>>())
-                    .map(move |b| (*m.0, b.as_mut()))
+                    .filter_map(Hook::get_unshared_mut)
+                    .map(move |h| (*m.0, h))
+            })
+    }
+
+    /// Call `f` for every hook from all plugins registered to a slot and collect the results.
+    /// Results are gathered in the same order [`HookRegistry::slot_hooks_and_plugin`] produces the
+    /// hooks. The calls are profiled while profiling is enabled; see
+    /// [`HookRegistry::set_profiling`]. With a circuit breaker, hooks that panic are left out of
+    /// the results and suspended hooks are skipped; see [`HookRegistry::set_circuit_breaker`].
+    pub fn dispatch<Slot, R>(&self, mut f: impl FnMut(Id, &Slot::TraitObject) -> R) -> Vec<(Id, R)>
+    where
+        Slot: HookSlot,
+    {
+        let slot = self.checked_id::<Slot>();
+        dispatch::successes(dispatch::dispatch(
+            Monitors::new(&self.profiler, &self.breaker),
+            slot,
+            self.named_hooks::<Slot>(slot),
+            |plugin, hook| Ok(f(plugin, hook)),
+        ))
+    }
+
+    /// Call `f` for every mutable hook from all plugins registered to a slot and collect the
+    /// results. Results are gathered in the same order [`HookRegistry::slot_hooks_and_plugin_mut`]
+    /// produces the hooks, skipping shared hooks. The calls are profiled and protected like
+    /// [`HookRegistry::dispatch`].
+    pub fn dispatch_mut<Slot, R>(
+        &mut self,
+        mut f: impl FnMut(Id, &mut Slot::TraitObject) -> R,
+    ) -> Vec<(Id, R)>
+    where
+        Slot: HookSlot,
+    {
+        let slot = self.checked_id::<Slot>();
+        dispatch::successes(dispatch::dispatch(
+            Monitors::new(&self.profiler, &self.breaker),
+            slot,
+            Self::named_hooks_mut::<Slot>(&mut self.slot_hooks, slot),
+            |plugin, hook| Ok(f(plugin, hook)),
+        ))
+    }
+
+    /// Call the fallible `f` for every hook from all plugins registered to a slot and collect the
+    /// results like [`HookRegistry::dispatch`]. With a circuit breaker, both errors returned by
+    /// `f` and panics count as failures of the hook, and panics are returned as
+    /// [`HookFailure::Panicked`]; see [`HookRegistry::set_circuit_breaker`].
+    pub fn try_dispatch<Slot, R, E>(
+        &self,
+        f: impl FnMut(Id, &Slot::TraitObject) -> Result<R, E>,
+    ) -> Vec<(Id, Result<R, HookFailure<E>>)>
+    where
+        Slot: HookSlot,
+        E: Display,
+    {
+        let slot = self.checked_id::<Slot>();
+        dispatch::dispatch(
+            Monitors::new(&self.profiler, &self.breaker),
+            slot,
+            self.named_hooks::<Slot>(slot),
+            f,
+        )
+    }
+
+    /// Call the fallible `f` for every mutable hook from all plugins registered to a slot and
+    /// collect the results like [`HookRegistry::try_dispatch`], skipping shared hooks like
+    /// [`HookRegistry::dispatch_mut`].
+    pub fn try_dispatch_mut<Slot, R, E>(
+        &mut self,
+        f: impl FnMut(Id, &mut Slot::TraitObject) -> Result<R, E>,
+    ) -> Vec<(Id, Result<R, HookFailure<E>>)>
+    where
+        Slot: HookSlot,
+        E: Display,
+    {
+        let slot = self.checked_id::<Slot>();
+        dispatch::dispatch(
+            Monitors::new(&self.profiler, &self.breaker),
+            slot,
+            Self::named_hooks_mut::<Slot>(&mut self.slot_hooks, slot),
+            f,
+        )
+    }
+
+    /// Get an iterator over all the hooks registered to a slot along with their plugin id and name.
+    fn named_hooks<Slot>(
+        &self,
+        slot: SlotId,
+    ) -> impl Iterator<Item = (Id, Option<Id>, &Slot::TraitObject)>
+    where
+        Slot: HookSlot,
+    {
+        self.slot_hooks
+            .get(&slot)
+            .into_iter()
+            .flatten()
+            .flat_map(|m| {
+                m.1.iter()
+                    .filter_map(move |h| Some((*m.0, h.name, h.get::<Slot::TraitObject>()?)))
+            })
+    }
+
+    /// Get an iterator over all the mutable hooks registered to a slot along with their plugin id
+    /// and name. Takes the hooks rather than the registry so that the profiler can still be
+    /// borrowed.
+    fn named_hooks_mut<Slot>(
+        slot_hooks: &mut HookIndex<Id, S>,
+        slot: SlotId,
+    ) -> impl Iterator<Item = (Id, Option<Id>, &mut Slot::TraitObject)>
+    where
+        Slot: HookSlot,
+    {
+        slot_hooks
+            .get_mut(&slot)
+            .into_iter()
+            .flatten()
+            .flat_map(|m| {
+                m.1.iter_mut().filter_map(|h| {
+                    let name = h.name;
+                    Some((*m.0, name, h.get_unshared_mut::<Slot::TraitObject>()?))
+                })
             })
     }
 }
@@ -380,25 +1294,193 @@ impl<Id, S> HookRegistry<Id, S>
     Id: Copy + Ord + Hash,
     S: BuildHasher + Default,
 {
+    /// Declare a hook slot to the registry, recording the slot's metadata even before any hook is
+    /// registered to it. This allows [`PluginRegistry::validate`][crate::PluginRegistry::validate]
+    /// to report [`SlotCardinality::ExactlyOne`] slots that no plugin provides.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SlotTypeMismatch`] if the slot's id was already used with a different trait
+    /// object.
+    pub fn declare_slot<Slot>(&mut self) -> Result<(), SlotTypeMismatch>
+    where
+        Slot: HookSlot,
+    {
+        self.slots
+            .entry(Slot::id())
+            .or_insert_with(SlotInfo::of::<Slot>);
+        self.verify_slot::<Slot>()
+    }
+
+    fn check_register<Slot>(
+        &mut self,
+        plugin: Id,
+        name: Option<Id>,
+    ) -> Result<(), RegisterHookError<Id>>
+    where
+        Slot: HookSlot,
+    {
+        let info = *self
+            .slots
+            .entry(Slot::id())
+            .or_insert_with(SlotInfo::of::<Slot>);
+        self.verify_slot::<Slot>()?;
+        self.check_hook(&info, plugin, name)
+    }
+
+    /// Check that a hook named `name` of `plugin` can be added to the slot described by `info`
+    /// alongside the hooks already registered to it.
+    fn check_hook(
+        &self,
+        info: &SlotInfo,
+        plugin: Id,
+        name: Option<Id>,
+    ) -> Result<(), RegisterHookError<Id>> {
+        let Some(plugin_hooks) = self.slot_hooks.get(&info.id) else {
+            return Ok(());
+        };
+        if plugin_hooks
+            .get(&plugin)
+            .is_some_and(|hooks| hooks.iter().any(|h| h.name == name))
+        {
+            return Err(RegisterHookError::Duplicate {
+                slot: info.name,
+                plugin,
+                name,
+            });
+        }
+        if info.cardinality.is_single() {
+            if let Some((&existing, _)) = plugin_hooks.iter().find(|(_, hooks)| !hooks.is_empty()) {
+                return Err(RegisterHookError::CardinalityExceeded {
+                    slot: info.name,
+                    plugin,
+                    existing,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, hook: Hook<Id>) {
+        self.slot_hooks
+            .entry(hook.slot)
+            .or_default()
+            .entry(hook.plugin)
+            .or_default()
+            .push(hook);
+    }
+
     /// Register a hook for a slot with the given plugin and optional name.
+    ///
+    /// # Errors
+    ///
+    /// Returns the hook back in a [`RejectedHook`] if it could not be registered. The error is
+    /// [`RegisterHookError::Duplicate`] if the plugin already registered a hook with the same name
+    /// for the slot, [`RegisterHookError::CardinalityExceeded`] if the slot accepts at most one
+    /// hook and already has one, or [`RegisterHookError::TypeMismatch`] if the slot's id was first
+    /// used with a different trait object.
     pub fn register<Slot>(
         &mut self,
         hook: Box<Slot::TraitObject>,
         plugin: Id,
         name: Option<Id>,
-    ) -> Result<(), Box<Slot::TraitObject>>
+    ) -> Result<(), RejectedHook<Id, Box<Slot::TraitObject>>>
+    where
+        Slot: HookSlot,
+    {
+        match self.check_register::<Slot>(plugin, name) {
+            Ok(()) => {
+                let library = self.library.clone();
+                self.insert(Hook::owned(plugin, Slot::id(), name, hook, library));
+                Ok(())
+            }
+            Err(error) => Err(RejectedHook { hook, error }),
+        }
+    }
+
+    /// Register a hook shared through an [`Arc`] for a slot with the given plugin and optional
+    /// name. Unlike hooks owned by the registry, shared hooks are returned by the `_shared`
+    /// accessors such as [`HookRegistry::get_first_shared`] and included in snapshots taken with
+    /// [`HookRegistry::snapshot`]. The registry keeps a clone of the [`Arc`], so the hook cannot be
+    /// borrowed mutably while any other clone is alive.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`HookRegistry::register`].
+    pub fn register_shared<Slot>(
+        &mut self,
+        hook: Arc<Slot::TraitObject>,
+        plugin: Id,
+        name: Option<Id>,
+    ) -> Result<(), RejectedHook<Id, Arc<Slot::TraitObject>>>
     where
         Slot: HookSlot,
     {
-        let slot = Slot::id();
-        let plugin_hooks = self.slot_hooks.entry(slot).or_default();
-        let hooks = plugin_hooks.entry(plugin).or_default();
-        if !hooks.iter().any(|h| h.name == name) {
-            hooks.push(Hook::new(plugin, slot, name, Box::new(hook)));
-            Ok(())
-        } else {
-            Err(hook)
+        match self.check_register::<Slot>(plugin, name) {
+            Ok(()) => {
+                let library = self.library.clone();
+                self.insert(Hook::shared(plugin, Slot::id(), name, hook, library));
+                Ok(())
+            }
+            Err(error) => Err(RejectedHook { hook, error }),
+        }
+    }
+
+    /// Create an empty registry that knows all the slots of this registry, so that hooks registered
+    /// to it are checked against the slots' trait objects before being [appended][Self::append].
+    /// The registry is used on behalf of `plugin` with empty resources, and hooks are attached to
+    /// `library` like [`HookRegistry::with_plugin`].
+    pub(crate) fn staging(&self, plugin: Id, library: Option<LibraryHandle>) -> Self
+    where
+        S: Clone,
+    {
+        Self {
+            slot_hooks: HashMap::with_hasher(self.slot_hooks.hasher().clone()),
+            slots: self.slots.clone(),
+            services: self.services.clone(),
+            dependencies: self.dependencies.clone(),
+            configs: self.configs.clone(),
+            profiler: None,
+            breaker: None,
+            caller: Some(plugin),
+            resources: Some(PluginResources::default()),
+            library,
+        }
+    }
+
+    /// Move all hooks registered in `other` into this registry. Hooks are checked against the
+    /// hooks of this registry like [`HookRegistry::register`], and nothing is moved if any hook is
+    /// rejected.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RegisterHookError::TypeMismatch`] if a slot of `other` was recorded with a
+    /// different signature or trait object, and otherwise the same errors as
+    /// [`HookRegistry::register`] for the first hook that cannot be added.
+    pub(crate) fn append(&mut self, other: Self) -> Result<(), RegisterHookError<Id>> {
+        for (slot, other_plugin_hooks) in &other.slot_hooks {
+            let Some(info) = self.slots.get(slot) else {
+                continue;
+            };
+            if let Some(mismatch) = info.conflict(&other.slots[slot]) {
+                return Err(mismatch.into());
+            }
+            for hook in other_plugin_hooks.values().flatten() {
+                self.check_hook(info, hook.plugin, hook.name)?;
+            }
         }
+
+        for (slot, other_plugin_hooks) in other.slot_hooks {
+            let plugin_hooks = self.slot_hooks.entry(slot).or_default();
+            for (plugin, hooks) in other_plugin_hooks {
+                plugin_hooks.entry(plugin).or_default().extend(hooks);
+            }
+        }
+        for (slot, info) in other.slots {
+            self.slots.entry(slot).or_insert(info);
+        }
+        self.append_services(other.services);
+        Ok(())
     }
 }
 
@@ -409,6 +1491,350 @@ impl<Id, S> Default for HookRegistry<Id, S>
     fn default() -> Self {
         Self {
             slot_hooks: HashMap::default(),
+            slots: HashMap::default(),
+            services: HashMap::default(),
+            dependencies: HashMap::default(),
+            configs: HashMap::default(),
+            profiler: None,
+            breaker: None,
+            caller: None,
+            resources: None,
+            library: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        HookAccessError, HookRegistry, HookSlot, RegisterHookError, SlotCardinality, SlotId,
+        SlotTypeMismatch, hook_slot,
+    };
+    use std::sync::Arc;
+    use std::thread;
+
+    trait Greeter: Send + Sync {
+        fn greet(&self) -> String;
+    }
+
+    impl Greeter for String {
+        fn greet(&self) -> String {
+            format!("hello {self}")
+        }
+    }
+
+    hook_slot!(GreeterSlot: dyn Greeter);
+
+    hook_slot!(SingleGreeterSlot: dyn Greeter {
+        name: "single greeter",
+        cardinality: AtMostOne,
+    });
+
+    hook_slot!(AliasSlot: dyn Fn() -> String + Send + Sync {
+        id: "biner::hook::tests::GreeterSlot",
+    });
+
+    #[test]
+    fn stable_slot_ids() {
+        assert_eq!(
+            GreeterSlot::id(),
+            SlotId::new("biner::hook::tests::GreeterSlot")
+        );
+        assert_eq!(GreeterSlot::id(), AliasSlot::id());
+        assert_ne!(GreeterSlot::id(), SingleGreeterSlot::id());
+        // FNV-1a reference value
+        assert_eq!(SlotId::new("a").stable_hash(), 0xaf63_dc4c_8601_ec8c);
+
+        // Slots implemented by hand default to their type name
+        struct ManualSlot;
+
+        impl HookSlot for ManualSlot {
+            type TraitObject = dyn Greeter;
         }
+
+        assert_eq!(
+            ManualSlot::id(),
+            SlotId::new(std::any::type_name::<ManualSlot>())
+        );
+        assert_ne!(ManualSlot::id(), GreeterSlot::id());
+
+        let mut hooks = HookRegistry::new();
+        assert!(hooks.verify_slot::<AliasSlot>().is_ok());
+        assert!(
+            hooks
+                .register::<GreeterSlot>(Box::new("a".to_owned()), "a", None)
+                .is_ok()
+        );
+        assert!(hooks.verify_slot::<GreeterSlot>().is_ok());
+        let mismatch = hooks.verify_slot::<AliasSlot>().unwrap_err();
+        assert_eq!(mismatch.slot, GreeterSlot::id());
+        assert!(mismatch.expected.ends_with("Greeter"));
+        assert!(
+            hooks
+                .slot_info::<AliasSlot>()
+                .is_some_and(|info| !info.matches::<AliasSlot>())
+        );
+    }
+
+    #[test]
+    fn mismatched_slot_signatures() {
+        hook_slot!(GreeterV2Slot: dyn Greeter {
+            id: "biner::hook::tests::GreeterSlot",
+            signature: "Greeter v2",
+        });
+
+        assert_eq!(GreeterSlot::signature(), "dyn Greeter");
+        assert_eq!(GreeterV2Slot::signature(), "Greeter v2");
+
+        let mut hooks = HookRegistry::new();
+        assert!(
+            hooks
+                .register::<GreeterSlot>(Box::new("a".to_owned()), "a", None)
+                .is_ok()
+        );
+        let mismatch = hooks.verify_slot::<GreeterV2Slot>().unwrap_err();
+        assert_eq!(mismatch.expected_signature, "dyn Greeter");
+        assert_eq!(mismatch.found_signature, "Greeter v2");
+        assert!(hooks.try_get_first::<GreeterV2Slot>("a").is_err());
+
+        // Hooks built against another signature are rejected when merged
+        let mut other = HookRegistry::new();
+        assert!(
+            other
+                .register::<GreeterV2Slot>(Box::new("b".to_owned()), "b", None)
+                .is_ok()
+        );
+        assert_eq!(
+            hooks.append(other),
+            Err(RegisterHookError::TypeMismatch(mismatch))
+        );
+        assert!(!hooks.exists::<GreeterSlot>("b"));
+
+        // As are hooks colliding with an existing hook
+        let mut other = HookRegistry::new();
+        assert!(
+            other
+                .register::<GreeterSlot>(Box::new("c".to_owned()), "c", None)
+                .is_ok()
+        );
+        assert!(
+            other
+                .register::<GreeterSlot>(Box::new("a".to_owned()), "a", None)
+                .is_ok()
+        );
+        assert!(matches!(
+            hooks.append(other),
+            Err(RegisterHookError::Duplicate {
+                plugin: "a",
+                name: None,
+                ..
+            })
+        ));
+        assert!(!hooks.exists::<GreeterSlot>("c"));
+    }
+
+    #[test]
+    fn mismatched_slot_types() {
+        let mut hooks = HookRegistry::new();
+        assert!(
+            hooks
+                .register::<GreeterSlot>(Box::new("a".to_owned()), "a", None)
+                .is_ok()
+        );
+        let rejected = hooks
+            .register::<AliasSlot>(Box::new(|| "b".to_owned()), "b", None)
+            .unwrap_err();
+        assert_eq!((rejected.hook)(), "b");
+        assert!(matches!(
+            rejected.error,
+            RegisterHookError::TypeMismatch(SlotTypeMismatch { slot, .. }) if slot == GreeterSlot::id()
+        ));
+        assert!(hooks.declare_slot::<AliasSlot>().is_err());
+        assert!(hooks.try_get_first::<AliasSlot>("a").is_err());
+        assert!(hooks.try_slot_hooks_and_plugin::<AliasSlot>().is_err());
+        assert_eq!(
+            hooks
+                .try_get_first::<GreeterSlot>("a")
+                .unwrap()
+                .unwrap()
+                .greet(),
+            "hello a"
+        );
+        assert!(hooks.snapshot().verify_slot::<AliasSlot>().is_err());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic = "holds hooks of type"]
+    fn mismatched_slot_lookup_panics_in_debug() {
+        let mut hooks = HookRegistry::new();
+        assert!(hooks.declare_slot::<GreeterSlot>().is_ok());
+        let _ = hooks.get_first::<AliasSlot>("a");
+    }
+
+    #[test]
+    fn slot_metadata() {
+        assert!(GreeterSlot::name().ends_with("GreeterSlot"));
+        assert_eq!(GreeterSlot::description(), "");
+        assert_eq!(GreeterSlot::cardinality(), SlotCardinality::Many);
+        assert_eq!(SingleGreeterSlot::name(), "single greeter");
+        assert_eq!(SingleGreeterSlot::cardinality(), SlotCardinality::AtMostOne);
+    }
+
+    #[test]
+    fn register_enforces_cardinality() {
+        let mut hooks = HookRegistry::new();
+        assert!(hooks.slot_info::<SingleGreeterSlot>().is_none());
+        assert!(
+            hooks
+                .register::<SingleGreeterSlot>(Box::new("a".to_owned()), "a", None)
+                .is_ok()
+        );
+        assert_eq!(
+            hooks.slot_info::<SingleGreeterSlot>().unwrap().name(),
+            "single greeter"
+        );
+
+        let rejected = hooks
+            .register::<SingleGreeterSlot>(Box::new("b".to_owned()), "b", None)
+            .unwrap_err();
+        assert_eq!(rejected.hook.greet(), "hello b");
+        assert_eq!(
+            rejected.error,
+            RegisterHookError::CardinalityExceeded {
+                slot: "single greeter",
+                plugin: "b",
+                existing: "a",
+            }
+        );
+        assert_eq!(
+            rejected.to_string(),
+            "slot `single greeter` accepts at most one hook, but plugin `a` already registered one \
+             before plugin `b`"
+        );
+
+        let rejected = hooks
+            .register::<SingleGreeterSlot>(Box::new("a".to_owned()), "a", None)
+            .unwrap_err();
+        assert!(matches!(
+            rejected.error,
+            RegisterHookError::Duplicate {
+                plugin: "a",
+                name: None,
+                ..
+            }
+        ));
+
+        // Many slots accept hooks from any number of plugins
+        assert!(
+            hooks
+                .register::<GreeterSlot>(Box::new("a".to_owned()), "a", None)
+                .is_ok()
+        );
+        assert!(
+            hooks
+                .register::<GreeterSlot>(Box::new("b".to_owned()), "b", None)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn shared_hooks_outlive_removal() {
+        let mut hooks = HookRegistry::new();
+        let greeter: Arc<dyn Greeter> = Arc::new("world".to_owned());
+        assert!(
+            hooks
+                .register_shared::<GreeterSlot>(greeter, "a", None)
+                .is_ok()
+        );
+
+        let greeter = hooks.get_first_shared::<GreeterSlot>("a").unwrap();
+        // The registry cannot hand out mutable access while a clone is alive
+        assert_eq!(
+            hooks.try_get_first_mut::<GreeterSlot>("a").err(),
+            Some(HookAccessError::Shared {
+                slot: GreeterSlot::id(),
+                plugin: "a",
+                name: None,
+            })
+        );
+
+        hooks.remove_plugin_hooks("a");
+        assert!(!hooks.exists::<GreeterSlot>("a"));
+        assert_eq!(
+            thread::spawn(move || greeter.greet()).join().unwrap(),
+            "hello world"
+        );
+    }
+
+    #[test]
+    fn register_shared_hook() {
+        let mut hooks = HookRegistry::new();
+        let greeter: Arc<dyn Greeter> = Arc::new("shared".to_owned());
+        assert!(
+            hooks
+                .register_shared::<GreeterSlot>(greeter.clone(), "a", None)
+                .is_ok()
+        );
+        assert!(
+            hooks
+                .register_shared::<GreeterSlot>(greeter.clone(), "a", None)
+                .is_err()
+        );
+        assert!(Arc::ptr_eq(
+            &hooks.get_first_shared::<GreeterSlot>("a").unwrap(),
+            &greeter
+        ));
+        assert_eq!(
+            hooks
+                .slot_hooks_and_plugin_shared::<GreeterSlot>()
+                .map(|(plugin, hook)| (plugin, hook.greet()))
+                .collect::<Vec<_>>(),
+            vec![("a", "hello shared".to_owned())]
+        );
+
+        drop(greeter);
+        assert!(hooks.get_first_mut::<GreeterSlot>("a").is_some());
+    }
+
+    #[test]
+    fn mutable_access_skips_shared_hooks() {
+        let mut hooks = HookRegistry::new();
+        assert!(
+            hooks
+                .register::<GreeterSlot>(Box::new("a".to_owned()), "a", None)
+                .is_ok()
+        );
+        let greeter: Arc<dyn Greeter> = Arc::new("b".to_owned());
+        assert!(
+            hooks
+                .register_shared::<GreeterSlot>(greeter, "b", None)
+                .is_ok()
+        );
+        assert!(hooks.get_first_shared::<GreeterSlot>("a").is_none());
+
+        // Only the shared hook is in the snapshot, so only the owned hook can be borrowed mutably
+        let snapshot = hooks.snapshot();
+        assert_eq!(
+            snapshot
+                .slot_hooks_and_plugin::<GreeterSlot>()
+                .map(|(plugin, _)| plugin)
+                .collect::<Vec<_>>(),
+            ["b"]
+        );
+        assert!(hooks.get_first_mut::<GreeterSlot>("b").is_none());
+        assert_eq!(
+            hooks.dispatch_mut::<GreeterSlot, _>(|_, hook| hook.greet()),
+            vec![("a", "hello a".to_owned())]
+        );
+
+        drop(snapshot);
+        let mut greeted = hooks.dispatch_mut::<GreeterSlot, _>(|plugin, _| plugin);
+        greeted.sort_unstable();
+        assert_eq!(greeted, [("a", "a"), ("b", "b")]);
+        assert_eq!(
+            hooks.remove::<GreeterSlot>("a", None).unwrap().greet(),
+            "hello a"
+        );
     }
 }
@@ -0,0 +1,375 @@
+use super::dispatch::HookKey;
+use super::{HookRegistry, HookSlot, SlotId};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Formatter};
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Mutex, PoisonError};
+use thiserror::Error;
+
+/// What a [`CircuitBreaker`] does when a hook fails too many times in a row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum BreakerAction {
+    /// Suspend only the failing hook.
+    #[default]
+    SuspendHook,
+    /// Suspend all hooks of the plugin that registered the failing hook and request that the
+    /// plugin be disabled. This is only a request, not an action: the plugin stays enabled until
+    /// the host calls
+    /// [`PluginRegistry::handle_tripped_hooks`][crate::PluginRegistry::handle_tripped_hooks], which
+    /// disables it; the dispatch helpers cannot disable plugins as they only borrow the hooks.
+    RequestDisable,
+}
+
+/// Configuration of the circuit breaker of a [`HookRegistry`], set with
+/// [`HookRegistry::set_circuit_breaker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CircuitBreaker {
+    /// Number of consecutive failures after which a hook trips the breaker. A successful call
+    /// resets the count.
+    pub threshold: u32,
+    /// What to do when a hook trips the breaker.
+    pub action: BreakerAction,
+}
+
+impl CircuitBreaker {
+    /// Create a circuit breaker tripping after `threshold` consecutive failures of a hook.
+    #[must_use]
+    pub fn new(threshold: u32, action: BreakerAction) -> Self {
+        Self { threshold, action }
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(3, BreakerAction::SuspendHook)
+    }
+}
+
+/// A failed call of a hook by the dispatch helpers of a [`HookRegistry`] with a circuit breaker.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Error)]
+pub enum HookFailure<E> {
+    /// The hook returned an error.
+    #[error("{0}")]
+    Error(E),
+    /// The hook panicked, with the panic message if the panic payload was a string.
+    #[error("hook panicked: {0}")]
+    Panicked(String),
+}
+
+/// An event reported when a hook trips the circuit breaker of a [`HookRegistry`], returned by
+/// [`HookRegistry::take_tripped_hooks`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HookTripped<Id> {
+    /// Slot the hook is registered to.
+    pub slot: SlotId,
+    /// Plugin id of the plugin that registered the hook.
+    pub plugin: Id,
+    /// Name of the hook, if any.
+    pub name: Option<Id>,
+    /// Number of consecutive failures of the hook.
+    pub failures: u32,
+    /// Explanation of the last failure.
+    pub reason: String,
+    /// What the breaker did.
+    pub action: BreakerAction,
+}
+
+/// The state of the circuit breaker of a hook registry.
+pub(super) struct HookBreaker<Id> {
+    config: CircuitBreaker,
+    state: Mutex<BreakerState<Id>>,
+}
+
+struct BreakerState<Id> {
+    failures: HashMap<HookKey<Id>, u32>,
+    suspended_hooks: HashSet<HookKey<Id>>,
+    suspended_plugins: HashSet<Id>,
+    tripped: Vec<HookTripped<Id>>,
+}
+
+impl<Id> HookBreaker<Id>
+where
+    Id: Copy + Eq + Hash,
+{
+    fn new(config: CircuitBreaker) -> Self {
+        Self {
+            config,
+            state: Mutex::new(BreakerState {
+                failures: HashMap::new(),
+                suspended_hooks: HashSet::new(),
+                suspended_plugins: HashSet::new(),
+                tripped: Vec::new(),
+            }),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, BreakerState<Id>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Collect the hooks that are not suspended.
+    pub(super) fn admit<H>(
+        &self,
+        slot: SlotId,
+        hooks: impl Iterator<Item = (Id, Option<Id>, H)>,
+    ) -> Vec<(Id, Option<Id>, H)> {
+        let state = self.state();
+        hooks
+            .filter(|&(plugin, name, _)| {
+                !state.suspended_plugins.contains(&plugin)
+                    && !state.suspended_hooks.contains(&(slot, plugin, name))
+            })
+            .collect()
+    }
+
+    /// Record the outcomes of calls of hooks, tripping the breaker for hooks failing too often.
+    pub(super) fn record(
+        &self,
+        slot: SlotId,
+        outcomes: impl IntoIterator<Item = (Id, Option<Id>, Option<String>)>,
+    ) {
+        let mut state = self.state();
+        for (plugin, name, failure) in outcomes {
+            let key = (slot, plugin, name);
+            let Some(reason) = failure else {
+                state.failures.remove(&key);
+                continue;
+            };
+            let failures = state.failures.entry(key).or_default();
+            *failures += 1;
+            let failures = *failures;
+            if failures < self.config.threshold {
+                continue;
+            }
+            state.failures.remove(&key);
+            match self.config.action {
+                BreakerAction::SuspendHook => state.suspended_hooks.insert(key),
+                BreakerAction::RequestDisable => state.suspended_plugins.insert(plugin),
+            };
+            state.tripped.push(HookTripped {
+                slot,
+                plugin,
+                name,
+                failures,
+                reason,
+                action: self.config.action,
+            });
+        }
+    }
+}
+
+impl<Id> Debug for HookBreaker<Id> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HookBreaker")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<Id, S> HookRegistry<Id, S>
+where
+    Id: Copy + Ord + Hash,
+    S: BuildHasher,
+{
+    /// Set or remove the circuit breaker, which is disabled by default. With a circuit breaker,
+    /// the dispatch helpers such as [`HookRegistry::dispatch`] catch panics of hooks, and
+    /// [`HookRegistry::try_dispatch`] also counts the errors returned by hooks. A hook failing
+    /// [`CircuitBreaker::threshold`] times in a row trips the breaker: depending on
+    /// [`CircuitBreaker::action`], the hook or all hooks of its plugin are suspended and skipped by
+    /// the dispatch helpers, and a [`HookTripped`] event is queued for
+    /// [`HookRegistry::take_tripped_hooks`]. Hooks accessed directly, such as with
+    /// [`HookRegistry::slot_hooks_and_plugin`] or through a [`HookSnapshot`][super::HookSnapshot],
+    /// are neither protected nor suspended, and their calls are not profiled.
+    ///
+    /// Changing the configuration keeps the failure counts and suspended hooks, while removing the
+    /// breaker discards them.
+    pub fn set_circuit_breaker(&mut self, breaker: Option<CircuitBreaker>) {
+        self.breaker = match (breaker, self.breaker.take()) {
+            (None, _) => None,
+            (Some(config), Some(mut breaker)) => {
+                breaker.config = config;
+                Some(breaker)
+            }
+            (Some(config), None) => Some(HookBreaker::new(config)),
+        };
+    }
+
+    /// Get the configuration of the circuit breaker, if one is set.
+    #[must_use]
+    pub fn circuit_breaker(&self) -> Option<CircuitBreaker> {
+        self.breaker.as_ref().map(|breaker| breaker.config)
+    }
+
+    /// Take the events of all hooks that tripped the circuit breaker since the last call. Events
+    /// taken here no longer reach
+    /// [`PluginRegistry::handle_tripped_hooks`][crate::PluginRegistry::handle_tripped_hooks], so
+    /// the disable requested by [`BreakerAction::RequestDisable`] is not fulfilled.
+    pub fn take_tripped_hooks(&mut self) -> Vec<HookTripped<Id>> {
+        self.breaker
+            .as_mut()
+            .map(|breaker| std::mem::take(&mut breaker.state().tripped))
+            .unwrap_or_default()
+    }
+
+    /// Determine whether a hook of the plugin is suspended by the circuit breaker, either on its
+    /// own or along with all hooks of the plugin.
+    #[must_use]
+    pub fn is_suspended<Slot>(&self, plugin: Id, name: Option<Id>) -> bool
+    where
+        Slot: HookSlot,
+    {
+        self.breaker.as_ref().is_some_and(|breaker| {
+            let state = breaker.state();
+            state.suspended_plugins.contains(&plugin)
+                || state.suspended_hooks.contains(&(Slot::id(), plugin, name))
+        })
+    }
+
+    /// Determine whether all hooks of the plugin are suspended by the circuit breaker.
+    #[must_use]
+    pub fn is_plugin_suspended(&self, plugin: Id) -> bool {
+        self.breaker
+            .as_ref()
+            .is_some_and(|breaker| breaker.state().suspended_plugins.contains(&plugin))
+    }
+
+    /// Resume a hook suspended by the circuit breaker, returning whether it was suspended on its
+    /// own.
+    pub fn resume_hook<Slot>(&mut self, plugin: Id, name: Option<Id>) -> bool
+    where
+        Slot: HookSlot,
+    {
+        self.breaker.as_mut().is_some_and(|breaker| {
+            breaker
+                .state()
+                .suspended_hooks
+                .remove(&(Slot::id(), plugin, name))
+        })
+    }
+
+    /// Resume all hooks of the plugin suspended by the circuit breaker, returning whether any were
+    /// suspended.
+    pub fn resume_plugin(&mut self, plugin: Id) -> bool {
+        self.breaker.as_mut().is_some_and(|breaker| {
+            let mut state = breaker.state();
+            let suspended = state.suspended_hooks.len();
+            state.suspended_hooks.retain(|&(_, p, _)| p != plugin);
+            state.suspended_plugins.remove(&plugin) || state.suspended_hooks.len() != suspended
+        })
+    }
+
+    /// Forget the failures and suspensions of the hooks of the plugin.
+    pub(crate) fn reset_breaker(&mut self, plugin: Id) {
+        if let Some(breaker) = &mut self.breaker {
+            let mut state = breaker.state();
+            state.failures.retain(|&(_, p, _), _| p != plugin);
+            state.suspended_hooks.retain(|&(_, p, _)| p != plugin);
+            state.suspended_plugins.remove(&plugin);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        BreakerAction, CircuitBreaker, HookFailure, HookRegistry, HookSlot, HookTripped, hook_slot,
+    };
+
+    trait Handler: Send + Sync {
+        fn handle(&self) -> Result<u32, String>;
+    }
+
+    struct Healthy;
+
+    impl Handler for Healthy {
+        fn handle(&self) -> Result<u32, String> {
+            Ok(1)
+        }
+    }
+
+    struct Flaky;
+
+    impl Handler for Flaky {
+        fn handle(&self) -> Result<u32, String> {
+            Err("timed out".to_owned())
+        }
+    }
+
+    struct Panicking;
+
+    impl Handler for Panicking {
+        fn handle(&self) -> Result<u32, String> {
+            panic!("handler crashed")
+        }
+    }
+
+    hook_slot!(HandlerSlot: dyn Handler);
+
+    #[test]
+    fn trip_circuit_breaker() {
+        let mut hooks = HookRegistry::new();
+        hooks
+            .register::<HandlerSlot>(Box::new(Healthy), "healthy", None)
+            .unwrap();
+        hooks
+            .register::<HandlerSlot>(Box::new(Flaky), "flaky", None)
+            .unwrap();
+        hooks
+            .register::<HandlerSlot>(Box::new(Panicking), "panicking", None)
+            .unwrap();
+        hooks.set_circuit_breaker(Some(CircuitBreaker::new(2, BreakerAction::SuspendHook)));
+
+        let mut results = hooks.try_dispatch::<HandlerSlot, _, _>(|_, hook| hook.handle());
+        results.sort_unstable_by_key(|(plugin, _)| *plugin);
+        assert_eq!(
+            results,
+            vec![
+                ("flaky", Err(HookFailure::Error("timed out".to_owned()))),
+                ("healthy", Ok(1)),
+                (
+                    "panicking",
+                    Err(HookFailure::Panicked("handler crashed".to_owned()))
+                ),
+            ]
+        );
+        assert!(hooks.take_tripped_hooks().is_empty());
+
+        let _ = hooks.try_dispatch::<HandlerSlot, _, _>(|_, hook| hook.handle());
+        let mut tripped = hooks.take_tripped_hooks();
+        tripped.sort_unstable_by_key(|event| event.plugin);
+        assert_eq!(
+            tripped,
+            vec![
+                HookTripped {
+                    slot: HandlerSlot::id(),
+                    plugin: "flaky",
+                    name: None,
+                    failures: 2,
+                    reason: "timed out".to_owned(),
+                    action: BreakerAction::SuspendHook,
+                },
+                HookTripped {
+                    slot: HandlerSlot::id(),
+                    plugin: "panicking",
+                    name: None,
+                    failures: 2,
+                    reason: "hook panicked: handler crashed".to_owned(),
+                    action: BreakerAction::SuspendHook,
+                },
+            ]
+        );
+        assert!(hooks.is_suspended::<HandlerSlot>("flaky", None));
+        assert_eq!(
+            hooks.dispatch::<HandlerSlot, _>(|_, hook| hook.handle().is_ok()),
+            vec![("healthy", true)]
+        );
+
+        assert!(hooks.resume_hook::<HandlerSlot>("flaky", None));
+        assert_eq!(
+            hooks
+                .try_dispatch::<HandlerSlot, _, _>(|_, hook| hook.handle())
+                .len(),
+            2
+        );
+    }
+}
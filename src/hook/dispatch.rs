@@ -0,0 +1,181 @@
+use super::SlotId;
+use super::breaker::{HookBreaker, HookFailure};
+use super::profiling::HookProfiler;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+/// A hook identified by its slot, plugin and name.
+pub(super) type HookKey<Id> = (SlotId, Id, Option<Id>);
+
+/// The result of a call of a hook by a dispatch helper.
+type Outcome<R, E> = Result<R, HookFailure<E>>;
+
+/// A call of a hook to record with the profiler and the circuit breaker.
+type Sample<Id> = (Id, Option<Id>, Duration, Option<String>);
+
+/// The profiler and circuit breaker of a registry watching the calls of a dispatch helper.
+pub(super) struct Monitors<'a, Id> {
+    profiler: Option<&'a HookProfiler<Id>>,
+    breaker: Option<&'a HookBreaker<Id>>,
+}
+
+impl<Id> Clone for Monitors<'_, Id> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Id> Copy for Monitors<'_, Id> {}
+
+impl<'a, Id> Monitors<'a, Id>
+where
+    Id: Copy + Eq + Hash,
+{
+    pub(super) fn new(
+        profiler: &'a Option<HookProfiler<Id>>,
+        breaker: &'a Option<HookBreaker<Id>>,
+    ) -> Self {
+        Self {
+            profiler: profiler.as_ref(),
+            breaker: breaker.as_ref(),
+        }
+    }
+
+    fn is_idle(self) -> bool {
+        self.profiler.is_none() && self.breaker.is_none()
+    }
+
+    fn admit<H>(
+        self,
+        slot: SlotId,
+        hooks: impl Iterator<Item = (Id, Option<Id>, H)>,
+    ) -> Vec<(Id, Option<Id>, H)> {
+        match self.breaker {
+            Some(breaker) => breaker.admit(slot, hooks),
+            None => hooks.collect(),
+        }
+    }
+
+    /// Call `f` with a hook, catching panics if there is a circuit breaker.
+    fn call<H, R, E>(
+        self,
+        plugin: Id,
+        name: Option<Id>,
+        hook: H,
+        f: impl FnOnce(Id, H) -> Result<R, E>,
+    ) -> (Outcome<R, E>, Sample<Id>)
+    where
+        E: Display,
+    {
+        let started = Instant::now();
+        let outcome = if self.breaker.is_some() {
+            panic::catch_unwind(AssertUnwindSafe(|| f(plugin, hook)))
+                .map_err(|payload| {
+                    HookFailure::Panicked(crate::plugin::panic_message(payload.as_ref()))
+                })
+                .and_then(|result| result.map_err(HookFailure::Error))
+        } else {
+            f(plugin, hook).map_err(HookFailure::Error)
+        };
+        let elapsed = started.elapsed();
+        let failure = outcome.as_ref().err().map(ToString::to_string);
+        (outcome, (plugin, name, elapsed, failure))
+    }
+
+    fn record(self, slot: SlotId, samples: Vec<Sample<Id>>) {
+        if let Some(profiler) = self.profiler {
+            profiler.record(
+                slot,
+                samples
+                    .iter()
+                    .map(|&(plugin, name, elapsed, _)| (plugin, name, elapsed)),
+            );
+        }
+        if let Some(breaker) = self.breaker {
+            breaker.record(
+                slot,
+                samples
+                    .into_iter()
+                    .map(|(plugin, name, _, failure)| (plugin, name, failure)),
+            );
+        }
+    }
+}
+
+/// Call `f` for every hook and collect the outcomes, reporting the calls to `monitors`.
+pub(super) fn dispatch<Id, H, R, E>(
+    monitors: Monitors<'_, Id>,
+    slot: SlotId,
+    hooks: impl Iterator<Item = (Id, Option<Id>, H)>,
+    mut f: impl FnMut(Id, H) -> Result<R, E>,
+) -> Vec<(Id, Outcome<R, E>)>
+where
+    Id: Copy + Eq + Hash,
+    E: Display,
+{
+    if monitors.is_idle() {
+        return hooks
+            .map(|(plugin, _, hook)| (plugin, f(plugin, hook).map_err(HookFailure::Error)))
+            .collect();
+    }
+    let mut samples = Vec::new();
+    let outcomes = monitors
+        .admit(slot, hooks)
+        .into_iter()
+        .map(|(plugin, name, hook)| {
+            let (outcome, sample) = monitors.call(plugin, name, hook, &mut f);
+            samples.push(sample);
+            (plugin, outcome)
+        })
+        .collect();
+    monitors.record(slot, samples);
+    outcomes
+}
+
+/// Call `f` for every hook in parallel and collect the outcomes, reporting the calls to
+/// `monitors`.
+#[cfg(feature = "rayon")]
+pub(super) fn par_dispatch<Id, H, R, E>(
+    monitors: Monitors<'_, Id>,
+    slot: SlotId,
+    hooks: impl Iterator<Item = (Id, Option<Id>, H)>,
+    f: impl Fn(Id, H) -> Result<R, E> + Sync + Send,
+) -> Vec<(Id, Outcome<R, E>)>
+where
+    Id: Copy + Eq + Hash + Send + Sync,
+    H: Send,
+    R: Send,
+    E: Display + Send,
+{
+    use rayon::prelude::*;
+
+    if monitors.is_idle() {
+        return hooks
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|(plugin, _, hook)| (plugin, f(plugin, hook).map_err(HookFailure::Error)))
+            .collect();
+    }
+    let (outcomes, samples): (Vec<_>, Vec<_>) = monitors
+        .admit(slot, hooks)
+        .into_par_iter()
+        .map(|(plugin, name, hook)| {
+            let (outcome, sample) = monitors.call(plugin, name, hook, &f);
+            ((plugin, outcome), sample)
+        })
+        .unzip();
+    monitors.record(slot, samples);
+    outcomes
+}
+
+/// Keep the results of successful calls of infallible hooks, dropping the hooks that panicked.
+pub(super) fn successes<Id, R>(
+    outcomes: Vec<(Id, Outcome<R, std::convert::Infallible>)>,
+) -> Vec<(Id, R)> {
+    outcomes
+        .into_iter()
+        .filter_map(|(plugin, outcome)| Some((plugin, outcome.ok()?)))
+        .collect()
+}
@@ -0,0 +1,173 @@
+use super::dispatch::{self, Monitors};
+use super::{HookRegistry, HookSlot};
+use rayon::prelude::*;
+use std::hash::{BuildHasher, Hash};
+
+impl<Id, S> HookRegistry<Id, S>
+where
+    Id: Copy + Ord + Hash + Send + Sync,
+    S: BuildHasher,
+{
+    /// Get a parallel iterator over the plugin hooks for the specified slot. Hooks are produced in
+    /// the same order as [`HookRegistry::plugin_slot_hooks`].
+    #[must_use]
+    pub fn par_plugin_slot_hooks<Slot>(
+        &self,
+        plugin: Id,
+    ) -> impl IndexedParallelIterator<Item = &Slot::TraitObject>
+    where
+        Slot: HookSlot,
+    {
+        self.plugin_slot_hooks::<Slot>(plugin)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+    }
+
+    /// Get a parallel iterator over the mutable plugin hooks for the specified slot. Hooks are
+    /// produced in the same order as [`HookRegistry::plugin_slot_hooks_mut`], which skips shared
+    /// hooks.
+    #[must_use]
+    pub fn par_plugin_slot_hooks_mut<Slot>(
+        &mut self,
+        plugin: Id,
+    ) -> impl IndexedParallelIterator<Item = &mut Slot::TraitObject>
+    where
+        Slot: HookSlot,
+    {
+        self.plugin_slot_hooks_mut::<Slot>(plugin)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+    }
+
+    /// Get a parallel iterator over all the hooks from all plugins registered to a slot, including
+    /// the id of the plugin that registered that slot. Hooks are produced in the same order as
+    /// [`HookRegistry::slot_hooks_and_plugin`].
+    #[must_use]
+    pub fn par_slot_hooks_and_plugin<Slot>(
+        &self,
+    ) -> impl IndexedParallelIterator<Item = (Id, &Slot::TraitObject)>
+    where
+        Slot: HookSlot,
+    {
+        self.slot_hooks_and_plugin::<Slot>()
+            .collect::<Vec<_>>()
+            .into_par_iter()
+    }
+
+    /// Get a parallel iterator over all the mutable hooks from all plugins registered to a slot,
+    /// including the id of the plugin that registered that slot. Hooks are produced in the same
+    /// order as [`HookRegistry::slot_hooks_and_plugin_mut`], which skips shared hooks.
+    #[must_use]
+    pub fn par_slot_hooks_and_plugin_mut<Slot>(
+        &mut self,
+    ) -> impl IndexedParallelIterator<Item = (Id, &mut Slot::TraitObject)>
+    where
+        Slot: HookSlot,
+    {
+        self.slot_hooks_and_plugin_mut::<Slot>()
+            .collect::<Vec<_>>()
+            .into_par_iter()
+    }
+
+    /// Call `f` in parallel for every hook from all plugins registered to a slot and collect the
+    /// results. Results are gathered in the same order [`HookRegistry::slot_hooks_and_plugin`]
+    /// produces the hooks. The calls are profiled and protected like [`HookRegistry::dispatch`].
+    pub fn par_dispatch<Slot, R, F>(&self, f: F) -> Vec<(Id, R)>
+    where
+        Slot: HookSlot,
+        R: Send,
+        F: Fn(Id, &Slot::TraitObject) -> R + Sync + Send,
+    {
+        let slot = self.checked_id::<Slot>();
+        dispatch::successes(dispatch::par_dispatch(
+            Monitors::new(&self.profiler, &self.breaker),
+            slot,
+            self.named_hooks::<Slot>(slot),
+            |plugin, hook| Ok(f(plugin, hook)),
+        ))
+    }
+
+    /// Call `f` in parallel for every mutable hook from all plugins registered to a slot and
+    /// collect the results. Results are gathered in the same order
+    /// [`HookRegistry::slot_hooks_and_plugin_mut`] produces the hooks, skipping shared hooks. The
+    /// calls are profiled and protected like [`HookRegistry::dispatch`].
+    pub fn par_dispatch_mut<Slot, R, F>(&mut self, f: F) -> Vec<(Id, R)>
+    where
+        Slot: HookSlot,
+        R: Send,
+        F: Fn(Id, &mut Slot::TraitObject) -> R + Sync + Send,
+    {
+        let slot = self.checked_id::<Slot>();
+        dispatch::successes(dispatch::par_dispatch(
+            Monitors::new(&self.profiler, &self.breaker),
+            slot,
+            Self::named_hooks_mut::<Slot>(&mut self.slot_hooks, slot),
+            |plugin, hook| Ok(f(plugin, hook)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{HookRegistry, hook_slot};
+    use rayon::prelude::*;
+
+    trait Counter: Send + Sync {
+        fn count(&self) -> usize;
+
+        fn increment(&mut self);
+    }
+
+    impl Counter for usize {
+        fn count(&self) -> usize {
+            *self
+        }
+
+        fn increment(&mut self) {
+            *self += 1;
+        }
+    }
+
+    hook_slot!(CounterSlot: dyn Counter);
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn par_dispatch_matches_sequential_order() {
+        const PLUGINS: [&str; 6] = ["a", "b", "c", "d", "e", "f"];
+        let mut hooks = HookRegistry::new();
+        for (i, plugin) in PLUGINS.into_iter().enumerate() {
+            assert!(
+                hooks
+                    .register::<CounterSlot>(Box::new(i), plugin, None)
+                    .is_ok()
+            );
+            assert!(
+                hooks
+                    .register::<CounterSlot>(Box::new(i * 10), plugin, Some("tens"))
+                    .is_ok()
+            );
+        }
+
+        let sequential = hooks
+            .slot_hooks_and_plugin::<CounterSlot>()
+            .map(|(plugin, hook)| (plugin, hook.count()))
+            .collect::<Vec<_>>();
+        assert_eq!(sequential.len(), 12);
+        assert_eq!(
+            hooks.par_dispatch::<CounterSlot, _, _>(|_, hook| hook.count()),
+            sequential
+        );
+
+        hooks.par_dispatch_mut::<CounterSlot, _, _>(|_, hook| hook.increment());
+        assert_eq!(
+            hooks
+                .par_slot_hooks_and_plugin::<CounterSlot>()
+                .map(|(plugin, hook)| (plugin, hook.count()))
+                .collect::<Vec<_>>(),
+            sequential
+                .into_iter()
+                .map(|(plugin, count)| (plugin, count + 1))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use super::dispatch::HookKey;
+use super::{HookRegistry, SlotId, SlotInfo};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+/// Profiling statistics of a single hook, returned by [`HookRegistry::hook_profile`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HookProfileEntry<Id> {
+    /// Slot the hook is registered to.
+    pub slot: SlotId,
+    /// Name of the slot the hook is registered to, as given by [`SlotInfo::name`].
+    pub slot_name: &'static str,
+    /// Plugin id of the plugin that registered the hook.
+    pub plugin: Id,
+    /// Name of the hook, if any.
+    pub name: Option<Id>,
+    /// Number of times the hook was called by the dispatch helpers.
+    pub calls: u64,
+    /// Cumulative time spent in the hook.
+    pub total: Duration,
+}
+
+impl<Id> HookProfileEntry<Id> {
+    /// Get the mean time spent in a call of the hook.
+    #[must_use]
+    pub fn mean(&self) -> Duration {
+        match u32::try_from(self.calls) {
+            Ok(0) => Duration::ZERO,
+            Ok(calls) => self.total / calls,
+            Err(_) => Duration::from_secs_f64(self.total.as_secs_f64() / self.calls as f64),
+        }
+    }
+}
+
+/// Call counts and cumulative times of hooks.
+pub(super) struct HookProfiler<Id> {
+    hooks: Mutex<HashMap<HookKey<Id>, (u64, Duration)>>,
+}
+
+impl<Id> HookProfiler<Id>
+where
+    Id: Copy + Eq + Hash,
+{
+    fn new() -> Self {
+        Self {
+            hooks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(super) fn record(
+        &self,
+        slot: SlotId,
+        samples: impl IntoIterator<Item = (Id, Option<Id>, Duration)>,
+    ) {
+        let mut hooks = self.hooks.lock().unwrap_or_else(PoisonError::into_inner);
+        for (plugin, name, elapsed) in samples {
+            let (calls, total) = hooks.entry((slot, plugin, name)).or_default();
+            *calls += 1;
+            *total += elapsed;
+        }
+    }
+}
+
+impl<Id> Debug for HookProfiler<Id> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HookProfiler").finish_non_exhaustive()
+    }
+}
+
+impl<Id, S> HookRegistry<Id, S>
+where
+    Id: Copy + Ord + Hash,
+    S: BuildHasher,
+{
+    /// Enable or disable profiling of hooks, which is disabled by default. While profiling, the
+    /// dispatch helpers such as [`HookRegistry::dispatch`] record the number of calls of every hook
+    /// and the cumulative time spent in it, reported by [`HookRegistry::hook_profile`]. Hooks
+    /// accessed directly, such as with [`HookRegistry::slot_hooks_and_plugin`], are not profiled.
+    /// Disabling profiling discards the recorded statistics.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        if !enabled {
+            self.profiler = None;
+        } else if self.profiler.is_none() {
+            self.profiler = Some(HookProfiler::new());
+        }
+    }
+
+    /// Determine whether profiling of hooks is enabled; see [`HookRegistry::set_profiling`].
+    #[must_use]
+    pub fn is_profiling(&self) -> bool {
+        self.profiler.is_some()
+    }
+
+    /// Get the profiling statistics of all hooks called while profiling, sorted by the cumulative
+    /// time spent in the hook, slowest first.
+    #[must_use]
+    pub fn hook_profile(&self) -> Vec<HookProfileEntry<Id>> {
+        let Some(profiler) = &self.profiler else {
+            return Vec::new();
+        };
+        let mut entries = profiler
+            .hooks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(
+                |(&(slot, plugin, name), &(calls, total))| HookProfileEntry {
+                    slot,
+                    slot_name: self.slots.get(&slot).map_or(slot.name(), SlotInfo::name),
+                    plugin,
+                    name,
+                    calls,
+                    total,
+                },
+            )
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by(|a, b| {
+            b.total
+                .cmp(&a.total)
+                .then_with(|| (a.slot, a.plugin, a.name).cmp(&(b.slot, b.plugin, b.name)))
+        });
+        entries
+    }
+
+    /// Get the profiling statistics of the `count` hooks with the most cumulative time spent in
+    /// them, slowest first.
+    #[must_use]
+    pub fn top_hooks(&self, count: usize) -> Vec<HookProfileEntry<Id>> {
+        let mut entries = self.hook_profile();
+        entries.truncate(count);
+        entries
+    }
+
+    /// Clear the profiling statistics of all hooks, keeping profiling enabled if it is.
+    pub fn reset_profile(&mut self) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler
+                .hooks
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{HookRegistry, HookSlot, hook_slot};
+    use std::thread;
+    use std::time::Duration;
+
+    trait Render: Send + Sync {
+        fn render(&self);
+    }
+
+    impl Render for Duration {
+        fn render(&self) {
+            thread::sleep(*self);
+        }
+    }
+
+    hook_slot!(RenderSlot: dyn Render);
+
+    #[test]
+    fn profile_hooks() {
+        let mut hooks = HookRegistry::new();
+        hooks
+            .register::<RenderSlot>(Box::new(Duration::ZERO), "fast", None)
+            .unwrap();
+        hooks
+            .register::<RenderSlot>(Box::new(Duration::from_millis(5)), "slow", Some("shadows"))
+            .unwrap();
+        hooks.dispatch::<RenderSlot, _>(|_, hook| hook.render());
+        assert!(hooks.hook_profile().is_empty());
+
+        hooks.set_profiling(true);
+        for _ in 0..2 {
+            hooks.dispatch::<RenderSlot, _>(|_, hook| hook.render());
+        }
+        let top = hooks.top_hooks(1);
+        assert_eq!(top.len(), 1);
+        assert_eq!((top[0].plugin, top[0].name), ("slow", Some("shadows")));
+        assert_eq!(top[0].slot_name, RenderSlot::name());
+        assert_eq!(top[0].calls, 2);
+        assert!(top[0].mean() >= Duration::from_millis(5));
+        assert_eq!(hooks.hook_profile()[1].plugin, "fast");
+
+        hooks.reset_profile();
+        assert!(hooks.hook_profile().is_empty());
+        assert!(hooks.is_profiling());
+    }
+}
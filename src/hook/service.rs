@@ -0,0 +1,350 @@
+use super::{DynHook, HookRegistry, LibraryHandle};
+use std::any::{Any, TypeId, type_name};
+use std::hash::{BuildHasher, Hash};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// An error occurred while providing or looking up a service. It is generic over the type of plugin
+/// id used by the hook registry; see [`HookRegistry`] for more details.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Error)]
+pub enum ServiceError<Id> {
+    /// The plugin already provides a service of the same type.
+    #[error("plugin `{plugin}` already provides service `{service}`")]
+    Duplicate {
+        /// Plugin id of the plugin providing the service.
+        plugin: Id,
+        /// Type name of the service.
+        service: &'static str,
+    },
+    /// The plugin looked up a service of a plugin it does not list as a dependency.
+    #[error("plugin `{plugin}` does not list plugin `{provider}` as a dependency")]
+    Undeclared {
+        /// Plugin id of the plugin looking up the service.
+        plugin: Id,
+        /// Plugin id of the plugin the service was looked up from.
+        provider: Id,
+    },
+    /// The provider does not currently provide a service of the type.
+    #[error("plugin `{provider}` does not provide service `{service}`")]
+    NotProvided {
+        /// Plugin id of the plugin the service was looked up from.
+        provider: Id,
+        /// Type name of the service.
+        service: &'static str,
+    },
+    /// None of the dependencies of the plugin currently provide a service of the type.
+    #[error("no dependency of plugin `{plugin}` provides service `{service}`")]
+    NotFound {
+        /// Plugin id of the plugin looking up the service.
+        plugin: Id,
+        /// Type name of the service.
+        service: &'static str,
+    },
+    /// A plugin provided or looked up a service on behalf of another plugin.
+    #[error("plugin `{caller}` cannot use services on behalf of plugin `{plugin}`")]
+    Impersonated {
+        /// Plugin id the service was provided or looked up for.
+        plugin: Id,
+        /// Plugin id of the plugin the registry was passed to.
+        caller: Id,
+    },
+}
+
+/// A type-erased service. The erased value is always an `Arc<T>` of the service type `T`.
+#[derive(Debug, Clone)]
+pub(super) struct Service {
+    type_id: TypeId,
+    type_name: &'static str,
+    ptr: Arc<DynHook>,
+    // Dropped after the service, as the service's code may live in the library
+    #[expect(dead_code)]
+    library: Option<LibraryHandle>,
+}
+
+impl Service {
+    fn get<T>(&self) -> Option<Arc<T>>
+    where
+        T: ?Sized + Any,
+    {
+        self.ptr.downcast_ref::<Arc<T>>().cloned()
+    }
+}
+
+impl<Id, S> HookRegistry<Id, S>
+where
+    Id: Copy + Ord + Hash,
+    S: BuildHasher,
+{
+    /// Publish a service of type `T` provided by a plugin, usually during the plugin's
+    /// [`Plugin::load`][crate::Plugin::load]. Services are keyed by their type, such as
+    /// `Arc<dyn DatabasePool>`, and each plugin can provide one service of each type. Plugins
+    /// listing the provider in their [`PluginManifest::dependencies`][crate::PluginManifest::dependencies]
+    /// can then look the service up with [`HookRegistry::service`] or
+    /// [`HookRegistry::find_service`]. The service is withdrawn when the provider is unloaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ServiceError::Impersonated`] if the registry was passed to another plugin than
+    /// `plugin`; see [`HookRegistry::service`].
+    ///
+    /// Returns [`ServiceError::Duplicate`] if the plugin already provides a service of type `T`.
+    pub fn provide_service<T>(
+        &mut self,
+        plugin: Id,
+        service: Arc<T>,
+    ) -> Result<(), ServiceError<Id>>
+    where
+        T: ?Sized + Any + Send + Sync,
+    {
+        self.check_caller(plugin)?;
+        let services = self.services.entry(plugin).or_default();
+        if services.iter().any(|s| s.type_id == TypeId::of::<T>()) {
+            return Err(ServiceError::Duplicate {
+                plugin,
+                service: type_name::<T>(),
+            });
+        }
+        services.push(Service {
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+            ptr: Arc::new(service),
+            library: self.library.clone(),
+        });
+        Ok(())
+    }
+
+    /// Look up the service of type `T` provided by `provider` on behalf of `plugin`. Plugins can
+    /// only look up their own services and the services of the plugins they list as dependencies.
+    /// The returned [`Arc`] can be kept, such as for use in [`Plugin::enable`][crate::Plugin::enable],
+    /// and keeps the service alive after it is withdrawn.
+    ///
+    /// While the registry is passed to a plugin, such as in [`Plugin::load`][crate::Plugin::load],
+    /// the registry knows which plugin is calling and services can only be used on its behalf. The
+    /// plugin host itself can use services on behalf of any plugin.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ServiceError::Impersonated`] if the registry was passed to another plugin than
+    /// `plugin`.
+    ///
+    /// Returns [`ServiceError::Undeclared`] if `plugin` does not list `provider` as a dependency.
+    ///
+    /// Returns [`ServiceError::NotProvided`] if `provider` does not currently provide a service of
+    /// type `T`.
+    pub fn service<T>(&self, plugin: Id, provider: Id) -> Result<Arc<T>, ServiceError<Id>>
+    where
+        T: ?Sized + Any + Send + Sync,
+    {
+        self.check_caller(plugin)?;
+        if plugin != provider
+            && !self
+                .dependencies
+                .get(&plugin)
+                .is_some_and(|dependencies| dependencies.contains(&provider))
+        {
+            return Err(ServiceError::Undeclared { plugin, provider });
+        }
+        self.provided_service(provider)
+            .ok_or(ServiceError::NotProvided {
+                provider,
+                service: type_name::<T>(),
+            })
+    }
+
+    /// Look up the service of type `T` on behalf of `plugin` from the first of its dependencies
+    /// that provides one, in the order the dependencies are listed in its manifest.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ServiceError::Impersonated`] if the registry was passed to another plugin than
+    /// `plugin`; see [`HookRegistry::service`].
+    ///
+    /// Returns [`ServiceError::NotFound`] if none of the dependencies of `plugin` currently provide
+    /// a service of type `T`.
+    pub fn find_service<T>(&self, plugin: Id) -> Result<Arc<T>, ServiceError<Id>>
+    where
+        T: ?Sized + Any + Send + Sync,
+    {
+        self.check_caller(plugin)?;
+        self.dependencies
+            .get(&plugin)
+            .into_iter()
+            .flatten()
+            .find_map(|&provider| self.provided_service(provider))
+            .ok_or(ServiceError::NotFound {
+                plugin,
+                service: type_name::<T>(),
+            })
+    }
+
+    /// Get the type names of the services currently provided by a plugin.
+    pub fn provided_services(&self, plugin: Id) -> impl Iterator<Item = &'static str> {
+        self.services
+            .get(&plugin)
+            .into_iter()
+            .flatten()
+            .map(|service| service.type_name)
+    }
+
+    /// Withdraw all services provided by a plugin. Plugins that already looked up a service keep
+    /// their [`Arc`] of it.
+    pub fn withdraw_services(&mut self, plugin: Id) {
+        self.services.remove(&plugin);
+    }
+
+    /// Check that the registry is not passed to another plugin than `plugin`.
+    fn check_caller(&self, plugin: Id) -> Result<(), ServiceError<Id>> {
+        match self.caller {
+            Some(caller) if caller != plugin => Err(ServiceError::Impersonated { plugin, caller }),
+            _ => Ok(()),
+        }
+    }
+
+    fn provided_service<T>(&self, provider: Id) -> Option<Arc<T>>
+    where
+        T: ?Sized + Any,
+    {
+        self.services
+            .get(&provider)?
+            .iter()
+            .find(|service| service.type_id == TypeId::of::<T>())?
+            .get()
+    }
+
+    /// Record the dependencies of a plugin, which it is allowed to look up services from.
+    pub(crate) fn set_dependencies(&mut self, plugin: Id, dependencies: &[Id]) {
+        self.dependencies.insert(plugin, dependencies.to_vec());
+    }
+
+    pub(crate) fn remove_dependencies(&mut self, plugin: Id) {
+        self.dependencies.remove(&plugin);
+    }
+
+    /// Move the services provided in `other` into this registry, dropping services of types the
+    /// plugin already provides.
+    pub(super) fn append_services(&mut self, other: impl IntoIterator<Item = (Id, Vec<Service>)>) {
+        for (plugin, other_services) in other {
+            let services = self.services.entry(plugin).or_default();
+            for service in other_services {
+                if !services.iter().any(|s| s.type_id == service.type_id) {
+                    services.push(service);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{HookRegistry, Plugin, PluginRegistry, ServiceError, SimplePluginManifest};
+    use std::sync::Arc;
+
+    trait DatabasePool: Send + Sync {
+        fn url(&self) -> &str;
+    }
+
+    impl DatabasePool for String {
+        fn url(&self) -> &str {
+            self
+        }
+    }
+
+    struct Database;
+
+    impl Plugin for Database {
+        fn load(&mut self, hooks: &mut HookRegistry, _context: &mut ()) {
+            let pool: Arc<dyn DatabasePool> = Arc::new(String::from("postgres://"));
+            hooks.provide_service("database", Arc::new(pool)).unwrap();
+        }
+    }
+
+    #[derive(Default)]
+    struct App {
+        pool: Option<Arc<dyn DatabasePool>>,
+    }
+
+    impl Plugin for App {
+        fn load(&mut self, hooks: &mut HookRegistry, _context: &mut ()) {
+            let pool = hooks.find_service::<Arc<dyn DatabasePool>>("app").unwrap();
+            assert!(Arc::ptr_eq(
+                &pool,
+                &hooks.service("app", "database").unwrap()
+            ));
+            assert_eq!(
+                hooks.service::<Arc<dyn DatabasePool>>("app", "other").err(),
+                Some(ServiceError::Undeclared {
+                    plugin: "app",
+                    provider: "other",
+                })
+            );
+            // Plugins cannot pass themselves off as another plugin
+            assert_eq!(
+                hooks
+                    .service::<Arc<dyn DatabasePool>>("database", "database")
+                    .err(),
+                Some(ServiceError::Impersonated {
+                    plugin: "database",
+                    caller: "app",
+                })
+            );
+            assert_eq!(
+                hooks.provide_service("database", Arc::new(0_u8)),
+                Err(ServiceError::Impersonated {
+                    plugin: "database",
+                    caller: "app",
+                })
+            );
+            self.pool = Some(Arc::clone(&pool));
+        }
+
+        fn enable(&mut self, _context: &mut ()) {
+            assert_eq!(self.pool.as_ref().unwrap().url(), "postgres://");
+        }
+    }
+
+    #[test]
+    fn plugin_services() {
+        let mut plugins = PluginRegistry::new();
+        plugins
+            .register(
+                SimplePluginManifest::new("database", ""),
+                Some(|| Box::new(Database)),
+            )
+            .unwrap();
+        plugins
+            .register(
+                SimplePluginManifest::with_dependencies("app", "", vec!["database"]),
+                Some(|| Box::new(App::default())),
+            )
+            .unwrap();
+        plugins
+            .register(SimplePluginManifest::new("other", ""), None)
+            .unwrap();
+
+        plugins.enable("app", &mut ()).unwrap();
+        assert_eq!(
+            plugins
+                .hooks()
+                .service::<Arc<dyn DatabasePool>>("other", "database")
+                .err(),
+            Some(ServiceError::Undeclared {
+                plugin: "other",
+                provider: "database",
+            })
+        );
+        assert_eq!(
+            plugins
+                .hooks_mut()
+                .provide_service("database", Arc::new(0_u32)),
+            Ok(())
+        );
+        assert_eq!(plugins.hooks().provided_services("database").count(), 2);
+
+        let _ = plugins.unload("database", &mut ());
+        assert_eq!(plugins.hooks().provided_services("database").count(), 0);
+        assert!(matches!(
+            plugins.hooks().find_service::<u32>("app"),
+            Err(ServiceError::NotFound { plugin: "app", .. })
+        ));
+    }
+}
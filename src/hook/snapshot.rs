@@ -0,0 +1,270 @@
+use super::{Hook, HookRegistry, HookSlot, SlotId, SlotInfo, SlotTypeMismatch};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::hash::{BuildHasher, Hash};
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// An immutable snapshot of the shared hooks in a [`HookRegistry`], taken with
+/// [`HookRegistry::snapshot`]. Only hooks registered with [`HookRegistry::register_shared`] are
+/// included, as hooks owned by the registry cannot outlive it. The snapshot shares the hooks with
+/// the registry, so it stays valid after the hooks are removed from the registry, such as when
+/// their plugin is unloaded, and keeps those hooks alive until the snapshot is dropped.
+///
+/// Hooks accessed through a snapshot bypass the registry's circuit breaker and profiler: panics
+/// are not caught, suspended hooks are still produced, and calls are not timed. Use the dispatch
+/// helpers of the registry, such as [`HookRegistry::dispatch`], for those.
+///
+/// Like the registry's accessors, accessing the snapshot through a slot type that shares its id
+/// with a slot of a different trait object panics in debug builds and finds no hooks otherwise.
+pub struct HookSnapshot<Id = &'static str> {
+    slot_hooks: HashMap<SlotId, Arc<[Hook<Id>]>>,
+    slots: HashMap<SlotId, SlotInfo>,
+}
+
+impl<Id> HookSnapshot<Id>
+where
+    Id: Copy + Ord + Hash,
+{
+    /// Verify that the hook slot `Slot` uses the same trait object as the slot recorded under the
+    /// same id when the snapshot was taken. See [`HookRegistry::verify_slot`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SlotTypeMismatch`] if a different signature or trait object was recorded for the
+    /// slot's id.
+    pub fn verify_slot<Slot>(&self) -> Result<(), SlotTypeMismatch>
+    where
+        Slot: HookSlot,
+    {
+        match self.slots.get(&Slot::id()) {
+            Some(info) if !info.matches::<Slot>() => Err(info.mismatch::<Slot>()),
+            _ => Ok(()),
+        }
+    }
+
+    fn checked_id<Slot>(&self) -> SlotId
+    where
+        Slot: HookSlot,
+    {
+        #[cfg(debug_assertions)]
+        if let Err(error) = self.verify_slot::<Slot>() {
+            panic!("{error}");
+        }
+        Slot::id()
+    }
+
+    /// Get a snapshot of only the hooks registered to the specified slot. The slot snapshot shares
+    /// its hooks with this snapshot and can be cheaply cloned.
+    #[must_use]
+    pub fn slot<Slot>(&self) -> SlotHooks<Id, Slot>
+    where
+        Slot: HookSlot,
+    {
+        SlotHooks::new(
+            self.slot_hooks
+                .get(&self.checked_id::<Slot>())
+                .cloned()
+                .unwrap_or_else(|| Arc::new([])),
+        )
+    }
+
+    /// Get the first dyn object hook added by a plugin for the hook slot.
+    #[must_use]
+    pub fn get_first<Slot>(&self, plugin: Id) -> Option<&Slot::TraitObject>
+    where
+        Slot: HookSlot,
+    {
+        self.slot_hooks
+            .get(&self.checked_id::<Slot>())?
+            .iter()
+            .find(|h| h.plugin == plugin)?
+            .get()
+    }
+
+    /// Get the dyn object hook with the specified name added by a plugin for the hook slot.
+    #[must_use]
+    pub fn get_exact<Slot>(&self, plugin: Id, name: Option<Id>) -> Option<&Slot::TraitObject>
+    where
+        Slot: HookSlot,
+    {
+        self.slot_hooks
+            .get(&self.checked_id::<Slot>())?
+            .iter()
+            .find(|h| h.plugin == plugin && h.name == name)?
+            .get()
+    }
+
+    /// Get an iterator over all the hooks from all plugins registered to a slot, including the id
+    /// of the plugin that registered that slot. Hooks are produced in the same order as
+    /// [`HookRegistry::slot_hooks_and_plugin`] did when the snapshot was taken.
+    #[must_use]
+    pub fn slot_hooks_and_plugin<Slot>(&self) -> impl FusedIterator<Item = (Id, &Slot::TraitObject)>
+    where
+        Slot: HookSlot,
+    {
+        self.slot_hooks
+            .get(&self.checked_id::<Slot>())
+            .into_iter()
+            .flat_map(|hooks| hooks.iter())
+            .filter_map(|h| Some((h.plugin, h.get()?)))
+    }
+}
+
+impl<Id> Debug for HookSnapshot<Id>
+where
+    Id: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HookSnapshot")
+            .field("slot_hooks", &self.slot_hooks)
+            .field("slots", &self.slots)
+            .finish()
+    }
+}
+
+impl<Id> Default for HookSnapshot<Id> {
+    fn default() -> Self {
+        Self {
+            slot_hooks: HashMap::new(),
+            slots: HashMap::new(),
+        }
+    }
+}
+
+/// An immutable snapshot of the hooks registered to a single hook slot, obtained from
+/// [`HookSnapshot::slot`]. Cloning the slot snapshot is cheap as it shares the hooks.
+pub struct SlotHooks<Id, Slot> {
+    hooks: Arc<[Hook<Id>]>,
+    slot: PhantomData<fn() -> Slot>,
+}
+
+impl<Id, Slot> SlotHooks<Id, Slot>
+where
+    Id: Copy + Ord + Hash,
+    Slot: HookSlot,
+{
+    fn new(hooks: Arc<[Hook<Id>]>) -> Self {
+        Self {
+            hooks,
+            slot: PhantomData,
+        }
+    }
+
+    /// Get the number of hooks registered to the slot.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+
+    /// Determine whether no hooks were registered to the slot.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Get the first dyn object hook added by a plugin for the slot.
+    #[must_use]
+    pub fn get_first(&self, plugin: Id) -> Option<&Slot::TraitObject> {
+        self.hooks.iter().find(|h| h.plugin == plugin)?.get()
+    }
+
+    /// Get the dyn object hook with the specified name added by a plugin for the slot.
+    #[must_use]
+    pub fn get_exact(&self, plugin: Id, name: Option<Id>) -> Option<&Slot::TraitObject> {
+        self.hooks
+            .iter()
+            .find(|h| h.plugin == plugin && h.name == name)?
+            .get()
+    }
+
+    /// Get a shared reference to the first dyn object hook added by a plugin for the slot, which
+    /// stays alive after the slot snapshot is dropped.
+    #[must_use]
+    pub fn get_first_shared(&self, plugin: Id) -> Option<Arc<Slot::TraitObject>> {
+        self.hooks.iter().find(|h| h.plugin == plugin)?.get_shared()
+    }
+
+    /// Get a shared reference to the dyn object hook with the specified name added by a plugin for
+    /// the slot, which stays alive after the slot snapshot is dropped.
+    #[must_use]
+    pub fn get_exact_shared(&self, plugin: Id, name: Option<Id>) -> Option<Arc<Slot::TraitObject>> {
+        self.hooks
+            .iter()
+            .find(|h| h.plugin == plugin && h.name == name)?
+            .get_shared()
+    }
+
+    /// Get an iterator over the hooks added by a plugin for the slot.
+    #[must_use]
+    pub fn plugin_hooks(&self, plugin: Id) -> impl FusedIterator<Item = &Slot::TraitObject> {
+        self.hooks
+            .iter()
+            .filter(move |h| h.plugin == plugin)
+            .filter_map(Hook::get)
+    }
+
+    /// Get an iterator over all the hooks from all plugins registered to the slot, including the
+    /// id of the plugin that registered that slot.
+    #[must_use]
+    pub fn hooks_and_plugin(&self) -> impl FusedIterator<Item = (Id, &Slot::TraitObject)> {
+        self.hooks.iter().filter_map(|h| Some((h.plugin, h.get()?)))
+    }
+}
+
+impl<Id, Slot> Clone for SlotHooks<Id, Slot> {
+    fn clone(&self) -> Self {
+        Self {
+            hooks: self.hooks.clone(),
+            slot: PhantomData,
+        }
+    }
+}
+
+impl<Id, Slot> Debug for SlotHooks<Id, Slot>
+where
+    Id: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SlotHooks")
+            .field("hooks", &self.hooks)
+            .finish()
+    }
+}
+
+impl<Id, S> HookRegistry<Id, S>
+where
+    Id: Copy + Ord + Hash,
+    S: BuildHasher,
+{
+    /// Take an immutable snapshot of all the hooks currently registered with
+    /// [`HookRegistry::register_shared`]. The snapshot shares those hooks with the registry, so
+    /// while a snapshot is alive they cannot be borrowed mutably; see
+    /// [`HookAccessError::Shared`][super::HookAccessError::Shared]. Hooks owned by the registry
+    /// are left out and can still be borrowed mutably.
+    #[must_use]
+    pub fn snapshot(&self) -> HookSnapshot<Id> {
+        HookSnapshot {
+            slot_hooks: self
+                .slot_hooks
+                .iter()
+                .map(|(&slot, plugin_hooks)| {
+                    (
+                        slot,
+                        plugin_hooks
+                            .values()
+                            .flatten()
+                            .filter_map(Hook::share)
+                            .collect::<Arc<[_]>>(),
+                    )
+                })
+                .collect(),
+            slots: self
+                .slots
+                .iter()
+                .map(|(&slot, &info)| (slot, info))
+                .collect(),
+        }
+    }
+}
@@ -1,13 +1,38 @@
 //! A modular plugin framework with trait hooks.
+//!
+//! # Features
+//!
+//! - `discovery`: Adds discovering plugins from TOML or JSON manifest files in plugin directories
+//!   with [`PluginDiscovery`].
+//! - `dylib`: Adds loading plugins from dynamic libraries at runtime with
+//!   [`PluginRegistry::register_library`].
+//! - `process`: Adds running plugins in a child process communicating over JSON-RPC with
+//!   [`ProcessPlugin`].
+//! - `rayon`: Adds parallel iteration and dispatch over the hooks of a slot to [`HookRegistry`].
+//! - `script`: Adds plugins written as Rhai scripts with [`PluginRegistry::register_script`].
+//! - `serde`: Implements `Serialize` and `Deserialize` for [`SimplePluginManifest`], the plugin
+//!   errors, [`RegistrySnapshot`], [`EnablementProfile`] and [`DependencyGraph`].
+//! - `tracing`: Emits [`tracing`](https://docs.rs/tracing) spans for the operations of
+//!   [`PluginRegistry`] on plugins, recording the plugin id and the plugin whose operation cascaded
+//!   to it, and runs plugin lifecycle methods in a span of the plugin so that events emitted by
+//!   plugins are attributed to them. Requires plugin ids to implement `Debug`.
+//! - `wasm`: Adds running sandboxed plugins compiled to WebAssembly with [`WasmPlugin`], using the
+//!   wasmtime runtime.
 
 #![warn(missing_docs)]
 
+#[cfg(feature = "discovery")]
+mod discovery;
 mod hook;
 mod plugin;
+mod shared;
 
+#[cfg(feature = "discovery")]
+pub use discovery::*;
 pub use hook::*;
 pub use linkme::distributed_slice as static_plugin_initializer;
 pub use plugin::*;
+pub use shared::*;
 
 /// Declares a slot for hosting static plugin initializers that can be registered in client plugins
 /// by [`register_static_plugin`]. The plugin host can then use
@@ -124,6 +149,14 @@ macro_rules! register_static_plugin {
 /// associated type. Plugins and host can then use methods on [`HookRegistry`] to register or access
 /// hooks attached to that slot.
 ///
+/// The slot's [`SlotId`] is derived from the module path and name of the slot, so it is stable
+/// between separately compiled binaries as long as the slot is declared in the same module. Its
+/// [`HookSlot::signature`] is the trait object type as written, such as `"dyn MyHookTrait"`.
+///
+/// Slot metadata can optionally be declared in braces after the trait object, setting any of the
+/// slot's `id` (a string), `signature` (a string), `name`, `description` and `cardinality` (a
+/// [`SlotCardinality`] variant). See [`HookSlot`] for the defaults.
+///
 /// # Examples
 ///
 /// ```
@@ -135,17 +168,79 @@ macro_rules! register_static_plugin {
 ///
 /// hook_slot!(pub MyHookSlot: dyn MyHookTrait);
 ///
+/// pub trait MyRendererTrait: Send + Sync {
+///     // ...
+/// }
+///
+/// hook_slot!(pub MyRendererSlot: dyn MyRendererTrait {
+///     id: "my_app::renderer",
+///     signature: "my_app::MyRendererTrait v2",
+///     name: "renderer",
+///     description: "Renders the application window",
+///     cardinality: ExactlyOne,
+/// });
+///
 /// # fn main() {}
 /// ```
 #[macro_export]
 macro_rules! hook_slot {
-    ($(#[$meta:meta])* $pub:vis $name:ident : $traitobj:ty) => {
+    (@id $name:ident) => {
+        fn id() -> $crate::SlotId {
+            const ID: $crate::SlotId =
+                $crate::SlotId::new(concat!(module_path!(), "::", stringify!($name)));
+            ID
+        }
+    };
+    (@id $name:ident id $value:tt $($rest:tt)*) => {
+        fn id() -> $crate::SlotId {
+            const ID: $crate::SlotId = $crate::SlotId::new($value);
+            ID
+        }
+    };
+    (@id $name:ident $key:ident $value:tt $($rest:tt)*) => {
+        $crate::hook_slot!(@id $name $($rest)*);
+    };
+    (@signature $traitobj:ty $(,)?) => {
+        fn signature() -> &'static str {
+            stringify!($traitobj)
+        }
+    };
+    (@signature $traitobj:ty, signature $value:tt $($rest:tt)*) => {
+        fn signature() -> &'static str {
+            $value
+        }
+    };
+    (@signature $traitobj:ty, $key:ident $value:tt $($rest:tt)*) => {
+        $crate::hook_slot!(@signature $traitobj, $($rest)*);
+    };
+    (@meta id $value:tt) => {};
+    (@meta signature $value:tt) => {};
+    (@meta name $value:tt) => {
+        fn name() -> &'static str {
+            $value
+        }
+    };
+    (@meta description $value:tt) => {
+        fn description() -> &'static str {
+            $value
+        }
+    };
+    (@meta cardinality $value:tt) => {
+        fn cardinality() -> $crate::SlotCardinality {
+            $crate::SlotCardinality::$value
+        }
+    };
+    ($(#[$meta:meta])* $pub:vis $name:ident : $traitobj:ty $({ $($key:ident : $value:tt),* $(,)? })?) => {
         $(#[$meta])*
         #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
         $pub struct $name;
 
         impl $crate::HookSlot for $name {
             type TraitObject = $traitobj;
+
+            $crate::hook_slot!(@id $name $($($key $value)*)?);
+            $crate::hook_slot!(@signature $traitobj, $($($key $value)*)?);
+            $($($crate::hook_slot!(@meta $key $value);)*)?
         }
     };
 }
@@ -153,6 +248,44 @@ impl $crate::HookSlot for $name {
 #[cfg(test)]
 mod tests {
     use crate::{Plugin, PluginRegistry, SimplePluginManifest};
+    #[cfg(any(feature = "discovery", feature = "process", feature = "script"))]
+    pub(crate) use temp_dir::TempDir;
+
+    #[cfg(any(feature = "discovery", feature = "process", feature = "script"))]
+    mod temp_dir {
+        use std::ops::Deref;
+        use std::path::{Path, PathBuf};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::{env, fs, process};
+
+        /// A temporary directory unique to a test, removed when dropped.
+        pub(crate) struct TempDir(PathBuf);
+
+        impl TempDir {
+            pub(crate) fn new(name: &str) -> Self {
+                static COUNT: AtomicUsize = AtomicUsize::new(0);
+                let count = COUNT.fetch_add(1, Ordering::Relaxed);
+                let path = env::temp_dir().join(format!("biner-{name}-{}-{count}", process::id()));
+                let _ = fs::remove_dir_all(&path);
+                fs::create_dir_all(&path).unwrap();
+                Self(path)
+            }
+        }
+
+        impl Deref for TempDir {
+            type Target = Path;
+
+            fn deref(&self) -> &Path {
+                &self.0
+            }
+        }
+
+        impl Drop for TempDir {
+            fn drop(&mut self) {
+                let _ = fs::remove_dir_all(&self.0);
+            }
+        }
+    }
 
     static_plugin_slot!(pub TEST_PLUGIN_SLOT);
 
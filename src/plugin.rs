@@ -1,9 +1,51 @@
-use crate::HookRegistry;
+mod async_plugin;
+mod config;
+#[cfg(feature = "dylib")]
+mod dylib;
+mod graph;
+mod isolation;
+mod metrics;
+#[cfg(feature = "process")]
+mod process;
+mod profile;
+mod resources;
+#[cfg(feature = "script")]
+mod script;
+mod status;
+mod trace;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+pub use async_plugin::*;
+pub use config::*;
+#[cfg(feature = "dylib")]
+pub use dylib::*;
+pub use graph::*;
+pub use isolation::*;
+pub use metrics::*;
+#[cfg(feature = "process")]
+pub use process::*;
+pub use profile::*;
+pub use resources::*;
+#[cfg(feature = "script")]
+pub use script::*;
+pub use status::*;
+pub use trace::TracePluginId;
+#[cfg(feature = "wasm")]
+pub use wasm::*;
+
+use crate::hook::LibraryHandle;
+use crate::{HookRegistry, RegisterHookError, SlotCardinality};
+use isolation::isolate_lifecycle;
+pub(crate) use isolation::panic_message;
 use petgraph::algo;
 use petgraph::prelude::*;
+use status::Transition;
 use std::borrow::Cow;
 use std::fmt::Display;
 use std::hash::{BuildHasher, RandomState};
+use std::sync::Arc;
+use std::time::Instant;
 use std::{
     any::Any,
     collections::{HashMap, hash_map},
@@ -12,10 +54,12 @@
     iter::FusedIterator,
 };
 use thiserror::Error;
+use trace::Operation;
 
 /// An error occurred while registering a plugin. It is generic over the type of plugin id used by
 /// the plugin system; see [`PluginRegistry`] for more details.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Error)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RegisterPluginError<Id> {
     /// A plugin with the same id has already been registered.
     #[error("duplicate plugin `{0}` already registered")]
@@ -28,6 +72,7 @@ pub enum RegisterPluginError<Id> {
 /// An Error occurred while loading a plugin. It is generic over the type of hte plugin id used by
 /// the plugin system; see [`PluginRegistry`] for more details.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Error)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LoadPluginError<Id> {
     /// No plugin with the given plugin id is currently registered.
     #[error("plugin `{0}` not found")]
@@ -58,6 +103,95 @@ pub enum LoadPluginError<Id> {
         /// Explanation provided by the plugin for why the plugin rejected the dependency.
         reason: String,
     },
+    /// The plugin previously failed, and cannot be loaded or enabled until it is retried with
+    /// [`PluginRegistry::retry`].
+    #[error("plugin `{0}` previously failed and was not retried")]
+    PreviouslyFailed(Id),
+    /// The plugin was quarantined, and cannot be loaded or enabled until it is retried with
+    /// [`PluginRegistry::retry`].
+    #[error("plugin `{0}` is quarantined")]
+    Quarantined(Id),
+    /// The plugin panicked in a lifecycle method with panic isolation enabled, and is now failed;
+    /// see [`PluginRegistry::set_panic_isolation`].
+    #[error(transparent)]
+    Panicked(#[from] PluginPanic<Id>),
+    /// The plugin reported a failure from one of its fallible lifecycle methods, such as
+    /// [`AsyncPlugin::load_async`], or with [`Plugin::take_failure`].
+    #[error("plugin `{plugin}` failed: {reason}")]
+    Failed {
+        /// Plugin id of the plugin that failed.
+        plugin: Id,
+        /// Explanation provided by the plugin for the failure.
+        reason: String,
+    },
+    /// A hook registered by an async plugin while it loaded concurrently with other plugins was
+    /// rejected when added to the registry, such as for exceeding the cardinality of its slot or
+    /// for using a slot id that another plugin used with a different trait object.
+    /// With the `serde` feature, this error is serialized but cannot be deserialized, as it refers
+    /// to the slot by its static name.
+    #[error("hook of plugin `{plugin}` was rejected: {error}")]
+    #[cfg_attr(feature = "serde", serde(skip_deserializing))]
+    HookRejected {
+        /// Plugin id of the plugin that registered the hook.
+        plugin: Id,
+        /// The reason the hook was rejected.
+        error: RegisterHookError<Id>,
+    },
+}
+
+/// A problem with the hooks provided by the enabled plugins, as reported by
+/// [`PluginRegistry::validate`]. It is generic over the type of plugin id used by the plugin system;
+/// see [`PluginRegistry`] for more details.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Error)]
+pub enum SlotValidationError<Id> {
+    /// A [`SlotCardinality::ExactlyOne`] slot has no hook from an enabled plugin.
+    #[error("slot `{slot}` requires exactly one hook, but no enabled plugin provides one")]
+    MissingProvider {
+        /// Name of the hook slot.
+        slot: &'static str,
+    },
+    /// A slot accepting at most one hook has hooks from several enabled plugins.
+    #[error(
+        "slot `{slot}` accepts at most one hook, but enabled plugins {plugins:?} provide hooks"
+    )]
+    TooManyProviders {
+        /// Name of the hook slot.
+        slot: &'static str,
+        /// Plugin ids of the enabled plugins that provide hooks for the slot.
+        plugins: Vec<Id>,
+    },
+}
+
+/// A point-in-time listing of the plugins of a [`PluginRegistry`], taken with
+/// [`PluginRegistry::snapshot`] for diagnostics such as crash reports. With the `serde` feature, the
+/// snapshot can be serialized whenever the plugin manifests can.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegistrySnapshot<Manifest> {
+    /// The registered plugins, ordered by plugin id.
+    pub plugins: Vec<PluginSnapshot<Manifest>>,
+}
+
+/// The state of a single plugin in a [`RegistrySnapshot`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PluginSnapshot<Manifest> {
+    /// The manifest the plugin was registered with.
+    pub manifest: Manifest,
+    /// Whether the plugin was loaded.
+    pub loaded: bool,
+    /// Whether the plugin was enabled.
+    pub enabled: bool,
+}
+
+/// Why a plugin is active, as returned by [`PluginRegistry::load_activation`] and
+/// [`PluginRegistry::enable_activation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Activation {
+    /// Whether the plugin was activated explicitly, rather than only on behalf of its dependents.
+    pub explicit: bool,
+    /// Number of active plugins that list the plugin as a dependency.
+    pub dependents: usize,
 }
 
 /// Metadata about a plugin, including its id and required dependencies. The plugin host can provide
@@ -70,8 +204,9 @@ pub enum LoadPluginError<Id> {
 pub trait PluginManifest {
     /// Specifies the type used for plugin ids, which are used throughout the plugin system. This
     /// allows a plugin host to provide appropriate ids for its own needs, such as interned strings,
-    /// UUIDs, simple integers, etc.
-    type PluginId: Copy + Ord + Hash;
+    /// UUIDs, simple integers, etc. With the `tracing` feature, plugin ids must also implement
+    /// [`Debug`] to be recorded in spans; see [`TracePluginId`].
+    type PluginId: Copy + Ord + Hash + TracePluginId;
 
     /// Get the id of the plugin this manifest represents. This id should never change for a plugin.
     #[must_use]
@@ -96,12 +231,22 @@ fn dependencies(&self) -> &[Self::PluginId] {
     fn dependency_matches(&self, _dependency: &Self) -> Result<(), String> {
         Ok(())
     }
+
+    /// Validates a configuration supplied by the plugin host for this plugin against the schema of
+    /// configurations the plugin accepts, before it is passed to the plugin. A [`String`] [`Err`]
+    /// detailing the reason indicates an invalid configuration; an [`Ok`] result is a valid one.
+    ///
+    /// The default implementation accepts all configurations.
+    fn validate_config(&self, _config: &PluginConfig) -> Result<(), String> {
+        Ok(())
+    }
 }
 
 /// A default [`PluginManifest`] providing only the most basic required functionality of a manifest.
 /// It is generic over plugin id to still allow easy plugin host choice over the id type.
 /// It supports a basic plugin dependency list and a description of the plugin.
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SimplePluginManifest<Id = &'static str> {
     id: Id,
     description: Cow<'static, str>,
@@ -143,7 +288,7 @@ pub fn description(&self) -> &str {
 
 impl<Id> PluginManifest for SimplePluginManifest<Id>
 where
-    Id: Copy + Ord + Hash,
+    Id: Copy + Ord + Hash + TracePluginId,
 {
     type PluginId = Id;
 
@@ -188,6 +333,30 @@ fn enable(&mut self, _context: &mut Context) {}
 
     /// Called when the plugin host disables this plugin's hooks.
     fn disable(&mut self, _context: &mut Context) {}
+
+    /// Called by the plugin host right after [`Plugin::load`], [`Plugin::unload`],
+    /// [`Plugin::enable`] or [`Plugin::disable`] to take a failure the method could not report
+    /// itself, such as a trap in a sandboxed module. A failure returned as an explanation fails
+    /// the lifecycle step like a panic caught with [`PluginRegistry::set_panic_isolation`], except
+    /// that it is reported as [`LoadPluginError::Failed`]. It is also called after
+    /// [`AsyncPlugin::unload_async`] and [`AsyncPlugin::disable_async`], and on all loaded plugins
+    /// by [`PluginRegistry::handle_plugin_failures`].
+    ///
+    /// The default implementation never reports a failure.
+    fn take_failure(&mut self) -> Option<String> {
+        None
+    }
+
+    /// Called when the plugin host applies a new configuration to this loaded plugin with
+    /// [`PluginRegistry::reconfigure`]. Returning an [`Err`] with an explanation keeps the previous
+    /// configuration.
+    fn reconfigure(
+        &mut self,
+        _config: &PluginConfig,
+        _context: &mut Context,
+    ) -> Result<(), String> {
+        Ok(())
+    }
 }
 
 impl<Id, Context> dyn Plugin<Id, Context> {
@@ -207,14 +376,74 @@ pub fn downcast_mut<T: Plugin<Id, Context>>(&mut self) -> Option<&mut T> {
 /// Function signature of constructor for a plugin object.
 pub type FnPluginConstructor<Id, Context> = fn() -> Box<dyn Plugin<Id, Context>>;
 
+/// Constructor for an async plugin object that captures state, such as a compiled script.
+type SharedAsyncPluginConstructor<Id, Context> =
+    Arc<dyn Fn() -> Box<dyn AsyncPlugin<Id, Context>> + Send + Sync>;
+
+enum PluginConstructor<Id, Context> {
+    Sync(FnPluginConstructor<Id, Context>),
+    Async(FnAsyncPluginConstructor<Id, Context>),
+    // Present regardless of features, as plugin libraries share the layout of the registry
+    SharedAsync(SharedAsyncPluginConstructor<Id, Context>),
+}
+
+impl<Id, Context> Clone for PluginConstructor<Id, Context> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Sync(ctor) => Self::Sync(*ctor),
+            Self::Async(ctor) => Self::Async(*ctor),
+            Self::SharedAsync(ctor) => Self::SharedAsync(Arc::clone(ctor)),
+        }
+    }
+}
+
+impl<Id, Context> PluginConstructor<Id, Context> {
+    fn construct(&self) -> PluginInstance<Id, Context> {
+        match self {
+            Self::Sync(ctor) => PluginInstance::Sync(ctor()),
+            Self::Async(ctor) => PluginInstance::Async(ctor()),
+            Self::SharedAsync(ctor) => PluginInstance::Async(ctor()),
+        }
+    }
+}
+
+enum PluginInstance<Id, Context> {
+    Sync(Box<dyn Plugin<Id, Context>>),
+    Async(Box<dyn AsyncPlugin<Id, Context>>),
+}
+
+impl<Id, Context> PluginInstance<Id, Context> {
+    fn as_plugin(&self) -> &dyn Plugin<Id, Context> {
+        match self {
+            Self::Sync(plugin) => plugin.as_ref(),
+            Self::Async(plugin) => plugin.as_ref(),
+        }
+    }
+
+    fn as_plugin_mut(&mut self) -> &mut dyn Plugin<Id, Context> {
+        match self {
+            Self::Sync(plugin) => plugin.as_mut(),
+            Self::Async(plugin) => plugin.as_mut(),
+        }
+    }
+}
+
 struct PluginState<Manifest, Context>
 where
     Manifest: PluginManifest,
 {
     manifest: Manifest,
     enabled: bool,
-    ctor: Option<FnPluginConstructor<Manifest::PluginId, Context>>,
-    plugin: Option<Box<dyn Plugin<Manifest::PluginId, Context>>>,
+    explicit_enable: bool,
+    explicit_load: bool,
+    ctor: Option<PluginConstructor<Manifest::PluginId, Context>>,
+    plugin: Option<PluginInstance<Manifest::PluginId, Context>>,
+    resources: PluginResources,
+    failure: Option<LoadPluginError<Manifest::PluginId>>,
+    quarantined: bool,
+    transition: Option<Transition>,
+    // Dropped last, as the manifest, constructor and plugin may live in the library
+    library: Option<LibraryHandle>,
 }
 
 impl<Manifest, Context> PluginState<Manifest, Context>
@@ -223,14 +452,21 @@ impl<Manifest, Context> PluginState<Manifest, Context>
 {
     fn new(
         manifest: Manifest,
-        ctor: Option<FnPluginConstructor<Manifest::PluginId, Context>>,
-        plugin: Option<Box<dyn Plugin<Manifest::PluginId, Context>>>,
+        ctor: Option<PluginConstructor<Manifest::PluginId, Context>>,
+        plugin: Option<PluginInstance<Manifest::PluginId, Context>>,
     ) -> Self {
         Self {
             manifest,
             enabled: false,
+            explicit_enable: false,
+            explicit_load: false,
             ctor,
             plugin,
+            resources: PluginResources::default(),
+            failure: None,
+            quarantined: false,
+            transition: None,
+            library: None,
         }
     }
 }
@@ -243,6 +479,9 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("PluginState")
             .field("manifest", &self.manifest)
             .field("enabled", &self.enabled)
+            .field("explicit_enable", &self.explicit_enable)
+            .field("explicit_load", &self.explicit_load)
+            .field("resources", &self.resources)
             .finish_non_exhaustive()
     }
 }
@@ -262,6 +501,11 @@ pub struct PluginRegistry<Manifest = SimplePluginManifest, Context = (), S = Ran
     plugins: HashMap<Manifest::PluginId, PluginState<Manifest, Context>, S>,
     hooks: HookRegistry<Manifest::PluginId, S>,
     dependency_graph: GraphMap<Manifest::PluginId, usize, Directed, S>,
+    isolate_panics: bool,
+    metrics: LifecycleMetrics<Manifest::PluginId>,
+    // Dropped last, as plugin ids may point into the libraries
+    #[cfg(feature = "dylib")]
+    libraries: Vec<LibraryHandle>,
 }
 
 impl<Manifest, Context, S> PluginRegistry<Manifest, Context, S>
@@ -276,6 +520,10 @@ pub fn with_hasher(hash_builder: S) -> Self {
             plugins: HashMap::with_hasher(hash_builder.clone()),
             hooks: HookRegistry::with_hasher(hash_builder.clone()),
             dependency_graph: GraphMap::with_capacity_and_hasher(0, 0, hash_builder),
+            isolate_panics: false,
+            metrics: LifecycleMetrics::default(),
+            #[cfg(feature = "dylib")]
+            libraries: Vec::new(),
         }
     }
 
@@ -287,6 +535,10 @@ pub fn with_capacity_and_hasher(count: usize, hash_builder: S) -> Self {
             plugins: HashMap::with_capacity_and_hasher(count, hash_builder.clone()),
             hooks: HookRegistry::with_hasher(hash_builder.clone()),
             dependency_graph: GraphMap::with_capacity_and_hasher(count, 0, hash_builder),
+            isolate_panics: false,
+            metrics: LifecycleMetrics::default(),
+            #[cfg(feature = "dylib")]
+            libraries: Vec::new(),
         }
     }
 
@@ -322,6 +574,10 @@ pub fn new() -> Self {
             plugins: HashMap::new(),
             hooks: HookRegistry::new(),
             dependency_graph: DiGraphMap::new(),
+            isolate_panics: false,
+            metrics: LifecycleMetrics::default(),
+            #[cfg(feature = "dylib")]
+            libraries: Vec::new(),
         }
     }
 
@@ -332,6 +588,10 @@ pub fn with_capacity(count: usize) -> Self {
             plugins: HashMap::with_capacity(count),
             hooks: HookRegistry::new(),
             dependency_graph: GraphMap::with_capacity(count, 0),
+            isolate_panics: false,
+            metrics: LifecycleMetrics::default(),
+            #[cfg(feature = "dylib")]
+            libraries: Vec::new(),
         }
     }
 
@@ -371,6 +631,84 @@ pub fn is_enabled(&self, id: Manifest::PluginId) -> bool {
         self.plugins.get(&id).is_some_and(|state| state.enabled)
     }
 
+    /// Take a snapshot of all registered plugins with their manifests and whether they are loaded
+    /// and enabled.
+    #[must_use]
+    pub fn snapshot(&self) -> RegistrySnapshot<Manifest>
+    where
+        Manifest: Clone,
+    {
+        let mut plugins = self
+            .plugins
+            .iter()
+            .map(|(&id, state)| {
+                (
+                    id,
+                    PluginSnapshot {
+                        manifest: state.manifest.clone(),
+                        loaded: state.plugin.is_some(),
+                        enabled: state.enabled,
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        plugins.sort_unstable_by_key(|&(id, _)| id);
+        RegistrySnapshot {
+            plugins: plugins.into_iter().map(|(_, plugin)| plugin).collect(),
+        }
+    }
+
+    /// Determine whether a plugin with the given plugin id was explicitly enabled with
+    /// [`PluginRegistry::enable`], rather than only enabled as a dependency of another plugin.
+    #[must_use]
+    pub fn is_explicitly_enabled(&self, id: Manifest::PluginId) -> bool {
+        self.plugins
+            .get(&id)
+            .is_some_and(|state| state.enabled && state.explicit_enable)
+    }
+
+    /// Determine whether a plugin with the given plugin id was explicitly loaded with
+    /// [`PluginRegistry::load`] or [`PluginRegistry::enable`], rather than only loaded as a
+    /// dependency of another plugin.
+    #[must_use]
+    pub fn is_explicitly_loaded(&self, id: Manifest::PluginId) -> bool {
+        self.plugins
+            .get(&id)
+            .is_some_and(|state| state.plugin.is_some() && state.explicit_load)
+    }
+
+    /// Get why the plugin with the given plugin id is loaded, or [`None`] if it is not loaded.
+    #[must_use]
+    pub fn load_activation(&self, id: Manifest::PluginId) -> Option<Activation> {
+        let state = self.plugins.get(&id)?;
+        state.plugin.as_ref()?;
+        Some(Activation {
+            explicit: state.explicit_load,
+            dependents: self.count_dependents(id, |state| state.plugin.is_some()),
+        })
+    }
+
+    /// Get why the plugin with the given plugin id is enabled, or [`None`] if it is not enabled.
+    #[must_use]
+    pub fn enable_activation(&self, id: Manifest::PluginId) -> Option<Activation> {
+        let state = self.plugins.get(&id).filter(|state| state.enabled)?;
+        Some(Activation {
+            explicit: state.explicit_enable,
+            dependents: self.count_dependents(id, |state| state.enabled),
+        })
+    }
+
+    fn count_dependents(
+        &self,
+        id: Manifest::PluginId,
+        active: impl Fn(&PluginState<Manifest, Context>) -> bool,
+    ) -> usize {
+        self.dependency_graph
+            .neighbors_directed(id, Incoming)
+            .filter(|dependent| self.plugins.get(dependent).is_some_and(&active))
+            .count()
+    }
+
     /// Get a reference to the hook registry for managing plugin hooks.
     #[must_use]
     pub fn hooks(&self) -> &HookRegistry<Manifest::PluginId> {
@@ -445,8 +783,17 @@ pub fn register(
         &mut self,
         manifest: Manifest,
         ctor: Option<FnPluginConstructor<Manifest::PluginId, Context>>,
+    ) -> Result<Manifest::PluginId, RegisterPluginError<Manifest::PluginId>> {
+        self.register_state(manifest, ctor.map(PluginConstructor::Sync))
+    }
+
+    fn register_state(
+        &mut self,
+        manifest: Manifest,
+        ctor: Option<PluginConstructor<Manifest::PluginId, Context>>,
     ) -> Result<Manifest::PluginId, RegisterPluginError<Manifest::PluginId>> {
         let id = manifest.id();
+        let _span = trace::enter_operation(Operation::Register, &id, None);
         if let hash_map::Entry::Vacant(e) = self.plugins.entry(id) {
             let state = &mut e.insert(PluginState::new(manifest, ctor, None));
 
@@ -455,6 +802,8 @@ pub fn register(
             for (i, &dep) in state.manifest.dependencies().iter().enumerate() {
                 self.dependency_graph.add_edge(id, dep, i);
             }
+            self.hooks
+                .set_dependencies(id, state.manifest.dependencies());
             // Check for cycles
             if algo::is_cyclic_directed(&self.dependency_graph) {
                 // Rollback graph additions
@@ -486,6 +835,61 @@ pub fn register(
         }
     }
 
+    /// Remove the registered plugin with the given id without calling any of its methods. The
+    /// plugin must not be loaded.
+    pub(crate) fn unregister(&mut self, id: Manifest::PluginId) {
+        debug_assert!(!self.is_loaded(id));
+        self.plugins.remove(&id);
+        self.hooks.remove_dependencies(id);
+        self.hooks.remove_config(id);
+
+        // Cleanup dependency graph, removing node if it has not incoming dependencies
+        if self
+            .dependency_graph
+            .neighbors_directed(id, Incoming)
+            .next()
+            .is_none()
+        {
+            self.dependency_graph.remove_node(id);
+        }
+    }
+
+    /// Validate the hooks provided by the currently enabled plugins against the cardinality of
+    /// every slot known to the hook registry. Slots become known when declared with
+    /// [`HookRegistry::declare_slot`] or when any hook is registered to them.
+    ///
+    /// # Errors
+    ///
+    /// Returns every problem found, sorted by slot name. A [`SlotCardinality::ExactlyOne`] slot
+    /// without a hook from an enabled plugin is reported as
+    /// [`SlotValidationError::MissingProvider`], while a slot accepting at most one hook with hooks
+    /// from more than one enabled plugin is reported as [`SlotValidationError::TooManyProviders`].
+    pub fn validate(&self) -> Result<(), Vec<SlotValidationError<Manifest::PluginId>>> {
+        let mut errors = Vec::new();
+        for (info, mut plugins) in self.hooks.slot_providers() {
+            plugins.retain(|&plugin| self.is_enabled(plugin));
+            match info.cardinality() {
+                SlotCardinality::ExactlyOne if plugins.is_empty() => {
+                    errors.push(SlotValidationError::MissingProvider { slot: info.name() });
+                }
+                cardinality if cardinality.is_single() && plugins.len() > 1 => {
+                    plugins.sort_unstable();
+                    errors.push(SlotValidationError::TooManyProviders {
+                        slot: info.name(),
+                        plugins,
+                    });
+                }
+                _ => {}
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            errors.sort_unstable();
+            Err(errors)
+        }
+    }
+
     /// Get a reference to the plugin manifest by its id if that plugin has been registered.
     #[must_use]
     pub fn get_manifest(&self, id: Manifest::PluginId) -> Option<&Manifest> {
@@ -499,7 +903,11 @@ pub fn get_loaded_plugin(
         &self,
         id: Manifest::PluginId,
     ) -> Option<&dyn Plugin<Manifest::PluginId, Context>> {
-        self.plugins.get(&id)?.plugin.as_ref().map(AsRef::as_ref)
+        self.plugins
+            .get(&id)?
+            .plugin
+            .as_ref()
+            .map(PluginInstance::as_plugin)
     }
 
     /// Get a mutable reference to the dyn plugin object of the plugin with the given id if it is
@@ -513,7 +921,7 @@ pub fn get_loaded_plugin_mut(
             .get_mut(&id)?
             .plugin
             .as_mut()
-            .map(AsMut::as_mut)
+            .map(PluginInstance::as_plugin_mut)
     }
 
     /// Get a reference to the plugin with the given id, if it is currently loaded and if the
@@ -533,7 +941,7 @@ pub fn get_loaded_mut<T>(&mut self, id: Manifest::PluginId) -> Option<&mut T>
     where
         T: Plugin<Manifest::PluginId, Context>,
     {
-        self.plugins.get_mut(&id)?.plugin.as_mut()?.downcast_mut()
+        self.get_loaded_plugin_mut(id)?.downcast_mut()
     }
 
     /// Get a reference to the dyn plugin object of the plugin with the given id if it is currently
@@ -545,7 +953,7 @@ pub fn get_enabled_plugin(
     ) -> Option<&dyn Plugin<Manifest::PluginId, Context>> {
         let state = self.plugins.get(&id)?;
         if state.enabled {
-            state.plugin.as_ref().map(AsRef::as_ref)
+            state.plugin.as_ref().map(PluginInstance::as_plugin)
         } else {
             None
         }
@@ -560,7 +968,7 @@ pub fn get_enabled_plugin_mut(
     ) -> Option<&mut dyn Plugin<Manifest::PluginId, Context>> {
         let state = self.plugins.get_mut(&id)?;
         if state.enabled {
-            state.plugin.as_mut().map(AsMut::as_mut)
+            state.plugin.as_mut().map(PluginInstance::as_plugin_mut)
         } else {
             None
         }
@@ -606,6 +1014,7 @@ pub fn remove(
         impl IntoIterator<Item = Manifest::PluginId>,
         impl IntoIterator<Item = Manifest::PluginId>,
     ) {
+        let _span = trace::enter_operation(Operation::Remove, &id, None);
         let mut result = false;
         let mut unloaded = Vec::new();
         let mut disabled = Vec::new();
@@ -615,18 +1024,7 @@ impl IntoIterator<Item = Manifest::PluginId>,
             unloaded.extend(dep_unloaded);
             disabled.extend(dep_disabled);
 
-            self.plugins.remove(&id);
-
-            // Cleanup dependency graph, removing node if it has not incoming dependencies
-            if self
-                .dependency_graph
-                .neighbors_directed(id, Incoming)
-                .next()
-                .is_none()
-            {
-                self.dependency_graph.remove_node(id);
-            }
-
+            self.unregister(id);
             result = true;
         }
         (result, unloaded, disabled)
@@ -636,7 +1034,9 @@ impl IntoIterator<Item = Manifest::PluginId>,
     /// `context` to the plugin's [`Plugin::load`] method. If this plugin lists any dependencies
     /// in its manifest, attempts to load all of its dependencies before loading the specified
     /// plugin. The plugin will be created using the construction function registered with
-    /// the plugin. See [`PluginRegistry::register`] for more details.
+    /// the plugin. See [`PluginRegistry::register`] for more details. The plugin is marked as
+    /// explicitly loaded, while dependencies loaded along with it are only implicitly loaded; see
+    /// [`PluginRegistry::unload_and_release`].
     ///
     /// Use [`PluginRegistry::load_with`] to bypass the plugin constructor and use a provided plugin
     /// instance instead.
@@ -654,25 +1054,79 @@ impl IntoIterator<Item = Manifest::PluginId>,
     ///
     /// If the plugin's manifest determines a dependency does not match using
     /// [`PluginManifest::dependency_matches`], returns [`LoadPluginError::DependencyMismatch`].
+    ///
+    /// Returns [`LoadPluginError::PreviouslyFailed`] or [`LoadPluginError::Quarantined`] if the plugin or a
+    /// dependency previously failed or is quarantined, and [`LoadPluginError::Panicked`] if a plugin
+    /// panics with panic isolation enabled; see [`PluginRegistry::set_panic_isolation`].
     pub fn load(
         &mut self,
         id: Manifest::PluginId,
         context: &mut Context,
     ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
-        if self
-            .plugins
-            .get_mut(&id)
-            .ok_or(LoadPluginError::NotFound(id))?
-            .plugin
-            .is_none()
-        {
+        self.load_plugin(id, None, context)?;
+        self.plugins.get_mut(&id).unwrap().explicit_load = true;
+        Ok(())
+    }
+
+    fn load_plugin(
+        &mut self,
+        id: Manifest::PluginId,
+        parent: Option<Manifest::PluginId>,
+        context: &mut Context,
+    ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
+        if self.loadable(id)? {
+            let _span = trace::enter_operation(Operation::Load, &id, parent.as_ref());
+            let started = Instant::now();
             self.load_dependencies(id, context)?;
 
-            let state = &mut self.plugins.get_mut(&id).unwrap();
-            state
-                .plugin
-                .insert(state.ctor.ok_or(LoadPluginError::MissingConstructor(id))?())
-                .load(&mut self.hooks, context);
+            let ctor = self.plugins[&id]
+                .ctor
+                .as_ref()
+                .ok_or(LoadPluginError::MissingConstructor(id))?
+                .clone();
+            self.load_instance(id, || ctor.construct(), started, context)?;
+        }
+        Ok(())
+    }
+
+    /// Determine whether the plugin needs loading, failing if it cannot be loaded.
+    fn loadable(
+        &self,
+        id: Manifest::PluginId,
+    ) -> Result<bool, LoadPluginError<Manifest::PluginId>> {
+        let state = self.plugins.get(&id).ok_or(LoadPluginError::NotFound(id))?;
+        state.check_usable(id)?;
+        Ok(state.plugin.is_none())
+    }
+
+    fn load_instance(
+        &mut self,
+        id: Manifest::PluginId,
+        instance: impl FnOnce() -> PluginInstance<Manifest::PluginId, Context>,
+        started: Instant,
+        context: &mut Context,
+    ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
+        let state = self.plugins.get_mut(&id).unwrap();
+        let hooks = &mut self.hooks;
+        state.transition = Some(Transition::Loading);
+        let own = Instant::now();
+        let result = isolate_lifecycle(self.isolate_panics, id, LifecyclePhase::Load, || {
+            let plugin = state.plugin.insert(instance()).as_plugin_mut();
+            hooks.with_plugin(id, state.library.clone(), &mut state.resources, |hooks| {
+                plugin.load(hooks, context);
+            });
+            plugin.take_failure()
+        });
+        let own = own.elapsed();
+        state.transition = None;
+        if result.is_err() {
+            state.plugin = None;
+            state.resources.clear();
+        }
+        self.record_timing(id, LifecyclePhase::Load, own, started);
+        if let Err(error) = result {
+            self.fail_plugin(id, error.clone(), context);
+            return Err(error);
         }
         Ok(())
     }
@@ -682,6 +1136,7 @@ fn load_dependencies(
         id: Manifest::PluginId,
         context: &mut Context,
     ) -> Result<(), LoadPluginError<<Manifest as PluginManifest>::PluginId>> {
+        let _span = trace::enter_operation(Operation::LoadDependencies, &id, None);
         let mut dependencies = self
             .dependency_graph
             .edges(id)
@@ -707,7 +1162,7 @@ fn load_dependencies(
                         reason,
                     })?;
 
-                self.load(dep, context)?;
+                self.load_plugin(dep, Some(id), context)?;
             }
         }
         Ok(())
@@ -734,6 +1189,10 @@ fn load_dependencies(
     ///
     /// If the plugin's manifest determines a dependency does not match using
     /// [`PluginManifest::dependency_matches`], returns [`LoadPluginError::DependencyMismatch`].
+    ///
+    /// Returns [`LoadPluginError::PreviouslyFailed`] or [`LoadPluginError::Quarantined`] if the plugin or a
+    /// dependency previously failed or is quarantined, and [`LoadPluginError::Panicked`] if a plugin
+    /// panics with panic isolation enabled; see [`PluginRegistry::set_panic_isolation`].
     pub fn load_with<P>(
         &mut self,
         id: Manifest::PluginId,
@@ -743,21 +1202,13 @@ pub fn load_with<P>(
     where
         P: Into<Box<dyn Plugin<Manifest::PluginId, Context>>>,
     {
-        if self
-            .plugins
-            .get_mut(&id)
-            .ok_or(LoadPluginError::NotFound(id))?
-            .plugin
-            .is_none()
-        {
+        if self.loadable(id)? {
+            let _span = trace::enter_operation(Operation::Load, &id, None);
+            let started = Instant::now();
             self.load_dependencies(id, context)?;
-
-            let state = &mut self.plugins.get_mut(&id).unwrap();
-            state
-                .plugin
-                .insert(plugin.into())
-                .load(&mut self.hooks, context);
+            self.load_instance(id, || PluginInstance::Sync(plugin.into()), started, context)?;
         }
+        self.plugins.get_mut(&id).unwrap().explicit_load = true;
         Ok(())
     }
 
@@ -774,6 +1225,15 @@ pub fn unload(
         impl IntoIterator<Item = Manifest::PluginId>,
         impl IntoIterator<Item = Manifest::PluginId>,
     ) {
+        self.unload_plugin(id, None, context)
+    }
+
+    fn unload_plugin(
+        &mut self,
+        id: Manifest::PluginId,
+        parent: Option<Manifest::PluginId>,
+        context: &mut Context,
+    ) -> (Vec<Manifest::PluginId>, Vec<Manifest::PluginId>) {
         let mut unloaded = Vec::new();
         let mut disabled = Vec::new();
         if self
@@ -781,25 +1241,42 @@ impl IntoIterator<Item = Manifest::PluginId>,
             .get_mut(&id)
             .is_some_and(|state| state.plugin.is_some())
         {
+            let _span = trace::enter_operation(Operation::Unload, &id, parent.as_ref());
+            let started = Instant::now();
+
             // Disable first
-            disabled.extend(self.disable(id, context));
+            disabled.extend(self.disable_plugin(id, parent, context));
 
             // Unload downstream dependents first
             let mut dependents = self
                 .dependency_graph
-                .edges(id)
-                .map(|(_, d, &i)| (d, i))
+                .edges_directed(id, Incoming)
+                .map(|(d, _, &i)| (d, i))
                 .collect::<Vec<_>>();
             dependents.sort_unstable_by_key(|(_, i)| *i);
             for (dep, _) in dependents.into_iter().rev() {
-                let (dep_unloaded, dep_disabled) = self.unload(dep, context);
+                let (dep_unloaded, dep_disabled) = self.unload_plugin(dep, Some(id), context);
                 unloaded.extend(dep_unloaded);
                 disabled.extend(dep_disabled);
             }
 
             let state = &mut self.plugins.get_mut(&id).unwrap();
-            state.plugin.as_mut().unwrap().unload(context);
+            let plugin = state.plugin.as_mut().unwrap().as_plugin_mut();
+            let own = Instant::now();
+            let result = isolate_lifecycle(self.isolate_panics, id, LifecyclePhase::Unload, || {
+                plugin.unload(context);
+                plugin.take_failure()
+            });
+            let own = own.elapsed();
+            state.plugin = None;
+            state.explicit_load = false;
             self.hooks.remove_plugin_hooks(id);
+            self.hooks.withdraw_services(id);
+            self.plugins.get_mut(&id).unwrap().resources.clear();
+            self.record_timing(id, LifecyclePhase::Unload, own, started);
+            if let Err(error) = result {
+                self.fail_plugin(id, error, context);
+            }
             unloaded.push(id);
         }
         (unloaded, disabled)
@@ -809,7 +1286,10 @@ impl IntoIterator<Item = Manifest::PluginId>,
     /// `context` to the plugin's [`Plugin::enable`] method. If this plugin lists any dependencies
     /// in its manifest, attempts to enable all of its dependencies before enabling the specified
     /// plugin. If the plugin has not been loaded yet, will [`PluginRegistry::load`] the plugin
-    /// first.
+    /// first. The plugin is marked as explicitly enabled, while dependencies enabled along with it
+    /// are only implicitly enabled; see [`PluginRegistry::is_explicitly_enabled`]. Enabling a
+    /// plugin resumes its hooks suspended by the circuit breaker of the hook registry and resets
+    /// their failure counts.
     ///
     /// # Errors
     ///
@@ -824,10 +1304,27 @@ impl IntoIterator<Item = Manifest::PluginId>,
     ///
     /// If the plugin's manifest determines a dependency does not match using
     /// [`PluginManifest::dependency_matches`], returns [`LoadPluginError::DependencyMismatch`].
+    ///
+    /// Returns [`LoadPluginError::PreviouslyFailed`] or [`LoadPluginError::Quarantined`] if the plugin or a
+    /// dependency previously failed or is quarantined, and [`LoadPluginError::Panicked`] if a plugin
+    /// panics with panic isolation enabled; see [`PluginRegistry::set_panic_isolation`].
     pub fn enable(
         &mut self,
         id: Manifest::PluginId,
         context: &mut Context,
+    ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
+        self.enable_plugin(id, None, context)?;
+        let state = self.plugins.get_mut(&id).unwrap();
+        state.explicit_enable = true;
+        state.explicit_load = true;
+        Ok(())
+    }
+
+    fn enable_plugin(
+        &mut self,
+        id: Manifest::PluginId,
+        parent: Option<Manifest::PluginId>,
+        context: &mut Context,
     ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
         if !self
             .plugins
@@ -835,8 +1332,11 @@ pub fn enable(
             .ok_or(LoadPluginError::NotFound(id))?
             .enabled
         {
+            let _span = trace::enter_operation(Operation::Enable, &id, parent.as_ref());
+
             // Ensure plugin already loaded
-            self.load(id, context)?;
+            self.load_plugin(id, parent, context)?;
+            let started = Instant::now();
 
             // Ensure dependencies are all enabled
             let mut dependencies = self
@@ -846,12 +1346,24 @@ pub fn enable(
                 .collect::<Vec<_>>();
             dependencies.sort_unstable_by_key(|(_, i)| *i);
             for (dep, _) in dependencies {
-                self.enable(dep, context)?;
+                self.enable_plugin(dep, Some(id), context)?;
             }
 
             let state = &mut self.plugins.get_mut(&id).unwrap();
-            state.plugin.as_mut().unwrap().enable(context);
-            state.enabled = true;
+            let plugin = state.plugin.as_mut().unwrap().as_plugin_mut();
+            let own = Instant::now();
+            let result = isolate_lifecycle(self.isolate_panics, id, LifecyclePhase::Enable, || {
+                plugin.enable(context);
+                plugin.take_failure()
+            });
+            let own = own.elapsed();
+            state.enabled = result.is_ok();
+            self.record_timing(id, LifecyclePhase::Enable, own, started);
+            if let Err(error) = result {
+                self.fail_plugin(id, error.clone(), context);
+                return Err(error);
+            }
+            self.hooks.reset_breaker(id);
         }
         Ok(())
     }
@@ -864,26 +1376,118 @@ pub fn disable(
         id: Manifest::PluginId,
         context: &mut Context,
     ) -> impl IntoIterator<Item = Manifest::PluginId> {
+        self.disable_plugin(id, None, context)
+    }
+
+    fn disable_plugin(
+        &mut self,
+        id: Manifest::PluginId,
+        parent: Option<Manifest::PluginId>,
+        context: &mut Context,
+    ) -> Vec<Manifest::PluginId> {
         let mut disabled = Vec::new();
         if self.plugins.get_mut(&id).is_some_and(|state| state.enabled) {
+            let _span = trace::enter_operation(Operation::Disable, &id, parent.as_ref());
+            let started = Instant::now();
+
             // Ensure downstream dependents are all disabled first
             let mut dependents = self
                 .dependency_graph
                 .edges_directed(id, Incoming)
-                .map(|(_, d, &i)| (d, i))
+                .map(|(d, _, &i)| (d, i))
                 .collect::<Vec<_>>();
             dependents.sort_unstable_by_key(|(_, i)| *i);
             for (dep, _) in dependents.into_iter().rev() {
-                disabled.extend(self.disable(dep, context));
+                disabled.extend(self.disable_plugin(dep, Some(id), context));
             }
 
             let state = &mut self.plugins.get_mut(&id).unwrap();
-            state.plugin.as_mut().unwrap().disable(context);
+            let plugin = state.plugin.as_mut().unwrap().as_plugin_mut();
+            state.transition = Some(Transition::Disabling);
+            let own = Instant::now();
+            let result =
+                isolate_lifecycle(self.isolate_panics, id, LifecyclePhase::Disable, || {
+                    plugin.disable(context);
+                    plugin.take_failure()
+                });
+            let own = own.elapsed();
+            state.transition = None;
             state.enabled = false;
+            state.explicit_enable = false;
             disabled.push(id);
+            self.record_timing(id, LifecyclePhase::Disable, own, started);
+            if let Err(error) = result {
+                self.fail_plugin(id, error, context);
+            }
         }
         disabled
     }
+
+    /// Disable the plugin with the given plugin id as with [`PluginRegistry::disable`], then also
+    /// disable any dependencies that were only enabled on behalf of the disabled plugins and are no
+    /// longer needed by any enabled plugin. Explicitly enabled dependencies are kept enabled.
+    /// Returns an iterator over all the plugin ids disabled.
+    pub fn disable_and_release(
+        &mut self,
+        id: Manifest::PluginId,
+        context: &mut Context,
+    ) -> impl IntoIterator<Item = Manifest::PluginId> {
+        let mut disabled = self.disable(id, context).into_iter().collect::<Vec<_>>();
+        let mut candidates = self.release_candidates(&disabled);
+        while let Some(dep) = candidates.pop() {
+            if self.enable_activation(dep) == Some(Activation::default()) {
+                let released = self.disable(dep, context).into_iter().collect::<Vec<_>>();
+                candidates.extend(self.release_candidates(&released));
+                disabled.extend(released);
+            }
+        }
+        disabled
+    }
+
+    /// Unload the plugin with the given plugin id as with [`PluginRegistry::unload`], then also
+    /// unload any dependencies that were only loaded on behalf of the unloaded plugins and are no
+    /// longer needed by any loaded plugin. Explicitly loaded dependencies are kept loaded. Returns
+    /// both an iterator over all the plugin ids unloaded and an iterator over all the plugin ids
+    /// disabled.
+    pub fn unload_and_release(
+        &mut self,
+        id: Manifest::PluginId,
+        context: &mut Context,
+    ) -> (
+        impl IntoIterator<Item = Manifest::PluginId>,
+        impl IntoIterator<Item = Manifest::PluginId>,
+    ) {
+        let (unloaded, disabled) = self.unload(id, context);
+        let mut unloaded = unloaded.into_iter().collect::<Vec<_>>();
+        let mut disabled = disabled.into_iter().collect::<Vec<_>>();
+        let mut candidates = self.release_candidates(&unloaded);
+        while let Some(dep) = candidates.pop() {
+            if self.load_activation(dep) == Some(Activation::default()) {
+                let (dep_unloaded, dep_disabled) = self.unload(dep, context);
+                let dep_unloaded = dep_unloaded.into_iter().collect::<Vec<_>>();
+                disabled.extend(dep_disabled);
+                candidates.extend(self.release_candidates(&dep_unloaded));
+                unloaded.extend(dep_unloaded);
+            }
+        }
+        (unloaded, disabled)
+    }
+
+    /// Dependencies of the given plugins, in reverse dependency order so they are released in
+    /// dependency order when popped.
+    fn release_candidates(&self, ids: &[Manifest::PluginId]) -> Vec<Manifest::PluginId> {
+        let mut candidates = Vec::new();
+        for &id in ids.iter().rev() {
+            let mut dependencies = self
+                .dependency_graph
+                .edges(id)
+                .map(|(_, d, &i)| (d, i))
+                .collect::<Vec<_>>();
+            dependencies.sort_unstable_by_key(|(_, i)| *i);
+            candidates.extend(dependencies.into_iter().rev().map(|(d, _)| d));
+        }
+        candidates
+    }
 }
 
 impl<Manifest, Context, S> Default for PluginRegistry<Manifest, Context, S>
@@ -896,6 +1500,10 @@ fn default() -> Self {
             plugins: HashMap::default(),
             hooks: HookRegistry::default(),
             dependency_graph: GraphMap::default(),
+            isolate_panics: false,
+            metrics: LifecycleMetrics::default(),
+            #[cfg(feature = "dylib")]
+            libraries: Vec::new(),
         }
     }
 }
@@ -911,3 +1519,209 @@ fn from_iter<T: IntoIterator<Item = &'a fn(&mut Self)>>(iter: T) -> Self {
         PluginRegistry::from_initializers_with_hasher(iter, S::default())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Activation, HookRegistry, Plugin, PluginManifest, PluginRegistry, SimplePluginManifest,
+        SlotValidationError, hook_slot,
+    };
+    #[cfg(feature = "serde")]
+    use crate::{LoadPluginError, RegistrySnapshot};
+
+    struct TestPlugin;
+
+    impl Plugin for TestPlugin {}
+
+    trait Renderer: Send + Sync {}
+
+    impl Renderer for () {}
+
+    hook_slot!(RendererSlot: dyn Renderer {
+        name: "renderer",
+        cardinality: ExactlyOne,
+    });
+
+    struct RendererPlugin;
+
+    impl Plugin for RendererPlugin {
+        fn load(&mut self, hooks: &mut HookRegistry, _context: &mut ()) {
+            assert!(
+                hooks
+                    .register::<RendererSlot>(Box::new(()), "renderer", None)
+                    .is_ok()
+            );
+        }
+    }
+
+    #[test]
+    fn validate_exactly_one_slot() {
+        let mut plugins = PluginRegistry::new();
+        plugins.hooks_mut().declare_slot::<RendererSlot>().unwrap();
+        plugins
+            .register(
+                SimplePluginManifest::new("renderer", ""),
+                Some(|| Box::new(RendererPlugin)),
+            )
+            .unwrap();
+
+        let missing = Err(vec![SlotValidationError::MissingProvider {
+            slot: "renderer",
+        }]);
+        assert_eq!(plugins.validate(), missing);
+
+        // Hooks of plugins that are only loaded do not count as provided
+        plugins.load("renderer", &mut ()).unwrap();
+        assert_eq!(plugins.validate(), missing);
+
+        plugins.enable("renderer", &mut ()).unwrap();
+        assert_eq!(plugins.validate(), Ok(()));
+    }
+
+    #[test]
+    fn unload_cascades_to_dependents() {
+        let mut plugins = PluginRegistry::new();
+        plugins
+            .register(
+                SimplePluginManifest::new("base", ""),
+                Some(|| Box::new(TestPlugin)),
+            )
+            .unwrap();
+        plugins
+            .register(
+                SimplePluginManifest::with_dependencies("dependent", "", vec!["base"]),
+                Some(|| Box::new(TestPlugin)),
+            )
+            .unwrap();
+
+        plugins.enable("dependent", &mut ()).unwrap();
+        assert!(plugins.is_enabled("base"));
+
+        assert_eq!(
+            plugins
+                .disable("base", &mut ())
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["dependent", "base"]
+        );
+        let mut context = ();
+        let (unloaded, _) = plugins.unload("base", &mut context);
+        assert_eq!(
+            unloaded.into_iter().collect::<Vec<_>>(),
+            vec!["dependent", "base"]
+        );
+        assert_eq!(plugins.loaded_plugin_count(), 0);
+    }
+
+    #[test]
+    fn registry_snapshot() {
+        let mut plugins = PluginRegistry::new();
+        plugins
+            .register(SimplePluginManifest::new("b", "second"), None)
+            .unwrap();
+        plugins
+            .register(
+                SimplePluginManifest::new("a", "first"),
+                Some(|| Box::new(TestPlugin)),
+            )
+            .unwrap();
+        plugins.load("a", &mut ()).unwrap();
+
+        let snapshot = plugins.snapshot();
+        assert_eq!(
+            snapshot
+                .plugins
+                .iter()
+                .map(|p| (p.manifest.id(), p.loaded, p.enabled))
+                .collect::<Vec<_>>(),
+            vec![("a", true, false), ("b", false, false)]
+        );
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&snapshot).unwrap();
+            let parsed: RegistrySnapshot<SimplePluginManifest<String>> =
+                serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed.plugins[1].manifest.description(), "second");
+            assert!(parsed.plugins[0].loaded);
+
+            let error = LoadPluginError::MissingConstructor("b");
+            let json = serde_json::to_string(&error).unwrap();
+            assert_eq!(json, r#"{"MissingConstructor":"b"}"#);
+        }
+    }
+
+    #[test]
+    fn release_orphaned_dependencies() {
+        let mut plugins = PluginRegistry::new();
+        for (id, dependencies) in [
+            ("core", vec![]),
+            ("util", vec!["core"]),
+            ("app", vec!["util", "core"]),
+            ("tool", vec!["core"]),
+        ] {
+            plugins
+                .register(
+                    SimplePluginManifest::with_dependencies(id, "", dependencies),
+                    Some(|| Box::new(TestPlugin)),
+                )
+                .unwrap();
+        }
+        plugins.enable("app", &mut ()).unwrap();
+        plugins.enable("tool", &mut ()).unwrap();
+        assert!(plugins.is_explicitly_loaded("app"));
+        assert!(!plugins.is_explicitly_loaded("util"));
+        assert_eq!(
+            plugins.enable_activation("core"),
+            Some(Activation {
+                explicit: false,
+                dependents: 3
+            })
+        );
+
+        // Core is still needed by the tool
+        assert_eq!(
+            plugins
+                .disable_and_release("app", &mut ())
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["app", "util"]
+        );
+        assert!(plugins.is_enabled("core"));
+        assert_eq!(
+            plugins.load_activation("util"),
+            Some(Activation {
+                explicit: false,
+                dependents: 1
+            })
+        );
+
+        let mut context = ();
+        let (unloaded, disabled) = plugins.unload_and_release("app", &mut context);
+        assert_eq!(
+            unloaded.into_iter().collect::<Vec<_>>(),
+            vec!["app", "util"]
+        );
+        assert_eq!(disabled.into_iter().count(), 0);
+
+        // Explicitly loaded dependencies are kept
+        plugins.load("core", &mut ()).unwrap();
+        let (unloaded, disabled) = plugins.unload_and_release("tool", &mut context);
+        assert_eq!(unloaded.into_iter().collect::<Vec<_>>(), vec!["tool"]);
+        assert_eq!(disabled.into_iter().collect::<Vec<_>>(), vec!["tool"]);
+        assert_eq!(
+            plugins.enable_activation("core"),
+            Some(Activation {
+                explicit: false,
+                dependents: 0
+            })
+        );
+        assert_eq!(
+            plugins.load_activation("core"),
+            Some(Activation {
+                explicit: true,
+                dependents: 0
+            })
+        );
+    }
+}
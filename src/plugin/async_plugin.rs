@@ -0,0 +1,1245 @@
+use super::status::Transition;
+use super::trace;
+use super::{
+    LifecyclePhase, LoadPluginError, Plugin, PluginConstructor, PluginInstance, PluginManifest,
+    PluginRegistry, PluginState, RegisterPluginError,
+};
+use crate::HookRegistry;
+use petgraph::Direction::{self, Incoming, Outgoing};
+use std::collections::HashMap;
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, Instant};
+
+/// A boxed future returned by the lifecycle methods of an [`AsyncPlugin`].
+pub type PluginFuture<'a, T = ()> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Function signature of constructor for an async plugin object.
+pub type FnAsyncPluginConstructor<Id, Context> = fn() -> Box<dyn AsyncPlugin<Id, Context>>;
+
+/// Plugins with an asynchronous lifecycle, for plugins that need to perform I/O such as connecting
+/// to databases or sockets while being loaded or enabled. Async plugins are registered with
+/// [`PluginRegistry::register_async`] and driven by the async lifecycle methods of the registry,
+/// such as [`PluginRegistry::load_async`], which run independent plugins concurrently.
+///
+/// Since async lifecycle methods of different plugins run concurrently, they receive a shared
+/// `context` and, when loading, a hook registry private to the plugin which is merged into the
+/// plugin registry's hooks once the plugin has loaded successfully. Merging checks the hooks like
+/// [`HookRegistry::register`], so the load of a plugin whose hooks conflict with those of a plugin
+/// loaded concurrently fails with [`LoadPluginError::HookRejected`].
+///
+/// An async plugin is also a [`Plugin`], whose synchronous methods are called instead when the
+/// plugin is driven by the synchronous lifecycle methods of the registry, such as
+/// [`PluginRegistry::load`].
+pub trait AsyncPlugin<Id = &'static str, Context = ()>: Plugin<Id, Context> {
+    /// Called when the host requests a plugin be loaded. The plugin should register any hooks
+    /// provided by the plugin and perform any other initialization of the plugin. Returning an
+    /// [`Err`] with an explanation fails loading the plugin.
+    fn load_async<'a>(
+        &'a mut self,
+        _hooks: &'a mut HookRegistry<Id>,
+        _context: &'a Context,
+    ) -> PluginFuture<'a, Result<(), String>> {
+        Box::pin(future::ready(Ok(())))
+    }
+
+    /// Called when the host unloads this plugin. Hooks registered by this plugin will
+    /// automatically be unregistered after unloading.
+    fn unload_async<'a>(&'a mut self, _context: &'a Context) -> PluginFuture<'a> {
+        Box::pin(future::ready(()))
+    }
+
+    /// Called when the plugin host enables this plugin's hooks. Returning an [`Err`] with an
+    /// explanation fails enabling the plugin.
+    fn enable_async<'a>(
+        &'a mut self,
+        _context: &'a Context,
+    ) -> PluginFuture<'a, Result<(), String>> {
+        Box::pin(future::ready(Ok(())))
+    }
+
+    /// Called when the plugin host disables this plugin's hooks.
+    fn disable_async<'a>(&'a mut self, _context: &'a Context) -> PluginFuture<'a> {
+        Box::pin(future::ready(()))
+    }
+}
+
+/// Polls a set of futures concurrently, completing with their outputs in the original order.
+struct JoinAll<'a, T> {
+    futures: Vec<Option<PluginFuture<'a, T>>>,
+    outputs: Vec<Option<T>>,
+}
+
+fn join_all<'a, T>(futures: impl IntoIterator<Item = PluginFuture<'a, T>>) -> JoinAll<'a, T> {
+    let futures = futures.into_iter().map(Some).collect::<Vec<_>>();
+    let outputs = futures.iter().map(|_| None).collect();
+    JoinAll { futures, outputs }
+}
+
+/// Measure the wall time until `future` completes, added to the time spent `before` it.
+fn timed<'a, T>(before: Duration, future: PluginFuture<'a, T>) -> PluginFuture<'a, (T, Duration)>
+where
+    T: 'a,
+{
+    Box::pin(async move {
+        let started = Instant::now();
+        let output = future.await;
+        (output, before + started.elapsed())
+    })
+}
+
+impl<T> Future for JoinAll<'_, T>
+where
+    T: Unpin,
+{
+    type Output = Vec<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut pending = false;
+        for (slot, output) in this.futures.iter_mut().zip(&mut this.outputs) {
+            if let Some(future) = slot {
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(value) => {
+                        *output = Some(value);
+                        *slot = None;
+                    }
+                    Poll::Pending => pending = true,
+                }
+            }
+        }
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(this.outputs.iter_mut().filter_map(Option::take).collect())
+        }
+    }
+}
+
+/// Tracks the plugins loaded and enabled by an async lifecycle method of the registry, so that
+/// they can be rolled back if the method fails or its future is dropped before completion.
+struct Rollback<'a, Manifest, Context>
+where
+    Manifest: PluginManifest,
+    Manifest::PluginId: 'static,
+    Context: 'static,
+{
+    registry: &'a mut PluginRegistry<Manifest, Context>,
+    context: &'a mut Context,
+    started: Instant,
+    loaded: Vec<Manifest::PluginId>,
+    enabled: Vec<Manifest::PluginId>,
+}
+
+impl<'a, Manifest, Context> Rollback<'a, Manifest, Context>
+where
+    Manifest: PluginManifest,
+    Manifest::PluginId: 'static,
+    Context: 'static,
+{
+    fn new(registry: &'a mut PluginRegistry<Manifest, Context>, context: &'a mut Context) -> Self {
+        Self {
+            registry,
+            context,
+            started: Instant::now(),
+            loaded: Vec::new(),
+            enabled: Vec::new(),
+        }
+    }
+
+    /// Load the planned plugins in waves of independent plugins.
+    async fn load(
+        &mut self,
+        order: Vec<Manifest::PluginId>,
+    ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
+        for wave in self.registry.waves(order, Outgoing) {
+            self.registry
+                .load_wave(wave, self.started, self.context, &mut self.loaded)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Enable the planned plugins in waves of independent plugins.
+    async fn enable(
+        &mut self,
+        order: Vec<Manifest::PluginId>,
+    ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
+        // Like the synchronous lifecycle, enabling is timed separately from loading
+        self.started = Instant::now();
+        for wave in self.registry.waves(order, Outgoing) {
+            self.registry
+                .enable_wave(wave, self.started, self.context, &mut self.enabled)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Disable and unload the tracked plugins with their async lifecycle methods, in reverse order.
+    /// Plugins are only forgotten once rolled back, so that dropping this future rolls back the
+    /// rest synchronously.
+    async fn undo(mut self) {
+        while let Some(&id) = self.enabled.last() {
+            self.registry
+                .disable_wave(vec![id], Instant::now(), self.context, &mut Vec::new())
+                .await;
+            self.enabled.pop();
+        }
+        while let Some(&id) = self.loaded.last() {
+            self.registry
+                .unload_wave(vec![id], Instant::now(), self.context, &mut Vec::new())
+                .await;
+            self.loaded.pop();
+        }
+    }
+
+    /// Keep the tracked plugins loaded and enabled.
+    fn complete(mut self) {
+        self.loaded.clear();
+        self.enabled.clear();
+    }
+}
+
+impl<Manifest, Context> Drop for Rollback<'_, Manifest, Context>
+where
+    Manifest: PluginManifest,
+    Manifest::PluginId: 'static,
+    Context: 'static,
+{
+    fn drop(&mut self) {
+        // Plugins of a wave dropped while loading were never loaded
+        for state in self.registry.plugins.values_mut() {
+            if state.transition == Some(Transition::Loading) {
+                state.transition = None;
+            }
+        }
+        while let Some(id) = self.enabled.pop() {
+            self.registry.disable_plugin(id, None, self.context);
+        }
+        while let Some(id) = self.loaded.pop() {
+            self.registry.unload_plugin(id, None, self.context);
+        }
+    }
+}
+
+impl<Manifest, Context> PluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+{
+    /// Register an async plugin if a plugin with the same id as specified in the `manifest` has
+    /// not already been registered and return its id. This behaves the same as
+    /// [`PluginRegistry::register`], except the optional constructor creates an [`AsyncPlugin`].
+    ///
+    /// # Errors
+    ///
+    /// If a plugin with the same id as specified in the manifest has already been registered, will
+    /// return [`RegisterPluginError::Duplicate`].
+    ///
+    /// If registering this plugin would result in a cycle of plugin dependencies, will return
+    /// [`RegisterPluginError::CyclicDependency`].
+    pub fn register_async(
+        &mut self,
+        manifest: Manifest,
+        ctor: Option<FnAsyncPluginConstructor<Manifest::PluginId, Context>>,
+    ) -> Result<Manifest::PluginId, RegisterPluginError<Manifest::PluginId>> {
+        self.register_state(manifest, ctor.map(PluginConstructor::Async))
+    }
+}
+
+impl<Manifest, Context> PluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+    Manifest::PluginId: 'static,
+    Context: 'static,
+{
+    /// Load the plugin registered with the given plugin id if it is not currently loaded, along
+    /// with all of its dependencies, the same as [`PluginRegistry::load`]. Plugins that do not
+    /// depend on each other are loaded concurrently, while a plugin is only loaded once all of its
+    /// dependencies have finished loading. Async plugins are loaded with
+    /// [`AsyncPlugin::load_async`] and all other plugins with [`Plugin::load`].
+    ///
+    /// If any plugin fails to load, all plugins loaded by this call are unloaded again in reverse
+    /// order before returning the error. If the returned future is dropped before completion, the
+    /// plugins still being loaded are discarded, while the plugins that finished loading are
+    /// unloaded again in reverse order with their synchronous [`Plugin::unload`] method.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LoadPluginError::NotFound`] if no plugin has been registered with the specified
+    /// id.
+    ///
+    /// If no constructor function was specified when a plugin was registered, returns
+    /// [`LoadPluginError::MissingConstructor`].
+    ///
+    /// Returns [`LoadPluginError::DependencyNotFound`] if any dependencies have not been
+    /// registered.
+    ///
+    /// If the plugin's manifest determines a dependency does not match using
+    /// [`PluginManifest::dependency_matches`], returns [`LoadPluginError::DependencyMismatch`].
+    ///
+    /// If a plugin fails in [`AsyncPlugin::load_async`], returns [`LoadPluginError::Failed`].
+    ///
+    /// If a hook registered in [`AsyncPlugin::load_async`] is rejected when merged into the
+    /// registry's hooks, returns [`LoadPluginError::HookRejected`].
+    pub async fn load_async(
+        &mut self,
+        id: Manifest::PluginId,
+        context: &mut Context,
+    ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
+        if self
+            .plugins
+            .get(&id)
+            .ok_or(LoadPluginError::NotFound(id))?
+            .plugin
+            .is_none()
+        {
+            let mut order = Vec::new();
+            self.plan_load(id, &mut order)?;
+
+            let mut rollback = Rollback::new(self, context);
+            if let Err(error) = rollback.load(order).await {
+                rollback.undo().await;
+                return Err(error);
+            }
+            rollback.complete();
+        }
+        self.plugins.get_mut(&id).unwrap().explicit_load = true;
+        Ok(())
+    }
+
+    /// Unload the plugin with the given plugin id, the same as [`PluginRegistry::unload`]. The
+    /// plugin and all plugins that depend on it are first disabled with
+    /// [`PluginRegistry::disable_async`], then unloaded with dependents unloaded before their
+    /// dependencies. Plugins that do not depend on each other are unloaded concurrently.
+    /// Returns both an iterator over all the plugin ids unloaded and an iterator over all the
+    /// plugin ids disabled.
+    ///
+    /// If the returned future is dropped before completion, the plugins still being unloaded
+    /// remain loaded.
+    pub async fn unload_async(
+        &mut self,
+        id: Manifest::PluginId,
+        context: &mut Context,
+    ) -> (
+        impl IntoIterator<Item = Manifest::PluginId>,
+        impl IntoIterator<Item = Manifest::PluginId>,
+    ) {
+        let mut unloaded = Vec::new();
+        let mut disabled = Vec::new();
+        if self.is_loaded(id) {
+            let started = Instant::now();
+            disabled.extend(self.disable_async(id, context).await);
+
+            let mut order = Vec::new();
+            self.plan_closure(id, Incoming, |s| s.plugin.is_some(), &mut order);
+            for wave in self.waves(order, Incoming) {
+                self.unload_wave(wave, started, context, &mut unloaded)
+                    .await;
+            }
+        }
+        (unloaded, disabled)
+    }
+
+    /// Enable the plugin with the given plugin id if it is not currently enabled, along with all
+    /// of its dependencies, the same as [`PluginRegistry::enable`]. If the plugin has not been
+    /// loaded yet, will load the plugin first like [`PluginRegistry::load_async`]. Plugins that do
+    /// not depend on each other are enabled concurrently, while a plugin is only enabled once all
+    /// of its dependencies have been enabled.
+    ///
+    /// If any plugin fails to load or enable, all plugins enabled by this call are disabled and all
+    /// plugins loaded by this call are unloaded again in reverse order before returning the error.
+    /// If the returned future is dropped before completion, the plugins still being enabled remain
+    /// disabled, while the plugins that finished enabling or loading are disabled and unloaded
+    /// again in reverse order with their synchronous [`Plugin::disable`] and [`Plugin::unload`]
+    /// methods.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`PluginRegistry::load_async`].
+    ///
+    /// If a plugin fails in [`AsyncPlugin::enable_async`], returns [`LoadPluginError::Failed`].
+    pub async fn enable_async(
+        &mut self,
+        id: Manifest::PluginId,
+        context: &mut Context,
+    ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
+        let state = self.plugins.get(&id).ok_or(LoadPluginError::NotFound(id))?;
+        if !state.enabled {
+            let mut load_order = Vec::new();
+            if state.plugin.is_none() {
+                self.plan_load(id, &mut load_order)?;
+            }
+            let mut enable_order = Vec::new();
+            self.plan_closure(id, Outgoing, |s| !s.enabled, &mut enable_order);
+            for &id in &enable_order {
+                self.plugins[&id].check_usable(id)?;
+            }
+
+            let mut rollback = Rollback::new(self, context);
+            let mut result = rollback.load(load_order).await;
+            if result.is_ok() {
+                result = rollback.enable(enable_order).await;
+            }
+            if let Err(error) = result {
+                rollback.undo().await;
+                return Err(error);
+            }
+            rollback.complete();
+        }
+        let state = self.plugins.get_mut(&id).unwrap();
+        state.explicit_enable = true;
+        state.explicit_load = true;
+        Ok(())
+    }
+
+    /// Disable the plugin with the given plugin id, the same as [`PluginRegistry::disable`]. All
+    /// plugins that list this plugin as a dependency are disabled before this plugin, while
+    /// plugins that do not depend on each other are disabled concurrently. Returns an iterator
+    /// over all the plugin ids disabled.
+    ///
+    /// If the returned future is dropped before completion, the plugins still being disabled
+    /// remain enabled.
+    pub async fn disable_async(
+        &mut self,
+        id: Manifest::PluginId,
+        context: &mut Context,
+    ) -> impl IntoIterator<Item = Manifest::PluginId> {
+        let mut disabled = Vec::new();
+        if self.is_enabled(id) {
+            let started = Instant::now();
+            let mut order = Vec::new();
+            self.plan_closure(id, Incoming, |s| s.enabled, &mut order);
+            for wave in self.waves(order, Incoming) {
+                self.disable_wave(wave, started, context, &mut disabled)
+                    .await;
+            }
+        }
+        disabled
+    }
+
+    fn sorted_neighbors(
+        &self,
+        id: Manifest::PluginId,
+        direction: Direction,
+    ) -> Vec<Manifest::PluginId> {
+        let mut neighbors = self
+            .dependency_graph
+            .edges_directed(id, direction)
+            .map(|(a, b, &i)| (if a == id { b } else { a }, i))
+            .collect::<Vec<_>>();
+        neighbors.sort_unstable_by_key(|(_, i)| *i);
+        neighbors.into_iter().map(|(n, _)| n).collect()
+    }
+
+    /// Collects the unloaded plugin and its unloaded dependencies, with dependencies first, while
+    /// validating they can be loaded.
+    fn plan_load(
+        &self,
+        id: Manifest::PluginId,
+        order: &mut Vec<Manifest::PluginId>,
+    ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
+        if order.contains(&id) {
+            return Ok(());
+        }
+        for dep in self.sorted_neighbors(id, Outgoing) {
+            let dep_state = self
+                .plugins
+                .get(&dep)
+                .ok_or(LoadPluginError::DependencyNotFound {
+                    plugin: id,
+                    dependency: dep,
+                })?;
+
+            if dep_state.plugin.is_none() {
+                self.plugins[&id]
+                    .manifest
+                    .dependency_matches(&dep_state.manifest)
+                    .map_err(|reason| LoadPluginError::DependencyMismatch {
+                        plugin: id,
+                        dependency: dep,
+                        reason,
+                    })?;
+
+                self.plan_load(dep, order)?;
+            }
+        }
+        self.plugins[&id].check_usable(id)?;
+        if self.plugins[&id].ctor.is_none() {
+            return Err(LoadPluginError::MissingConstructor(id));
+        }
+        order.push(id);
+        Ok(())
+    }
+
+    /// Collects the plugin and all plugins transitively reachable in `direction` that satisfy
+    /// `filter`, with the farthest plugins first.
+    fn plan_closure(
+        &self,
+        id: Manifest::PluginId,
+        direction: Direction,
+        filter: fn(&PluginState<Manifest, Context>) -> bool,
+        order: &mut Vec<Manifest::PluginId>,
+    ) {
+        if order.contains(&id) || !self.plugins.get(&id).is_some_and(filter) {
+            return;
+        }
+        for next in self.sorted_neighbors(id, direction).into_iter().rev() {
+            self.plan_closure(next, direction, filter, order);
+        }
+        order.push(id);
+    }
+
+    /// Groups the planned plugins into waves, where each plugin only has prerequisites in
+    /// `direction` within earlier waves.
+    fn waves(
+        &self,
+        order: Vec<Manifest::PluginId>,
+        direction: Direction,
+    ) -> Vec<Vec<Manifest::PluginId>> {
+        let mut levels = HashMap::with_capacity(order.len());
+        let mut waves: Vec<Vec<_>> = Vec::new();
+        for id in order {
+            let level = self
+                .dependency_graph
+                .neighbors_directed(id, direction)
+                .filter_map(|n| levels.get(&n).map(|l| l + 1))
+                .max()
+                .unwrap_or(0);
+            levels.insert(id, level);
+            if waves.len() <= level {
+                waves.resize_with(level + 1, Vec::new);
+            }
+            waves[level].push(id);
+        }
+        waves
+    }
+
+    async fn load_wave(
+        &mut self,
+        wave: Vec<Manifest::PluginId>,
+        started: Instant,
+        context: &mut Context,
+        loaded: &mut Vec<Manifest::PluginId>,
+    ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
+        let mut result = Ok(());
+        let mut pending = Vec::new();
+        for id in wave {
+            let state = self.plugins.get_mut(&id).unwrap();
+            let library = state.library.clone();
+            state.transition = Some(Transition::Loading);
+            let own = Instant::now();
+            match state.ctor.as_ref().unwrap().construct() {
+                PluginInstance::Async(plugin) => {
+                    let staging = self.hooks.staging(id, library);
+                    pending.push((id, plugin, staging, own.elapsed()));
+                }
+                instance => {
+                    let _span = trace::enter_plugin(&id, LifecyclePhase::Load);
+                    let plugin = state.plugin.insert(instance).as_plugin_mut();
+                    self.hooks
+                        .with_plugin(id, library, &mut state.resources, |hooks| {
+                            plugin.load(hooks, context);
+                        });
+                    let failure = plugin.take_failure();
+                    state.transition = None;
+                    if failure.is_some() {
+                        state.plugin = None;
+                        state.resources.clear();
+                    }
+                    self.record_timing(id, LifecyclePhase::Load, own.elapsed(), started);
+                    match failure {
+                        None => loaded.push(id),
+                        Some(reason) => {
+                            let error = LoadPluginError::Failed { plugin: id, reason };
+                            self.fail_plugin(id, error.clone(), context);
+                            if result.is_ok() {
+                                result = Err(error);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let shared = &*context;
+        let results = join_all(pending.iter_mut().map(|(id, plugin, hooks, own)| {
+            let load =
+                trace::instrument(id, LifecyclePhase::Load, plugin.load_async(hooks, shared));
+            timed(*own, load)
+        }))
+        .await;
+
+        for ((id, plugin, mut hooks, _), (outcome, own)) in pending.into_iter().zip(results) {
+            let state = self.plugins.get_mut(&id).unwrap();
+            state.transition = None;
+            let outcome = match outcome {
+                Ok(()) => {
+                    let resources = hooks.take_resources();
+                    // Plugins of the same wave staged their hooks separately, so the hooks are
+                    // checked against each other only now
+                    match self.hooks.append(hooks) {
+                        Ok(()) => {
+                            state.plugin = Some(PluginInstance::Async(plugin));
+                            state.resources = resources;
+                            Ok(())
+                        }
+                        Err(error) => Err(LoadPluginError::HookRejected { plugin: id, error }),
+                    }
+                }
+                Err(reason) => Err(LoadPluginError::Failed { plugin: id, reason }),
+            };
+            self.record_timing(id, LifecyclePhase::Load, own, started);
+            match outcome {
+                Ok(()) => loaded.push(id),
+                Err(error) => {
+                    self.fail_plugin(id, error.clone(), context);
+                    if result.is_ok() {
+                        result = Err(error);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    async fn unload_wave(
+        &mut self,
+        wave: Vec<Manifest::PluginId>,
+        started: Instant,
+        context: &mut Context,
+        unloaded: &mut Vec<Manifest::PluginId>,
+    ) {
+        let mut failures = Vec::new();
+        let mut timings = Vec::new();
+        for &id in &wave {
+            if let Some(PluginInstance::Sync(plugin)) =
+                &mut self.plugins.get_mut(&id).unwrap().plugin
+            {
+                let _span = trace::enter_plugin(&id, LifecyclePhase::Unload);
+                let own = Instant::now();
+                plugin.unload(context);
+                timings.push((id, own.elapsed()));
+                failures.extend(plugin.take_failure().map(|reason| (id, reason)));
+            }
+        }
+
+        let shared = &*context;
+        let (ids, futures): (Vec<_>, Vec<_>) = self
+            .plugins
+            .iter_mut()
+            .filter_map(|(&id, state)| match &mut state.plugin {
+                Some(PluginInstance::Async(plugin)) if wave.contains(&id) => {
+                    let unload =
+                        trace::instrument(&id, LifecyclePhase::Unload, plugin.unload_async(shared));
+                    Some((id, timed(Duration::ZERO, unload)))
+                }
+                _ => None,
+            })
+            .unzip();
+        let results = join_all(futures).await;
+        timings.extend(
+            ids.into_iter()
+                .zip(results.into_iter().map(|((), own)| own)),
+        );
+
+        for id in wave {
+            let state = self.plugins.get_mut(&id).unwrap();
+            if let Some(PluginInstance::Async(plugin)) = &mut state.plugin {
+                failures.extend(plugin.take_failure().map(|reason| (id, reason)));
+            }
+            state.plugin = None;
+            state.explicit_load = false;
+            self.hooks.remove_plugin_hooks(id);
+            self.hooks.withdraw_services(id);
+            self.plugins.get_mut(&id).unwrap().resources.clear();
+            unloaded.push(id);
+        }
+        for (id, own) in timings {
+            self.record_timing(id, LifecyclePhase::Unload, own, started);
+        }
+        for (id, reason) in failures {
+            self.fail_plugin(id, LoadPluginError::Failed { plugin: id, reason }, context);
+        }
+    }
+
+    async fn enable_wave(
+        &mut self,
+        wave: Vec<Manifest::PluginId>,
+        started: Instant,
+        context: &mut Context,
+        enabled: &mut Vec<Manifest::PluginId>,
+    ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
+        let mut result = Ok(());
+        for &id in &wave {
+            let state = self.plugins.get_mut(&id).unwrap();
+            if let Some(PluginInstance::Sync(plugin)) = &mut state.plugin {
+                let _span = trace::enter_plugin(&id, LifecyclePhase::Enable);
+                let own = Instant::now();
+                plugin.enable(context);
+                let failure = plugin.take_failure();
+                state.enabled = failure.is_none();
+                self.record_timing(id, LifecyclePhase::Enable, own.elapsed(), started);
+                match failure {
+                    None => {
+                        self.hooks.reset_breaker(id);
+                        enabled.push(id);
+                    }
+                    Some(reason) => {
+                        let error = LoadPluginError::Failed { plugin: id, reason };
+                        self.fail_plugin(id, error.clone(), context);
+                        if result.is_ok() {
+                            result = Err(error);
+                        }
+                    }
+                }
+            }
+        }
+
+        let shared = &*context;
+        let (ids, futures): (Vec<_>, Vec<_>) = self
+            .plugins
+            .iter_mut()
+            .filter_map(|(&id, state)| match &mut state.plugin {
+                Some(PluginInstance::Async(plugin)) if wave.contains(&id) => {
+                    let enable =
+                        trace::instrument(&id, LifecyclePhase::Enable, plugin.enable_async(shared));
+                    Some((id, timed(Duration::ZERO, enable)))
+                }
+                _ => None,
+            })
+            .unzip();
+        let results = join_all(futures).await;
+
+        for (id, (outcome, own)) in ids.into_iter().zip(results) {
+            self.record_timing(id, LifecyclePhase::Enable, own, started);
+            match outcome {
+                Ok(()) => {
+                    self.plugins.get_mut(&id).unwrap().enabled = true;
+                    self.hooks.reset_breaker(id);
+                    enabled.push(id);
+                }
+                Err(reason) => {
+                    let error = LoadPluginError::Failed { plugin: id, reason };
+                    self.fail_plugin(id, error.clone(), context);
+                    if result.is_ok() {
+                        result = Err(error);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    async fn disable_wave(
+        &mut self,
+        wave: Vec<Manifest::PluginId>,
+        started: Instant,
+        context: &mut Context,
+        disabled: &mut Vec<Manifest::PluginId>,
+    ) {
+        let mut failures = Vec::new();
+        let mut timings = Vec::new();
+        for &id in &wave {
+            let state = self.plugins.get_mut(&id).unwrap();
+            state.transition = Some(Transition::Disabling);
+            if let Some(PluginInstance::Sync(plugin)) = &mut state.plugin {
+                let _span = trace::enter_plugin(&id, LifecyclePhase::Disable);
+                let own = Instant::now();
+                plugin.disable(context);
+                timings.push((id, own.elapsed()));
+                failures.extend(plugin.take_failure().map(|reason| (id, reason)));
+            }
+        }
+
+        let shared = &*context;
+        let (ids, futures): (Vec<_>, Vec<_>) = self
+            .plugins
+            .iter_mut()
+            .filter_map(|(&id, state)| match &mut state.plugin {
+                Some(PluginInstance::Async(plugin)) if wave.contains(&id) => {
+                    let disable = trace::instrument(
+                        &id,
+                        LifecyclePhase::Disable,
+                        plugin.disable_async(shared),
+                    );
+                    Some((id, timed(Duration::ZERO, disable)))
+                }
+                _ => None,
+            })
+            .unzip();
+        let results = join_all(futures).await;
+        timings.extend(
+            ids.into_iter()
+                .zip(results.into_iter().map(|((), own)| own)),
+        );
+
+        for id in wave {
+            let state = self.plugins.get_mut(&id).unwrap();
+            if let Some(PluginInstance::Async(plugin)) = &mut state.plugin {
+                failures.extend(plugin.take_failure().map(|reason| (id, reason)));
+            }
+            state.enabled = false;
+            state.explicit_enable = false;
+            state.transition = None;
+            disabled.push(id);
+        }
+        for (id, own) in timings {
+            self.record_timing(id, LifecyclePhase::Disable, own, started);
+        }
+        for (id, reason) in failures {
+            self.fail_plugin(id, LoadPluginError::Failed { plugin: id, reason }, context);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AsyncPlugin, HookRegistry, LifecyclePhase, LoadPluginError, Plugin, PluginFuture,
+        PluginRegistry, PluginStatus, RegisterHookError, SimplePluginManifest, hook_slot,
+    };
+    use std::future::{self, Future};
+    use std::pin::pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread::{self, Thread};
+    use std::time::Duration;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct ThreadWaker(Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    /// Future that stays pending until `count` reaches `target`.
+    fn wait_for(count: &'static AtomicUsize, target: usize) -> impl Future<Output = ()> + Send {
+        future::poll_fn(move |cx| {
+            if count.load(Ordering::SeqCst) >= target {
+                Poll::Ready(())
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+    }
+
+    trait Named: Send + Sync {
+        fn name(&self) -> &'static str;
+    }
+
+    hook_slot!(NamedSlot: dyn Named);
+
+    struct Name(&'static str);
+
+    impl Named for Name {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+    }
+
+    static RENDEZVOUS: AtomicUsize = AtomicUsize::new(0);
+
+    struct Rendezvous(&'static str);
+
+    impl Plugin for Rendezvous {}
+
+    impl AsyncPlugin for Rendezvous {
+        fn load_async<'a>(
+            &'a mut self,
+            hooks: &'a mut HookRegistry,
+            _context: &'a (),
+        ) -> PluginFuture<'a, Result<(), String>> {
+            Box::pin(async move {
+                // Only completes if both plugins are loading at the same time
+                RENDEZVOUS.fetch_add(1, Ordering::SeqCst);
+                wait_for(&RENDEZVOUS, 2).await;
+                hooks
+                    .register::<NamedSlot>(Box::new(Name(self.0)), self.0, None)
+                    .ok();
+                Ok(())
+            })
+        }
+    }
+
+    #[test]
+    fn independent_plugins_load_concurrently() {
+        let mut plugins = PluginRegistry::new();
+        plugins
+            .register_async(
+                SimplePluginManifest::new("a", ""),
+                Some(|| Box::new(Rendezvous("a"))),
+            )
+            .unwrap();
+        plugins
+            .register_async(
+                SimplePluginManifest::new("b", ""),
+                Some(|| Box::new(Rendezvous("b"))),
+            )
+            .unwrap();
+        plugins
+            .register(
+                SimplePluginManifest::with_dependencies("c", "", vec!["a", "b"]),
+                Some(|| Box::new(Rendezvous("c"))),
+            )
+            .unwrap();
+
+        block_on(plugins.enable_async("c", &mut ())).unwrap();
+
+        assert_eq!(plugins.enabled_plugin_count(), 3);
+        assert_eq!(
+            plugins.hooks().get_first::<NamedSlot>("a").unwrap().name(),
+            "a"
+        );
+        assert_eq!(
+            plugins.hooks().get_first::<NamedSlot>("b").unwrap().name(),
+            "b"
+        );
+        // Sync lifecycle methods were used for `c`, which registered no hooks
+        assert!(!plugins.hooks().exists::<NamedSlot>("c"));
+
+        let mut context = ();
+        let (unloaded, disabled) = block_on(plugins.unload_async("a", &mut context));
+        assert_eq!(unloaded.into_iter().collect::<Vec<_>>(), vec!["c", "a"]);
+        assert_eq!(disabled.into_iter().collect::<Vec<_>>(), vec!["c", "a"]);
+        assert!(plugins.is_enabled("b"));
+        assert!(!plugins.hooks().exists::<NamedSlot>("a"));
+    }
+
+    hook_slot!(SingleNamedSlot: dyn Named {
+        name: "single named",
+        cardinality: AtMostOne,
+    });
+
+    struct Provider(&'static str);
+
+    impl Plugin for Provider {}
+
+    impl AsyncPlugin for Provider {
+        fn load_async<'a>(
+            &'a mut self,
+            hooks: &'a mut HookRegistry,
+            _context: &'a (),
+        ) -> PluginFuture<'a, Result<(), String>> {
+            hooks
+                .register::<SingleNamedSlot>(Box::new(Name(self.0)), self.0, None)
+                .ok();
+            Box::pin(future::ready(Ok(())))
+        }
+    }
+
+    #[test]
+    fn concurrent_loads_enforce_cardinality() {
+        let mut plugins = PluginRegistry::new();
+        plugins
+            .register_async(
+                SimplePluginManifest::new("a", ""),
+                Some(|| Box::new(Provider("a"))),
+            )
+            .unwrap();
+        plugins
+            .register_async(
+                SimplePluginManifest::new("b", ""),
+                Some(|| Box::new(Provider("b"))),
+            )
+            .unwrap();
+        plugins
+            .register(
+                SimplePluginManifest::with_dependencies("c", "", vec!["a", "b"]),
+                Some(|| Box::new(Base)),
+            )
+            .unwrap();
+
+        // Both providers staged their hook successfully, so the later one is rejected when merged
+        let error = block_on(plugins.load_async("c", &mut ())).unwrap_err();
+        assert_eq!(
+            error,
+            LoadPluginError::HookRejected {
+                plugin: "b",
+                error: RegisterHookError::CardinalityExceeded {
+                    slot: "single named",
+                    plugin: "b",
+                    existing: "a",
+                },
+            }
+        );
+        assert_eq!(plugins.status("b"), Some(PluginStatus::Failed { error }));
+        assert_eq!(plugins.loaded_plugin_count(), 0);
+        assert!(!plugins.hooks().exists::<SingleNamedSlot>("a"));
+    }
+
+    static EVENTS: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+
+    struct Recorder<const FAIL: bool>(&'static str);
+
+    impl<const FAIL: bool> Plugin for Recorder<FAIL> {}
+
+    impl<const FAIL: bool> AsyncPlugin for Recorder<FAIL> {
+        fn load_async<'a>(
+            &'a mut self,
+            _hooks: &'a mut HookRegistry,
+            _context: &'a (),
+        ) -> PluginFuture<'a, Result<(), String>> {
+            EVENTS.lock().unwrap().push(self.0);
+            Box::pin(future::ready(if FAIL {
+                Err("unreachable database".to_owned())
+            } else {
+                Ok(())
+            }))
+        }
+
+        fn unload_async<'a>(&'a mut self, _context: &'a ()) -> PluginFuture<'a> {
+            EVENTS.lock().unwrap().push("unload");
+            Box::pin(future::ready(()))
+        }
+    }
+
+    #[test]
+    fn failed_load_rolls_back() {
+        let mut plugins = PluginRegistry::new();
+        plugins
+            .register_async(
+                SimplePluginManifest::new("base", ""),
+                Some(|| Box::new(Recorder::<false>("base"))),
+            )
+            .unwrap();
+        plugins
+            .register_async(
+                SimplePluginManifest::with_dependencies("broken", "", vec!["base"]),
+                Some(|| Box::new(Recorder::<true>("broken"))),
+            )
+            .unwrap();
+
+        assert_eq!(
+            block_on(plugins.load_async("broken", &mut ())),
+            Err(LoadPluginError::Failed {
+                plugin: "broken",
+                reason: "unreachable database".to_owned()
+            })
+        );
+        assert_eq!(*EVENTS.lock().unwrap(), vec!["base", "broken", "unload"]);
+        assert_eq!(plugins.loaded_plugin_count(), 0);
+    }
+
+    struct Pending;
+
+    impl Plugin for Pending {}
+
+    impl AsyncPlugin for Pending {
+        fn load_async<'a>(
+            &'a mut self,
+            hooks: &'a mut HookRegistry,
+            _context: &'a (),
+        ) -> PluginFuture<'a, Result<(), String>> {
+            hooks
+                .register::<NamedSlot>(Box::new(Name("pending")), "pending", None)
+                .ok();
+            Box::pin(future::pending())
+        }
+    }
+
+    struct Base;
+
+    impl Plugin for Base {
+        fn load(&mut self, hooks: &mut HookRegistry, _context: &mut ()) {
+            hooks
+                .register::<NamedSlot>(Box::new(Name("base")), "base", None)
+                .ok();
+        }
+    }
+
+    #[test]
+    fn cancelled_load_rolls_back() {
+        let mut plugins = PluginRegistry::new();
+        plugins
+            .register(
+                SimplePluginManifest::new("base", ""),
+                Some(|| Box::new(Base)),
+            )
+            .unwrap();
+        plugins
+            .register_async(
+                SimplePluginManifest::with_dependencies("pending", "", vec!["base"]),
+                Some(|| Box::new(Pending)),
+            )
+            .unwrap();
+
+        let mut context = ();
+        {
+            let waker = Waker::noop();
+            let mut cx = Context::from_waker(waker);
+            let mut future = pin!(plugins.enable_async("pending", &mut context));
+            assert!(future.as_mut().poll(&mut cx).is_pending());
+        }
+
+        // The dependency loaded in an earlier wave is unloaded again
+        assert_eq!(plugins.loaded_plugin_count(), 0);
+        assert_eq!(plugins.status("pending"), Some(PluginStatus::Registered));
+        assert!(!plugins.hooks().exists::<NamedSlot>("base"));
+        assert!(!plugins.hooks().exists::<NamedSlot>("pending"));
+    }
+
+    struct Unreachable;
+
+    impl Plugin for Unreachable {}
+
+    impl AsyncPlugin for Unreachable {
+        fn enable_async<'a>(
+            &'a mut self,
+            _context: &'a (),
+        ) -> PluginFuture<'a, Result<(), String>> {
+            Box::pin(future::ready(Err("unreachable server".to_owned())))
+        }
+    }
+
+    #[test]
+    fn failed_enable_rolls_back_loads() {
+        let mut plugins = PluginRegistry::new();
+        plugins
+            .register(
+                SimplePluginManifest::new("base", ""),
+                Some(|| Box::new(Base)),
+            )
+            .unwrap();
+        plugins
+            .register_async(
+                SimplePluginManifest::with_dependencies("broken", "", vec!["base"]),
+                Some(|| Box::new(Unreachable)),
+            )
+            .unwrap();
+
+        assert!(matches!(
+            block_on(plugins.enable_async("broken", &mut ())),
+            Err(LoadPluginError::Failed {
+                plugin: "broken",
+                ..
+            })
+        ));
+        assert_eq!(plugins.enabled_plugin_count(), 0);
+        assert_eq!(plugins.loaded_plugin_count(), 0);
+        assert!(!plugins.hooks().exists::<NamedSlot>("base"));
+    }
+
+    struct Flaky;
+
+    impl Plugin for Flaky {}
+
+    impl AsyncPlugin for Flaky {
+        fn load_async<'a>(
+            &'a mut self,
+            hooks: &'a mut HookRegistry,
+            _context: &'a (),
+        ) -> PluginFuture<'a, Result<(), String>> {
+            hooks
+                .register::<NamedSlot>(Box::new(Name("flaky")), "flaky", None)
+                .ok();
+            Box::pin(future::ready(Ok(())))
+        }
+
+        fn enable_async<'a>(
+            &'a mut self,
+            _context: &'a (),
+        ) -> PluginFuture<'a, Result<(), String>> {
+            Box::pin(future::ready(Err("connection refused".to_owned())))
+        }
+    }
+
+    #[test]
+    fn failed_enable_removes_hooks() {
+        let mut plugins = PluginRegistry::new();
+        plugins
+            .register_async(
+                SimplePluginManifest::new("flaky", ""),
+                Some(|| Box::new(Flaky)),
+            )
+            .unwrap();
+        block_on(plugins.load_async("flaky", &mut ())).unwrap();
+        assert!(plugins.hooks().exists::<NamedSlot>("flaky"));
+
+        // Like a synchronous plugin failing to enable, the plugin stays loaded without its hooks
+        let error = block_on(plugins.enable_async("flaky", &mut ())).unwrap_err();
+        assert_eq!(
+            error,
+            LoadPluginError::Failed {
+                plugin: "flaky",
+                reason: "connection refused".to_owned(),
+            }
+        );
+        assert!(plugins.is_loaded("flaky"));
+        assert_eq!(
+            plugins.status("flaky"),
+            Some(PluginStatus::Failed { error })
+        );
+        assert!(!plugins.hooks().exists::<NamedSlot>("flaky"));
+    }
+
+    struct Slow;
+
+    impl Plugin for Slow {}
+
+    impl AsyncPlugin for Slow {
+        fn enable_async<'a>(
+            &'a mut self,
+            _context: &'a (),
+        ) -> PluginFuture<'a, Result<(), String>> {
+            Box::pin(async {
+                thread::sleep(Duration::from_millis(20));
+                Ok(())
+            })
+        }
+    }
+
+    #[test]
+    fn async_lifecycle_metrics() {
+        let mut plugins = PluginRegistry::new();
+        plugins
+            .register_async(
+                SimplePluginManifest::new("slow", ""),
+                Some(|| Box::new(Slow)),
+            )
+            .unwrap();
+        plugins
+            .register(
+                SimplePluginManifest::with_dependencies("base", "", vec!["slow"]),
+                Some(|| Box::new(Base)),
+            )
+            .unwrap();
+
+        block_on(plugins.enable_async("base", &mut ())).unwrap();
+        let _ = block_on(plugins.unload_async("slow", &mut ()));
+        for id in ["slow", "base"] {
+            let metrics = plugins.metrics().plugin(id).unwrap();
+            for phase in [
+                LifecyclePhase::Load,
+                LifecyclePhase::Enable,
+                LifecyclePhase::Disable,
+                LifecyclePhase::Unload,
+            ] {
+                assert_eq!(metrics.phase(phase).calls, 1, "{id} {phase}");
+            }
+        }
+        let slow = plugins.metrics().plugin("slow").unwrap();
+        let base = plugins.metrics().plugin("base").unwrap();
+        assert!(slow.enable.own >= Duration::from_millis(20));
+        assert!(base.enable.own < Duration::from_millis(20));
+        assert!(
+            base.enable.total >= Duration::from_millis(20),
+            "includes dependencies"
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use super::isolation::isolate;
+use super::{LifecyclePhase, LoadPluginError, PluginManifest, PluginPanic, PluginRegistry};
+use std::any::{Any, type_name};
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use std::time::Instant;
+use thiserror::Error;
+
+/// A configuration value supplied by the plugin host for a single plugin, set with
+/// [`PluginRegistry::set_config`]. The value can be of any type agreed on between the host and the
+/// plugin, such as a typed configuration struct, or a serialized document such as a
+/// `toml::Table` or `serde_json::Value` for the plugin to deserialize.
+///
+/// Plugins read their configuration during [`Plugin::load`][super::Plugin::load] with
+/// [`HookRegistry::config`][crate::HookRegistry::config], and receive later changes through
+/// [`Plugin::reconfigure`][super::Plugin::reconfigure].
+#[derive(Clone)]
+pub struct PluginConfig {
+    value: Arc<dyn Any + Send + Sync>,
+    type_name: &'static str,
+}
+
+impl PluginConfig {
+    /// Create a configuration holding `value`.
+    #[must_use]
+    pub fn new<T>(value: T) -> Self
+    where
+        T: Any + Send + Sync,
+    {
+        Self {
+            value: Arc::new(value),
+            type_name: type_name::<T>(),
+        }
+    }
+
+    /// Get the configuration value if it is of type `T`.
+    #[must_use]
+    pub fn get<T>(&self) -> Option<&T>
+    where
+        T: Any,
+    {
+        self.value.downcast_ref()
+    }
+
+    /// Determine whether the configuration value is of type `T`.
+    #[must_use]
+    pub fn is<T>(&self) -> bool
+    where
+        T: Any,
+    {
+        self.value.is::<T>()
+    }
+
+    /// Get the type name of the configuration value.
+    #[must_use]
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+}
+
+impl Debug for PluginConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginConfig")
+            .field("type_name", &self.type_name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An error occurred while configuring a plugin. It is generic over the type of the plugin id used
+/// by the plugin system; see [`PluginRegistry`] for more details.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Error)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConfigurePluginError<Id> {
+    /// No plugin with the given plugin id is currently registered.
+    #[error("plugin `{0}` not found")]
+    NotFound(Id),
+    /// The plugin's manifest rejected the configuration in
+    /// [`PluginManifest::validate_config`].
+    #[error("invalid configuration for plugin `{plugin}`: {reason}")]
+    Invalid {
+        /// Plugin id of the plugin being configured.
+        plugin: Id,
+        /// Explanation of why the configuration was rejected.
+        reason: String,
+    },
+    /// The running plugin failed to apply the configuration in
+    /// [`Plugin::reconfigure`][super::Plugin::reconfigure].
+    #[error("plugin `{plugin}` failed to apply configuration: {reason}")]
+    Rejected {
+        /// Plugin id of the plugin being configured.
+        plugin: Id,
+        /// Explanation of why the configuration was not applied.
+        reason: String,
+    },
+    /// The running plugin panicked while applying the configuration with panic isolation enabled,
+    /// and is now failed; see [`PluginRegistry::set_panic_isolation`].
+    #[error(transparent)]
+    Panicked(#[from] PluginPanic<Id>),
+}
+
+impl<Manifest, Context> PluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+    Manifest::PluginId: 'static,
+    Context: 'static,
+{
+    /// Get the configuration of the plugin with the given plugin id, if one was set.
+    #[must_use]
+    pub fn config(&self, id: Manifest::PluginId) -> Option<&PluginConfig> {
+        self.hooks.config(id)
+    }
+
+    /// Set the configuration of the plugin with the given plugin id after validating it with
+    /// [`PluginManifest::validate_config`]. The plugin receives the configuration the next time it
+    /// is loaded; use [`PluginRegistry::reconfigure`] to also apply it to a loaded plugin.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigurePluginError::NotFound`] if no plugin has been registered with the
+    /// specified id.
+    ///
+    /// Returns [`ConfigurePluginError::Invalid`] if the plugin's manifest rejects the
+    /// configuration.
+    pub fn set_config(
+        &mut self,
+        id: Manifest::PluginId,
+        config: PluginConfig,
+    ) -> Result<(), ConfigurePluginError<Manifest::PluginId>> {
+        self.validate_config(id, &config)?;
+        self.hooks.set_config(id, config);
+        Ok(())
+    }
+
+    /// Apply a new configuration to the plugin with the given plugin id without unloading it. The
+    /// configuration is validated with [`PluginManifest::validate_config`] and, if the plugin is
+    /// loaded, passed to its [`Plugin::reconfigure`][super::Plugin::reconfigure] method along with
+    /// `context`. The plugin keeps its previous configuration if either rejects the new one. If the
+    /// plugin is not loaded, behaves like [`PluginRegistry::set_config`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigurePluginError::NotFound`] if no plugin has been registered with the
+    /// specified id.
+    ///
+    /// Returns [`ConfigurePluginError::Invalid`] if the plugin's manifest rejects the
+    /// configuration.
+    ///
+    /// Returns [`ConfigurePluginError::Rejected`] if the loaded plugin fails to apply the
+    /// configuration, or [`ConfigurePluginError::Panicked`] if it panics while doing so with panic
+    /// isolation enabled.
+    pub fn reconfigure(
+        &mut self,
+        id: Manifest::PluginId,
+        config: PluginConfig,
+        context: &mut Context,
+    ) -> Result<(), ConfigurePluginError<Manifest::PluginId>> {
+        self.validate_config(id, &config)?;
+        if let Some(plugin) = &mut self.plugins.get_mut(&id).unwrap().plugin {
+            let plugin = plugin.as_plugin_mut();
+            let started = Instant::now();
+            let result = isolate(self.isolate_panics, id, LifecyclePhase::Reconfigure, || {
+                plugin.reconfigure(&config, context)
+            });
+            self.record_timing(id, LifecyclePhase::Reconfigure, started.elapsed(), started);
+            match result {
+                Ok(result) => result
+                    .map_err(|reason| ConfigurePluginError::Rejected { plugin: id, reason })?,
+                Err(panic) => {
+                    self.fail_plugin(id, LoadPluginError::Panicked(panic.clone()), context);
+                    return Err(ConfigurePluginError::Panicked(panic));
+                }
+            }
+        }
+        self.hooks.set_config(id, config);
+        Ok(())
+    }
+
+    fn validate_config(
+        &self,
+        id: Manifest::PluginId,
+        config: &PluginConfig,
+    ) -> Result<(), ConfigurePluginError<Manifest::PluginId>> {
+        self.plugins
+            .get(&id)
+            .ok_or(ConfigurePluginError::NotFound(id))?
+            .manifest
+            .validate_config(config)
+            .map_err(|reason| ConfigurePluginError::Invalid { plugin: id, reason })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ConfigurePluginError, HookRegistry, Plugin, PluginConfig, PluginManifest, PluginRegistry,
+        SimplePluginManifest,
+    };
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct CacheConfig {
+        capacity: usize,
+    }
+
+    struct CacheManifest(SimplePluginManifest);
+
+    impl PluginManifest for CacheManifest {
+        type PluginId = &'static str;
+
+        fn id(&self) -> Self::PluginId {
+            self.0.id()
+        }
+
+        fn validate_config(&self, config: &PluginConfig) -> Result<(), String> {
+            match config.get::<CacheConfig>() {
+                Some(config) if config.capacity > 0 => Ok(()),
+                Some(_) => Err("capacity must be positive".to_owned()),
+                None => Err(format!(
+                    "expected cache config, found {}",
+                    config.type_name()
+                )),
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct Cache {
+        capacity: usize,
+    }
+
+    impl Plugin for Cache {
+        fn load(&mut self, hooks: &mut HookRegistry, _context: &mut ()) {
+            self.capacity = hooks
+                .config("cache")
+                .and_then(PluginConfig::get::<CacheConfig>)
+                .map_or(16, |config| config.capacity);
+        }
+
+        fn reconfigure(&mut self, config: &PluginConfig, _context: &mut ()) -> Result<(), String> {
+            let config = config.get::<CacheConfig>().unwrap();
+            if config.capacity < self.capacity {
+                return Err("cannot shrink a running cache".to_owned());
+            }
+            self.capacity = config.capacity;
+            Ok(())
+        }
+    }
+
+    fn capacity(plugins: &PluginRegistry<CacheManifest>) -> usize {
+        plugins
+            .get_loaded_plugin("cache")
+            .unwrap()
+            .downcast_ref::<Cache>()
+            .unwrap()
+            .capacity
+    }
+
+    #[test]
+    fn configure_plugin() {
+        let mut plugins = PluginRegistry::new();
+        plugins
+            .register(
+                CacheManifest(SimplePluginManifest::new("cache", "")),
+                Some(|| Box::new(Cache::default())),
+            )
+            .unwrap();
+
+        assert_eq!(
+            plugins.set_config("cache", PluginConfig::new(64_usize)),
+            Err(ConfigurePluginError::Invalid {
+                plugin: "cache",
+                reason: "expected cache config, found usize".to_owned()
+            })
+        );
+        plugins
+            .set_config("cache", PluginConfig::new(CacheConfig { capacity: 64 }))
+            .unwrap();
+        plugins.load("cache", &mut ()).unwrap();
+        assert_eq!(capacity(&plugins), 64);
+
+        plugins
+            .reconfigure(
+                "cache",
+                PluginConfig::new(CacheConfig { capacity: 128 }),
+                &mut (),
+            )
+            .unwrap();
+        assert_eq!(capacity(&plugins), 128);
+        assert!(matches!(
+            plugins.reconfigure(
+                "cache",
+                PluginConfig::new(CacheConfig { capacity: 0 }),
+                &mut ()
+            ),
+            Err(ConfigurePluginError::Invalid { .. })
+        ));
+        assert!(matches!(
+            plugins.reconfigure(
+                "cache",
+                PluginConfig::new(CacheConfig { capacity: 32 }),
+                &mut ()
+            ),
+            Err(ConfigurePluginError::Rejected { .. })
+        ));
+        assert_eq!(
+            plugins.config("cache").unwrap().get(),
+            Some(&CacheConfig { capacity: 128 })
+        );
+    }
+}
@@ -0,0 +1,555 @@
+use crate::hook::{LibraryHandle, fnv1a};
+use crate::{PluginManifest, PluginRegistry, SimplePluginManifest};
+use libloading::Library;
+use std::any::type_name;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// The version of the layout of [`PluginLibraryDeclaration`]. A plugin library is only loaded by a
+/// plugin host using the same version.
+pub const PLUGIN_LIBRARY_ABI_VERSION: u32 = 1;
+
+/// Hash identifying the biner version, compiler and enabled features a plugin library or host was
+/// built with.
+const BUILD_HASH: u64 = fnv1a(
+    concat!(
+        env!("CARGO_PKG_VERSION"),
+        "\n",
+        env!("BINER_RUSTC_VERSION"),
+        "\n",
+        env!("BINER_FEATURES")
+    )
+    .as_bytes(),
+);
+
+/// Name of the symbol exported by [`export_plugin_library`](crate::export_plugin_library).
+const DECLARATION_SYMBOL: &[u8] = b"BINER_PLUGIN_LIBRARY\0";
+
+/// An error occurred while registering the plugins of a dynamic library with
+/// [`PluginRegistry::register_library`].
+#[derive(Debug, Error)]
+pub enum LoadLibraryError {
+    /// The dynamic library could not be opened.
+    #[error("failed to open plugin library `{}`", .path.display())]
+    Open {
+        /// Path of the dynamic library.
+        path: PathBuf,
+        /// The error reported while opening the library.
+        source: libloading::Error,
+    },
+    /// The dynamic library does not export a plugin declaration with
+    /// [`export_plugin_library`](crate::export_plugin_library).
+    #[error("`{}` is not a plugin library", .path.display())]
+    NotAPluginLibrary {
+        /// Path of the dynamic library.
+        path: PathBuf,
+        /// The error reported while looking up the plugin declaration.
+        source: libloading::Error,
+    },
+    /// The plugin library was built with a different [`PLUGIN_LIBRARY_ABI_VERSION`] than the host.
+    #[error(
+        "plugin library `{}` uses plugin library ABI version {found}, but the host uses version {expected}",
+        .path.display()
+    )]
+    AbiVersionMismatch {
+        /// Path of the dynamic library.
+        path: PathBuf,
+        /// ABI version of the plugin host.
+        expected: u32,
+        /// ABI version of the plugin library.
+        found: u32,
+    },
+    /// The plugin library was built with a different version of biner or the compiler, or with
+    /// different biner features, than the host, so the layouts of their types may differ.
+    #[error(
+        "plugin library `{}` was built with a different biner version, compiler or features than the host",
+        .path.display()
+    )]
+    BuildMismatch {
+        /// Path of the dynamic library.
+        path: PathBuf,
+    },
+    /// The plugin library registers its plugins into a registry with different manifest or context
+    /// types than the host's registry.
+    #[error(
+        "plugin library `{}` registers plugins into `{found}`, but the host uses `{expected}`",
+        .path.display()
+    )]
+    RegistryMismatch {
+        /// Path of the dynamic library.
+        path: PathBuf,
+        /// Type name of the host's plugin registry.
+        expected: &'static str,
+        /// Type name of the plugin registry the library registers into.
+        found: String,
+    },
+}
+
+/// The entry point of a plugin library, exported by
+/// [`export_plugin_library`](crate::export_plugin_library) and read by
+/// [`PluginRegistry::register_library`].
+///
+/// The ABI version is always the first field, so that it can be checked before reading the rest
+/// of a declaration from a library built with a different layout.
+#[repr(C)]
+pub struct PluginLibraryDeclaration<Manifest = SimplePluginManifest, Context = ()>
+where
+    Manifest: PluginManifest,
+{
+    abi_version: u32,
+    build_hash: u64,
+    registry_type: fn() -> &'static str,
+    register: fn(&mut PluginRegistry<Manifest, Context>),
+}
+
+impl<Manifest, Context> PluginLibraryDeclaration<Manifest, Context>
+where
+    Manifest: PluginManifest,
+{
+    /// Declare a plugin library registering its plugins with `register`. Use
+    /// [`export_plugin_library`](crate::export_plugin_library) to export the declaration.
+    #[must_use]
+    pub const fn new(register: fn(&mut PluginRegistry<Manifest, Context>)) -> Self {
+        Self {
+            abi_version: PLUGIN_LIBRARY_ABI_VERSION,
+            build_hash: BUILD_HASH,
+            registry_type: type_name::<PluginRegistry<Manifest, Context>>,
+            register,
+        }
+    }
+}
+
+/// Exports the entry point of a plugin library built as a `cdylib`, which the plugin host loads at
+/// runtime with [`PluginRegistry::register_library`]. The provided function registers the plugins
+/// of the library, just like a static plugin initializer registered with
+/// [`register_static_plugin`][crate::register_static_plugin].
+///
+/// If the plugin host uses custom plugin manifests or a plugin context, declare the types of the
+/// host's registry before the function.
+///
+/// # Examples
+///
+/// ```standalone_crate
+/// use biner::{export_plugin_library, Plugin, SimplePluginManifest};
+///
+/// struct MyPlugin;
+///
+/// impl Plugin for MyPlugin {
+///     // ...
+/// }
+///
+/// export_plugin_library!(|registry| {
+///     registry
+///         .register(
+///             SimplePluginManifest::new("my_plugin", "My plugin example"),
+///             Some(|| Box::new(MyPlugin)),
+///         )
+///         .unwrap();
+/// });
+///
+/// # fn main() {} // Just needs to compile
+/// ```
+///
+/// With a custom plugin id and context:
+///
+/// ```standalone_crate
+/// use biner::{export_plugin_library, SimplePluginManifest};
+///
+/// export_plugin_library!(<SimplePluginManifest<i32>, String> |registry| {
+///     // ...
+/// });
+///
+/// # fn main() {} // Just needs to compile
+/// ```
+#[macro_export]
+macro_rules! export_plugin_library {
+    (<$($targ:ty),+> $register:expr) => {
+        #[doc(hidden)]
+        #[unsafe(no_mangle)]
+        pub static BINER_PLUGIN_LIBRARY: $crate::PluginLibraryDeclaration<$($targ),+> =
+            $crate::PluginLibraryDeclaration::new($register);
+    };
+    ($register:expr) => {
+        $crate::export_plugin_library!(<$crate::SimplePluginManifest, ()> $register);
+    };
+}
+
+impl<Manifest, Context> PluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+{
+    /// Open the dynamic library at `path` and register all the plugins it exports with
+    /// [`export_plugin_library`](crate::export_plugin_library), returning the sorted ids of the
+    /// newly registered plugins. The plugins are registered but not loaded.
+    ///
+    /// Before registering any plugin, checks that the library was built with the same
+    /// [`PLUGIN_LIBRARY_ABI_VERSION`], biner version, compiler and biner features as the host, and
+    /// that it registers into a registry with the same manifest and context types.
+    ///
+    /// The library stays mapped as long as the registry, or any hook registered by the library's
+    /// plugins, is alive. This includes hooks kept alive by a [`HookSnapshot`][crate::HookSnapshot].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LoadLibraryError::Open`] if the library could not be opened and
+    /// [`LoadLibraryError::NotAPluginLibrary`] if it does not export a plugin declaration.
+    ///
+    /// Returns [`LoadLibraryError::AbiVersionMismatch`], [`LoadLibraryError::BuildMismatch`] or
+    /// [`LoadLibraryError::RegistryMismatch`] if the library is not compatible with the host.
+    ///
+    /// # Safety
+    ///
+    /// Opening the library runs its initialization code, see [`Library::new`]. The library must
+    /// have been built as a `cdylib` exporting its plugins with
+    /// [`export_plugin_library`](crate::export_plugin_library), and both the library and the host
+    /// must use the same global allocator.
+    ///
+    /// Anything else created by the library's code must not be used once the registry and all the
+    /// hooks registered by the library's plugins have been dropped, as the library may be unmapped
+    /// by then. This includes hooks returned by the `_shared` accessors of
+    /// [`HookRegistry`][crate::HookRegistry] and plugin ids or other data borrowed from the library.
+    pub unsafe fn register_library(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<Vec<Manifest::PluginId>, LoadLibraryError> {
+        let path = path.as_ref();
+        // SAFETY: Running the library's initialization code is upheld by the caller
+        let library = unsafe { Library::new(path) }.map_err(|source| LoadLibraryError::Open {
+            path: path.to_owned(),
+            source,
+        })?;
+        // SAFETY: Only the address of the symbol is read until its layout has been checked
+        let declaration = unsafe {
+            library.get::<*const PluginLibraryDeclaration<Manifest, Context>>(DECLARATION_SYMBOL)
+        }
+        .map_err(|source| LoadLibraryError::NotAPluginLibrary {
+            path: path.to_owned(),
+            source,
+        })?;
+        let declaration = *declaration;
+
+        // SAFETY: The ABI version is the first field of every declaration layout
+        let abi_version = unsafe { declaration.cast::<u32>().read() };
+        if abi_version != PLUGIN_LIBRARY_ABI_VERSION {
+            return Err(LoadLibraryError::AbiVersionMismatch {
+                path: path.to_owned(),
+                expected: PLUGIN_LIBRARY_ABI_VERSION,
+                found: abi_version,
+            });
+        }
+        // SAFETY: The declaration has the same layout as the host's
+        let declaration = unsafe { &*declaration };
+        if declaration.build_hash != BUILD_HASH {
+            return Err(LoadLibraryError::BuildMismatch {
+                path: path.to_owned(),
+            });
+        }
+        let expected = type_name::<Self>();
+        let found = (declaration.registry_type)();
+        if found != expected {
+            return Err(LoadLibraryError::RegistryMismatch {
+                path: path.to_owned(),
+                expected,
+                found: found.to_owned(),
+            });
+        }
+
+        let existing = self.plugins.keys().copied().collect::<HashSet<_>>();
+        (declaration.register)(self);
+
+        let library: LibraryHandle = Arc::new(library);
+        let mut registered = Vec::new();
+        for (id, state) in &mut self.plugins {
+            if !existing.contains(id) {
+                state.library = Some(library.clone());
+                registered.push(*id);
+            }
+        }
+        registered.sort_unstable();
+        self.libraries.push(library);
+        Ok(registered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "discovery")]
+    use crate::{DiscoveryIssue, PluginDiscovery};
+    use crate::{LoadLibraryError, PluginRegistry, hook_slot};
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::{self, Command};
+    use std::sync::{Mutex, PoisonError};
+
+    hook_slot!(GreetingSlot: dyn Fn() -> String + Send + Sync {
+        id: "biner::fixture::greeting",
+    });
+
+    const FIXTURE_API: &str = r#"
+use biner::hook_slot;
+
+pub trait Greeter: Send + Sync {
+    fn greet(&self, name: &str) -> String;
+}
+
+hook_slot!(pub GreeterSlot: dyn Greeter);
+"#;
+
+    const FIXTURE_LIB: &str = r#"
+use biner::{HookRegistry, Plugin, SimplePluginManifest, export_plugin_library, hook_slot};
+use biner_fixture_api::{Greeter, GreeterSlot};
+
+hook_slot!(GreetingSlot: dyn Fn() -> String + Send + Sync {
+    id: "biner::fixture::greeting",
+});
+
+struct LibraryGreeter;
+
+impl Greeter for LibraryGreeter {
+    fn greet(&self, name: &str) -> String {
+        format!("hello {name} from a library")
+    }
+}
+
+impl Plugin for LibraryGreeter {
+    fn load(&mut self, hooks: &mut HookRegistry, _context: &mut ()) {
+        let greeting = std::sync::Arc::new(|| "hello from a library".to_owned());
+        assert!(hooks.register_shared::<GreetingSlot>(greeting, "greeter", None).is_ok());
+        assert!(hooks.register::<GreeterSlot>(Box::new(LibraryGreeter), "greeter", None).is_ok());
+    }
+}
+
+export_plugin_library!(|registry| {
+    registry
+        .register(
+            SimplePluginManifest::new("greeter", "Greets from a library"),
+            Some(|| Box::new(LibraryGreeter)),
+        )
+        .unwrap();
+});
+"#;
+
+    const FIXTURE_HOST: &str = r#"
+use biner::PluginRegistry;
+use biner_fixture_api::GreeterSlot;
+use std::env;
+
+fn main() {
+    let path = env::args_os().nth(1).unwrap();
+    let mut plugins: PluginRegistry = PluginRegistry::new();
+    // SAFETY: The library is built in the same workspace as the host
+    unsafe { plugins.register_library(path) }.unwrap();
+    plugins.enable("greeter", &mut ()).unwrap();
+    let greeter = plugins.hooks().get_first::<GreeterSlot>("greeter").unwrap();
+    print!("{}", greeter.greet("host"));
+}
+"#;
+
+    /// A workspace of a plugin library, a host binary and an API crate shared by both, built with
+    /// cargo and removed when dropped.
+    struct Fixture {
+        dir: PathBuf,
+        library: PathBuf,
+        host: PathBuf,
+    }
+
+    impl Fixture {
+        fn build() -> Self {
+            static FIXTURES: Mutex<usize> = Mutex::new(0);
+
+            // Build artifacts are shared between fixtures, and the sources of each are unique at the
+            // same depth, so their paths relative to the workspace match between fixtures
+            let target_dir = env::current_exe()
+                .unwrap()
+                .ancestors()
+                .nth(3)
+                .unwrap()
+                .join("biner-dylib-fixture");
+            let mut count = FIXTURES.lock().unwrap_or_else(PoisonError::into_inner);
+            *count += 1;
+            let dir = target_dir
+                .join("src")
+                .join(format!("{}-{count}", process::id()));
+            let manifest_dir = env!("CARGO_MANIFEST_DIR");
+            // The library must enable the same features as the host
+            let features = env!("BINER_FEATURES")
+                .split(',')
+                .filter(|feature| !feature.is_empty())
+                .collect::<Vec<_>>();
+            let biner = format!("biner = {{ path = {manifest_dir:?}, features = {features:?} }}");
+
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(
+                dir.join("Cargo.toml"),
+                "[workspace]\nmembers = [\"api\", \"library\", \"host\"]\nresolver = \"3\"\n",
+            )
+            .unwrap();
+            // Resolve the same dependency versions as biner
+            let lockfile = Path::new(manifest_dir).join("Cargo.lock");
+            if lockfile.exists() {
+                fs::copy(lockfile, dir.join("Cargo.lock")).unwrap();
+            }
+            for (member, kind, source, dependencies) in [
+                ("api", "[lib]", FIXTURE_API, biner.clone()),
+                (
+                    "library",
+                    "[lib]\ncrate-type = [\"cdylib\"]",
+                    FIXTURE_LIB,
+                    format!("{biner}\nbiner_fixture_api = {{ path = \"../api\" }}"),
+                ),
+                (
+                    "host",
+                    "[[bin]]\nname = \"biner_fixture_host\"",
+                    FIXTURE_HOST,
+                    format!("{biner}\nbiner_fixture_api = {{ path = \"../api\" }}"),
+                ),
+            ] {
+                fs::create_dir_all(dir.join(member)).unwrap();
+                fs::write(
+                    dir.join(member).join("Cargo.toml"),
+                    format!(
+                        "[package]\nname = \"biner_fixture_{member}\"\nversion = \"0.0.0\"\n\
+                         edition = \"2024\"\npublish = false\n\n{kind}\npath = \"main.rs\"\n\n\
+                         [dependencies]\n{dependencies}\n"
+                    ),
+                )
+                .unwrap();
+                fs::write(dir.join(member).join("main.rs"), source).unwrap();
+            }
+
+            let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned()))
+                .args(["build", "--quiet", "--workspace", "--manifest-path"])
+                .arg(dir.join("Cargo.toml"))
+                .env("CARGO_TARGET_DIR", &target_dir)
+                .status()
+                .unwrap();
+            assert!(status.success());
+            // Copy the outputs while building is locked, as other builds overwrite them
+            let output = target_dir.join("debug");
+            let library = dir.join(libloading::library_filename("biner_fixture_library"));
+            fs::copy(
+                output.join(libloading::library_filename("biner_fixture_library")),
+                &library,
+            )
+            .unwrap();
+            let host = dir.join(format!("biner_fixture_host{}", env::consts::EXE_SUFFIX));
+            fs::copy(
+                output.join(format!("biner_fixture_host{}", env::consts::EXE_SUFFIX)),
+                &host,
+            )
+            .unwrap();
+            Self { dir, library, host }
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn register_library_plugins() {
+        let fixture = Fixture::build();
+        let mut plugins: PluginRegistry = PluginRegistry::new();
+        // SAFETY: The fixture is built against this biner by the same compiler
+        let registered = unsafe { plugins.register_library(&fixture.library) }.unwrap();
+        assert_eq!(registered, ["greeter"]);
+
+        plugins.enable("greeter", &mut ()).unwrap();
+        assert_eq!(
+            plugins
+                .hooks()
+                .get_first::<GreetingSlot>("greeter")
+                .unwrap()(),
+            "hello from a library"
+        );
+
+        // Shared hooks in a snapshot keep the library mapped after the registry is dropped
+        let snapshot = plugins.hooks().snapshot();
+        drop(plugins);
+        assert_eq!(
+            snapshot
+                .slot::<GreetingSlot>()
+                .hooks_and_plugin()
+                .next()
+                .unwrap()
+                .1(),
+            "hello from a library"
+        );
+        drop(snapshot);
+
+        // Hooks of traits other than those of `std` work when the library and the host share a
+        // crate declaring them. This test itself cannot use such a trait, as it links a separate
+        // build of biner.
+        let output = Command::new(&fixture.host)
+            .arg(&fixture.library)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"hello host from a library");
+    }
+
+    #[test]
+    #[cfg(feature = "discovery")]
+    #[cfg_attr(miri, ignore)]
+    fn discover_library_plugins() {
+        let fixture = Fixture::build();
+        let (matching, mismatched) = (fixture.dir.join("matching"), fixture.dir.join("mismatched"));
+        for (dir, description) in [
+            (&matching, "Greets from a library"),
+            (&mismatched, "Greets from a file"),
+        ] {
+            fs::create_dir_all(dir).unwrap();
+            fs::write(
+                dir.join("greeter.toml"),
+                format!("id = \"greeter\"\ndescription = {description:?}\n"),
+            )
+            .unwrap();
+            fs::copy(
+                &fixture.library,
+                dir.join(libloading::library_filename("greeter")),
+            )
+            .unwrap();
+        }
+
+        let mut plugins: PluginRegistry = PluginRegistry::new();
+        // SAFETY: The fixture is built against this biner by the same compiler
+        let report = unsafe {
+            PluginDiscovery::new()
+                .directory(&matching)
+                .register_with_libraries(&mut plugins)
+        };
+        assert_eq!(report.registered, ["greeter"]);
+        assert!(report.issues.is_empty());
+        plugins.enable("greeter", &mut ()).unwrap();
+
+        // The manifest registered by the library must match the manifest file
+        let mut plugins: PluginRegistry = PluginRegistry::new();
+        // SAFETY: The fixture is built against this biner by the same compiler
+        let report = unsafe {
+            PluginDiscovery::new()
+                .directory(&mismatched)
+                .register_with_libraries(&mut plugins)
+        };
+        assert!(report.registered.is_empty());
+        assert!(matches!(
+            &report.issues[..],
+            [DiscoveryIssue::Library { reason, .. }] if reason.contains("different manifest")
+        ));
+        assert!(!plugins.exists("greeter"));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn reject_missing_library() {
+        let mut plugins: PluginRegistry = PluginRegistry::new();
+        // SAFETY: The library does not exist
+        let error = unsafe { plugins.register_library("does/not/exist.so") }.unwrap_err();
+        assert!(matches!(error, LoadLibraryError::Open { .. }));
+    }
+}
@@ -0,0 +1,345 @@
+use super::{PluginManifest, PluginRegistry};
+use std::fmt::{Display, Write};
+
+/// The kind of a dependency edge in a [`DependencyGraph`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum DependencyKind {
+    /// The plugin requires the dependency, which is registered.
+    Required,
+    /// The plugin requires the dependency, but no plugin with its id is registered.
+    Missing,
+}
+
+impl DependencyKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Required => "required",
+            Self::Missing => "missing",
+        }
+    }
+}
+
+/// A plugin in a [`DependencyGraph`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphNode<Id> {
+    /// Plugin id of the plugin.
+    pub id: Id,
+    /// Whether the plugin is registered, rather than only listed as a dependency.
+    pub registered: bool,
+    /// Whether the plugin was loaded.
+    pub loaded: bool,
+    /// Whether the plugin was enabled.
+    pub enabled: bool,
+}
+
+impl<Id> GraphNode<Id> {
+    fn state(&self) -> &'static str {
+        match (self.registered, self.loaded, self.enabled) {
+            (false, _, _) => "missing",
+            (true, _, true) => "enabled",
+            (true, true, false) => "loaded",
+            (true, false, false) => "registered",
+        }
+    }
+}
+
+/// A dependency of a plugin in a [`DependencyGraph`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphEdge<Id> {
+    /// Plugin id of the plugin listing the dependency.
+    pub plugin: Id,
+    /// Plugin id of the dependency.
+    pub dependency: Id,
+    /// Index of the dependency in the plugin's [`PluginManifest::dependencies`].
+    pub index: usize,
+    /// Kind of the dependency.
+    pub kind: DependencyKind,
+}
+
+/// The plugin dependency topology of a [`PluginRegistry`], taken with
+/// [`PluginRegistry::dependency_graph`]. The graph can be rendered to Graphviz DOT, Mermaid or JSON
+/// for documentation and debugging, and with the `serde` feature it can be serialized whenever the
+/// plugin ids can.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DependencyGraph<Id> {
+    /// All registered plugins and the dependencies they list, ordered by plugin id.
+    pub nodes: Vec<GraphNode<Id>>,
+    /// Edges from each plugin to its dependencies, ordered by plugin id and dependency index.
+    pub edges: Vec<GraphEdge<Id>>,
+}
+
+impl<Id> Default for DependencyGraph<Id> {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+}
+
+impl<Id> DependencyGraph<Id>
+where
+    Id: Copy + Ord + Display,
+{
+    fn node_index(&self, id: Id) -> usize {
+        self.nodes
+            .binary_search_by_key(&id, |node| node.id)
+            .unwrap()
+    }
+
+    /// Render the graph in the Graphviz DOT language. Each node is labelled with its plugin id and
+    /// state, and each edge with its dependency index and kind.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph plugins {\n");
+        for node in &self.nodes {
+            let style = match node.state() {
+                "missing" => "dotted",
+                "enabled" => "bold",
+                "registered" => "dashed",
+                _ => "solid",
+            };
+            writeln!(
+                dot,
+                "    \"{id}\" [label=\"{id}\\n{state}\", style={style}, loaded={loaded}, enabled={enabled}];",
+                id = escape_dot(node.id),
+                state = node.state(),
+                loaded = node.loaded,
+                enabled = node.enabled,
+            )
+            .unwrap();
+        }
+        for edge in &self.edges {
+            writeln!(
+                dot,
+                "    \"{}\" -> \"{}\" [label=\"{}: {}\"{}];",
+                escape_dot(edge.plugin),
+                escape_dot(edge.dependency),
+                edge.index,
+                edge.kind.as_str(),
+                if edge.kind == DependencyKind::Missing {
+                    ", style=dashed"
+                } else {
+                    ""
+                },
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Render the graph as a Mermaid flowchart. Each node is labelled with its plugin id and state
+    /// and styled by a class named after its state, and each edge is labelled with its dependency
+    /// index and kind.
+    #[must_use]
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::from("flowchart LR\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            writeln!(
+                mermaid,
+                "    n{i}[\"{}<br/>{}\"]:::{}",
+                escape_mermaid(node.id),
+                node.state(),
+                node.state(),
+            )
+            .unwrap();
+        }
+        for edge in &self.edges {
+            writeln!(
+                mermaid,
+                "    n{} {}|\"{}: {}\"| n{}",
+                self.node_index(edge.plugin),
+                if edge.kind == DependencyKind::Missing {
+                    "-.->"
+                } else {
+                    "-->"
+                },
+                edge.index,
+                edge.kind.as_str(),
+                self.node_index(edge.dependency),
+            )
+            .unwrap();
+        }
+        mermaid.push_str(
+            "    classDef enabled stroke-width:3px\n    classDef registered stroke-dasharray:4\n    \
+             classDef missing stroke-dasharray:2,fill:none\n",
+        );
+        mermaid
+    }
+
+    /// Render the graph as JSON, with the plugin ids as strings. The JSON has the same structure as
+    /// the graph's serialization with the `serde` feature.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"nodes\":[");
+        for (i, node) in self.nodes.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            write!(
+                json,
+                "{{\"id\":{},\"registered\":{},\"loaded\":{},\"enabled\":{}}}",
+                escape_json(node.id),
+                node.registered,
+                node.loaded,
+                node.enabled,
+            )
+            .unwrap();
+        }
+        json.push_str("],\"edges\":[");
+        for (i, edge) in self.edges.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            write!(
+                json,
+                "{{\"plugin\":{},\"dependency\":{},\"index\":{},\"kind\":\"{}\"}}",
+                escape_json(edge.plugin),
+                escape_json(edge.dependency),
+                edge.index,
+                edge.kind.as_str(),
+            )
+            .unwrap();
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+fn escape_dot(id: impl Display) -> String {
+    id.to_string().replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_mermaid(id: impl Display) -> String {
+    id.to_string()
+        .replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+}
+
+fn escape_json(id: impl Display) -> String {
+    let mut json = String::from("\"");
+    for c in id.to_string().chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+impl<Manifest, Context> PluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+{
+    /// Take a snapshot of the dependency graph of all registered plugins, including the state of
+    /// each plugin and dependencies that are listed but not registered.
+    #[must_use]
+    pub fn dependency_graph(&self) -> DependencyGraph<Manifest::PluginId> {
+        let mut nodes = self
+            .dependency_graph
+            .nodes()
+            .map(|id| {
+                let state = self.plugins.get(&id);
+                GraphNode {
+                    id,
+                    registered: state.is_some(),
+                    loaded: state.is_some_and(|state| state.plugin.is_some()),
+                    enabled: state.is_some_and(|state| state.enabled),
+                }
+            })
+            .collect::<Vec<_>>();
+        nodes.sort_unstable_by_key(|node| node.id);
+        let mut edges = self
+            .dependency_graph
+            .all_edges()
+            .map(|(plugin, dependency, &index)| GraphEdge {
+                plugin,
+                dependency,
+                index,
+                kind: if self.plugins.contains_key(&dependency) {
+                    DependencyKind::Required
+                } else {
+                    DependencyKind::Missing
+                },
+            })
+            .collect::<Vec<_>>();
+        edges.sort_unstable_by_key(|edge| (edge.plugin, edge.index));
+        DependencyGraph { nodes, edges }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Plugin, PluginRegistry, SimplePluginManifest};
+
+    struct TestPlugin;
+
+    impl Plugin for TestPlugin {}
+
+    #[test]
+    fn export_dependency_graph() {
+        let mut plugins: PluginRegistry = PluginRegistry::new();
+        plugins
+            .register(
+                SimplePluginManifest::new("core", ""),
+                Some(|| Box::new(TestPlugin)),
+            )
+            .unwrap();
+        plugins
+            .register(
+                SimplePluginManifest::with_dependencies("editor", "", vec!["core", "\"theme\""]),
+                Some(|| Box::new(TestPlugin)),
+            )
+            .unwrap();
+        plugins.load("core", &mut ()).unwrap();
+        plugins.enable("core", &mut ()).unwrap();
+
+        let graph = plugins.dependency_graph();
+        assert_eq!(
+            graph.to_dot(),
+            r#"digraph plugins {
+    "\"theme\"" [label="\"theme\"\nmissing", style=dotted, loaded=false, enabled=false];
+    "core" [label="core\nenabled", style=bold, loaded=true, enabled=true];
+    "editor" [label="editor\nregistered", style=dashed, loaded=false, enabled=false];
+    "editor" -> "core" [label="0: required"];
+    "editor" -> "\"theme\"" [label="1: missing", style=dashed];
+}
+"#
+        );
+        assert!(graph.to_mermaid().contains(
+            "    n0[\"#quot;theme#quot;<br/>missing\"]:::missing\n    n1[\"core<br/>enabled\"]:::enabled\n"
+        ));
+        assert!(
+            graph
+                .to_mermaid()
+                .contains("    n2 -.->|\"1: missing\"| n0\n")
+        );
+
+        let json = graph.to_json();
+        assert!(json.starts_with(
+            r#"{"nodes":[{"id":"\"theme\"","registered":false,"loaded":false,"enabled":false},"#
+        ));
+        assert!(json.ends_with(
+            r#"{"plugin":"editor","dependency":"\"theme\"","index":1,"kind":"missing"}]}"#
+        ));
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            serde_json::to_string(&graph).unwrap(),
+            json,
+            "manual JSON matches the serialized graph"
+        );
+    }
+}
@@ -0,0 +1,390 @@
+use super::{LoadPluginError, PluginManifest, PluginRegistry, TracePluginId, trace};
+use crate::{BreakerAction, HookTripped};
+use petgraph::Direction::Incoming;
+use std::any::Any;
+use std::fmt::{Display, Formatter};
+use std::panic::{self, AssertUnwindSafe};
+use thiserror::Error;
+
+/// A plugin lifecycle method, as reported by a [`PluginPanic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum LifecyclePhase {
+    /// Constructing the plugin or [`Plugin::load`][super::Plugin::load].
+    Load,
+    /// [`Plugin::enable`][super::Plugin::enable].
+    Enable,
+    /// [`Plugin::disable`][super::Plugin::disable].
+    Disable,
+    /// [`Plugin::unload`][super::Plugin::unload].
+    Unload,
+    /// [`Plugin::reconfigure`][super::Plugin::reconfigure].
+    Reconfigure,
+}
+
+impl Display for LifecyclePhase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Load => "load",
+            Self::Enable => "enable",
+            Self::Disable => "disable",
+            Self::Unload => "unload",
+            Self::Reconfigure => "reconfigure",
+        })
+    }
+}
+
+/// A panic caught in a plugin lifecycle method with panic isolation enabled; see
+/// [`PluginRegistry::set_panic_isolation`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Error)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[error("plugin `{plugin}` panicked in {phase}: {message}")]
+pub struct PluginPanic<Id> {
+    /// Plugin id of the plugin that panicked.
+    pub plugin: Id,
+    /// The lifecycle method that panicked.
+    pub phase: LifecyclePhase,
+    /// The panic message, if the panic payload was a string.
+    pub message: String,
+}
+
+/// Call `f` in the span of the plugin, catching any panic as a [`PluginPanic`] if `enabled`.
+pub(super) fn isolate<Id, R>(
+    enabled: bool,
+    plugin: Id,
+    phase: LifecyclePhase,
+    f: impl FnOnce() -> R,
+) -> Result<R, PluginPanic<Id>>
+where
+    Id: TracePluginId,
+{
+    let _span = trace::enter_plugin(&plugin, phase);
+    if !enabled {
+        return Ok(f());
+    }
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| PluginPanic {
+        plugin,
+        phase,
+        message: panic_message(payload.as_ref()),
+    })
+}
+
+/// Call the lifecycle method `f` of a plugin with [`isolate`], where `f` returns the failure the
+/// plugin reported with [`Plugin::take_failure`][super::Plugin::take_failure].
+pub(super) fn isolate_lifecycle<Id>(
+    enabled: bool,
+    plugin: Id,
+    phase: LifecyclePhase,
+    f: impl FnOnce() -> Option<String>,
+) -> Result<(), LoadPluginError<Id>>
+where
+    Id: Copy + TracePluginId,
+{
+    match isolate(enabled, plugin, phase, f)? {
+        Some(reason) => Err(LoadPluginError::Failed { plugin, reason }),
+        None => Ok(()),
+    }
+}
+
+/// Get the message of a panic from its payload.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_owned()
+    }
+}
+
+impl<Manifest, Context> PluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+    Manifest::PluginId: 'static,
+    Context: 'static,
+{
+    /// Enable or disable panic isolation, which is disabled by default. With panic isolation, a
+    /// panic in a plugin's constructor or lifecycle method called by the synchronous lifecycle
+    /// methods of the registry, such as [`PluginRegistry::load`] and [`PluginRegistry::enable`], is
+    /// caught instead of unwinding through the registry. The panicking plugin is moved to the
+    /// [`Failed`][super::PluginStatus::Failed] status: it is disabled, its hooks and services are
+    /// removed, and all plugins depending on it are disabled. A plugin that panicked while loading
+    /// is left unloaded. Loading or enabling a failed plugin fails with
+    /// [`LoadPluginError::PreviouslyFailed`][super::LoadPluginError::PreviouslyFailed] until it is retried with
+    /// [`PluginRegistry::retry`].
+    ///
+    /// Panics in the futures of [`AsyncPlugin`][super::AsyncPlugin] methods are not caught.
+    pub fn set_panic_isolation(&mut self, enabled: bool) {
+        self.isolate_panics = enabled;
+    }
+
+    /// Determine whether panic isolation is enabled; see [`PluginRegistry::set_panic_isolation`].
+    #[must_use]
+    pub fn is_panic_isolated(&self) -> bool {
+        self.isolate_panics
+    }
+
+    /// Get the panic the plugin with the given plugin id failed with, if it failed by panicking.
+    /// See [`PluginRegistry::failure`] for failures of any kind.
+    #[must_use]
+    pub fn fault(&self, id: Manifest::PluginId) -> Option<&PluginPanic<Manifest::PluginId>> {
+        match self.plugins.get(&id)?.failure.as_ref()? {
+            LoadPluginError::Panicked(panic) => Some(panic),
+            _ => None,
+        }
+    }
+
+    /// Get an iterator over the ids of all plugins that failed by panicking.
+    pub fn faulted_plugin_ids(&self) -> impl Iterator<Item = Manifest::PluginId> {
+        self.plugins
+            .iter()
+            .filter(|(_, state)| matches!(state.failure, Some(LoadPluginError::Panicked(_))))
+            .map(|(&id, _)| id)
+    }
+
+    /// Clear the failure of the plugin with the given plugin id if it failed by panicking, so that
+    /// it can be loaded and enabled again, returning the panic. Unlike [`PluginRegistry::retry`],
+    /// other failures and the quarantine of the plugin are kept.
+    pub fn clear_fault(
+        &mut self,
+        id: Manifest::PluginId,
+    ) -> Option<PluginPanic<Manifest::PluginId>> {
+        let state = self.plugins.get_mut(&id)?;
+        match state.failure.take() {
+            Some(LoadPluginError::Panicked(panic)) => Some(panic),
+            failure => {
+                state.failure = failure;
+                None
+            }
+        }
+    }
+
+    /// Take the events of all hooks that tripped the circuit breaker of the hook registry, as with
+    /// [`HookRegistry::take_tripped_hooks`][crate::HookRegistry::take_tripped_hooks], and disable
+    /// the plugins whose hooks requested it with [`BreakerAction::RequestDisable`] along with all
+    /// plugins that depend on them, passing `context` to their [`Plugin::disable`] methods. The
+    /// hooks of those plugins stay suspended until the plugin is enabled again, resumed with
+    /// [`HookRegistry::resume_plugin`][crate::HookRegistry::resume_plugin], or unloaded. Returns
+    /// the events for the host to report.
+    ///
+    /// Tripping the breaker only suspends hooks: plugins are disabled when the host calls this
+    /// method, such as after dispatching hooks or periodically along with
+    /// [`PluginRegistry::handle_plugin_failures`].
+    ///
+    /// [`Plugin::disable`]: super::Plugin::disable
+    pub fn handle_tripped_hooks(
+        &mut self,
+        context: &mut Context,
+    ) -> Vec<HookTripped<Manifest::PluginId>> {
+        let tripped = self.hooks.take_tripped_hooks();
+        for event in &tripped {
+            if event.action == BreakerAction::RequestDisable {
+                let _ = self.disable(event.plugin, context);
+            }
+        }
+        tripped
+    }
+
+    /// Take the failures loaded plugins report through [`Plugin::take_failure`] outside of their
+    /// lifecycle steps, such as the process of a process plugin crashing in one of its hooks. Each
+    /// failed plugin is moved to the [`Failed`][super::PluginStatus::Failed] status with
+    /// [`LoadPluginError::Failed`]: it is disabled, its hooks and services are removed, and all
+    /// plugins depending on it are disabled, passing `context` to their [`Plugin::disable`]
+    /// methods. Returns the plugin ids of the failed plugins.
+    ///
+    /// [`Plugin::take_failure`]: super::Plugin::take_failure
+    /// [`Plugin::disable`]: super::Plugin::disable
+    pub fn handle_plugin_failures(&mut self, context: &mut Context) -> Vec<Manifest::PluginId> {
+        let mut failures = self
+            .plugins
+            .iter_mut()
+            .filter(|(_, state)| state.failure.is_none())
+            .filter_map(|(&id, state)| {
+                let reason = state.plugin.as_mut()?.as_plugin_mut().take_failure()?;
+                Some((id, reason))
+            })
+            .collect::<Vec<_>>();
+        failures.sort_unstable_by_key(|(id, _)| *id);
+        for (plugin, reason) in &failures {
+            let error = LoadPluginError::Failed {
+                plugin: *plugin,
+                reason: reason.clone(),
+            };
+            self.fail_plugin(*plugin, error, context);
+        }
+        failures.into_iter().map(|(id, _)| id).collect()
+    }
+
+    /// Move the plugin that panicked or reported a failure to the failed status, disabling its
+    /// dependents and removing its hooks. Returns the plugin ids disabled.
+    pub(super) fn fail_plugin(
+        &mut self,
+        id: Manifest::PluginId,
+        error: LoadPluginError<Manifest::PluginId>,
+        context: &mut Context,
+    ) -> Vec<Manifest::PluginId> {
+        let mut disabled = Vec::new();
+        let mut dependents = self
+            .dependency_graph
+            .edges_directed(id, Incoming)
+            .map(|(d, _, &i)| (d, i))
+            .collect::<Vec<_>>();
+        dependents.sort_unstable_by_key(|(_, i)| *i);
+        for (dep, _) in dependents.into_iter().rev() {
+            disabled.extend(self.disable_plugin(dep, Some(id), context));
+        }
+
+        let state = self.plugins.get_mut(&id).unwrap();
+        if state.enabled {
+            state.enabled = false;
+            disabled.push(id);
+        }
+        state.explicit_enable = false;
+        state.failure = Some(error);
+        self.hooks.remove_plugin_hooks(id);
+        self.hooks.withdraw_services(id);
+        disabled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        BreakerAction, CircuitBreaker, HookRegistry, LifecyclePhase, LoadPluginError, Plugin,
+        PluginPanic, PluginRegistry, SimplePluginManifest, hook_slot,
+    };
+
+    trait Greeter: Send + Sync {}
+
+    impl Greeter for () {}
+
+    hook_slot!(GreeterSlot: dyn Greeter);
+
+    struct TestPlugin;
+
+    impl Plugin for TestPlugin {
+        fn load(&mut self, hooks: &mut HookRegistry, _context: &mut ()) {
+            hooks
+                .register::<GreeterSlot>(Box::new(()), "base", None)
+                .unwrap();
+        }
+    }
+
+    struct EmptyPlugin;
+
+    impl Plugin for EmptyPlugin {}
+
+    struct PanickingPlugin;
+
+    impl Plugin for PanickingPlugin {
+        fn load(&mut self, hooks: &mut HookRegistry, _context: &mut ()) {
+            hooks
+                .register::<GreeterSlot>(Box::new(()), "panicking", None)
+                .unwrap();
+        }
+
+        fn enable(&mut self, _context: &mut ()) {
+            panic!("enable failed");
+        }
+    }
+
+    #[test]
+    fn isolate_plugin_panics() {
+        let mut plugins = PluginRegistry::new();
+        plugins.set_panic_isolation(true);
+        plugins
+            .register(
+                SimplePluginManifest::new("base", ""),
+                Some(|| Box::new(TestPlugin)),
+            )
+            .unwrap();
+        plugins
+            .register(
+                SimplePluginManifest::with_dependencies("panicking", "", vec!["base"]),
+                Some(|| Box::new(PanickingPlugin)),
+            )
+            .unwrap();
+        plugins
+            .register(
+                SimplePluginManifest::with_dependencies("dependent", "", vec!["panicking"]),
+                Some(|| Box::new(EmptyPlugin)),
+            )
+            .unwrap();
+
+        let panic = PluginPanic {
+            plugin: "panicking",
+            phase: LifecyclePhase::Enable,
+            message: "enable failed".to_owned(),
+        };
+        assert_eq!(
+            plugins.enable("dependent", &mut ()),
+            Err(LoadPluginError::Panicked(panic.clone()))
+        );
+        assert!(plugins.is_enabled("base"));
+        assert!(!plugins.is_enabled("panicking"));
+        assert!(!plugins.is_enabled("dependent"));
+        assert!(!plugins.hooks().exists::<GreeterSlot>("panicking"));
+        assert!(plugins.hooks().exists::<GreeterSlot>("base"));
+        assert_eq!(
+            plugins.failure("panicking"),
+            Some(&LoadPluginError::Panicked(panic.clone()))
+        );
+        assert_eq!(plugins.fault("panicking"), Some(&panic));
+        assert_eq!(
+            plugins.faulted_plugin_ids().collect::<Vec<_>>(),
+            ["panicking"]
+        );
+        assert_eq!(
+            plugins.enable("panicking", &mut ()),
+            Err(LoadPluginError::PreviouslyFailed("panicking"))
+        );
+
+        // The registry stays usable after the panic
+        let _ = plugins.unload("base", &mut ());
+        assert_eq!(plugins.loaded_plugin_count(), 0);
+        assert_eq!(plugins.clear_fault("panicking"), Some(panic));
+        assert_eq!(plugins.failed_plugin_ids().count(), 0);
+        assert_eq!(plugins.retry("panicking"), None);
+    }
+
+    #[test]
+    fn disable_tripped_plugins() {
+        let mut plugins = PluginRegistry::new();
+        plugins
+            .register(
+                SimplePluginManifest::new("base", ""),
+                Some(|| Box::new(TestPlugin)),
+            )
+            .unwrap();
+        plugins.enable("base", &mut ()).unwrap();
+        plugins
+            .hooks_mut()
+            .set_circuit_breaker(Some(CircuitBreaker::new(1, BreakerAction::RequestDisable)));
+
+        let greeted = plugins
+            .hooks()
+            .dispatch::<GreeterSlot, _>(|_, _| panic!("greet failed"));
+        assert!(greeted.is_empty());
+        assert!(plugins.hooks().is_plugin_suspended("base"));
+
+        let tripped = plugins.handle_tripped_hooks(&mut ());
+        assert_eq!(tripped.len(), 1);
+        assert_eq!(tripped[0].reason, "hook panicked: greet failed");
+        assert!(!plugins.is_enabled("base"));
+        assert!(
+            plugins
+                .hooks()
+                .dispatch::<GreeterSlot, _>(|_, _| ())
+                .is_empty()
+        );
+        assert!(plugins.handle_tripped_hooks(&mut ()).is_empty());
+
+        plugins.enable("base", &mut ()).unwrap();
+        assert!(!plugins.hooks().is_plugin_suspended("base"));
+        assert_eq!(
+            plugins.hooks().dispatch::<GreeterSlot, _>(|_, _| ()),
+            vec![("base", ())]
+        );
+    }
+}
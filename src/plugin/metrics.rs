@@ -0,0 +1,276 @@
+use super::{LifecyclePhase, PluginManifest, PluginRegistry};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The wall time of a single plugin lifecycle call, passed to the exporter set with
+/// [`PluginRegistry::set_metrics_exporter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LifecycleTiming<Id> {
+    /// Plugin id of the plugin.
+    pub plugin: Id,
+    /// The lifecycle method called.
+    pub phase: LifecyclePhase,
+    /// Time spent in the plugin's own lifecycle method, including constructing the plugin when
+    /// loading.
+    pub own: Duration,
+    /// Time spent by the registry on the plugin, including cascading through its dependencies when
+    /// loading or enabling, and through its dependents when disabling or unloading.
+    pub total: Duration,
+}
+
+/// Accumulated timings of a lifecycle method of a plugin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct PhaseMetrics {
+    /// Number of times the lifecycle method was called.
+    pub calls: u64,
+    /// Cumulative time spent in the plugin's own lifecycle method.
+    pub own: Duration,
+    /// Cumulative time spent by the registry on the plugin, including cascading.
+    pub total: Duration,
+}
+
+/// Accumulated lifecycle timings of a plugin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct PluginMetrics {
+    /// Timings of loading the plugin.
+    pub load: PhaseMetrics,
+    /// Timings of enabling the plugin.
+    pub enable: PhaseMetrics,
+    /// Timings of disabling the plugin.
+    pub disable: PhaseMetrics,
+    /// Timings of unloading the plugin.
+    pub unload: PhaseMetrics,
+    /// Timings of reconfiguring the plugin.
+    pub reconfigure: PhaseMetrics,
+}
+
+impl PluginMetrics {
+    /// Get the timings of a lifecycle method.
+    #[must_use]
+    pub fn phase(&self, phase: LifecyclePhase) -> &PhaseMetrics {
+        match phase {
+            LifecyclePhase::Load => &self.load,
+            LifecyclePhase::Enable => &self.enable,
+            LifecyclePhase::Disable => &self.disable,
+            LifecyclePhase::Unload => &self.unload,
+            LifecyclePhase::Reconfigure => &self.reconfigure,
+        }
+    }
+
+    fn phase_mut(&mut self, phase: LifecyclePhase) -> &mut PhaseMetrics {
+        match phase {
+            LifecyclePhase::Load => &mut self.load,
+            LifecyclePhase::Enable => &mut self.enable,
+            LifecyclePhase::Disable => &mut self.disable,
+            LifecyclePhase::Unload => &mut self.unload,
+            LifecyclePhase::Reconfigure => &mut self.reconfigure,
+        }
+    }
+
+    /// Get the cumulative time spent in all of the plugin's own lifecycle methods.
+    #[must_use]
+    pub fn own(&self) -> Duration {
+        self.load.own + self.enable.own + self.disable.own + self.unload.own + self.reconfigure.own
+    }
+}
+
+type MetricsExporter<Id> = Arc<dyn Fn(&LifecycleTiming<Id>) + Send + Sync>;
+
+/// Lifecycle timings of the plugins of a [`PluginRegistry`], returned by
+/// [`PluginRegistry::metrics`]. Timings are measured for the lifecycle calls made by the
+/// synchronous lifecycle methods of the registry, such as [`PluginRegistry::load`] and
+/// [`PluginRegistry::enable`].
+pub struct LifecycleMetrics<Id> {
+    plugins: HashMap<Id, PluginMetrics>,
+    exporter: Option<MetricsExporter<Id>>,
+}
+
+impl<Id> LifecycleMetrics<Id>
+where
+    Id: Copy + Ord + Hash,
+{
+    /// Get the timings of a plugin, if any of its lifecycle methods were called.
+    #[must_use]
+    pub fn plugin(&self, id: Id) -> Option<&PluginMetrics> {
+        self.plugins.get(&id)
+    }
+
+    /// Get an iterator over the timings of all plugins.
+    pub fn iter(&self) -> impl Iterator<Item = (Id, &PluginMetrics)> {
+        self.plugins.iter().map(|(&id, metrics)| (id, metrics))
+    }
+
+    /// Get the `count` plugins that spent the most time in their own lifecycle methods, slowest
+    /// first.
+    #[must_use]
+    pub fn slowest(&self, count: usize) -> Vec<(Id, Duration)> {
+        let mut slowest = self
+            .plugins
+            .iter()
+            .map(|(&id, metrics)| (id, metrics.own()))
+            .collect::<Vec<_>>();
+        slowest.sort_unstable_by(|(a_id, a), (b_id, b)| b.cmp(a).then(a_id.cmp(b_id)));
+        slowest.truncate(count);
+        slowest
+    }
+
+    fn record(&mut self, timing: LifecycleTiming<Id>) {
+        let phase = self
+            .plugins
+            .entry(timing.plugin)
+            .or_default()
+            .phase_mut(timing.phase);
+        phase.calls += 1;
+        phase.own += timing.own;
+        phase.total += timing.total;
+        if let Some(exporter) = &self.exporter {
+            exporter(&timing);
+        }
+    }
+}
+
+impl<Id> Default for LifecycleMetrics<Id> {
+    fn default() -> Self {
+        Self {
+            plugins: HashMap::new(),
+            exporter: None,
+        }
+    }
+}
+
+impl<Id> Debug for LifecycleMetrics<Id>
+where
+    Id: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LifecycleMetrics")
+            .field("plugins", &self.plugins)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<Manifest, Context> PluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+{
+    /// Get the lifecycle timings of the plugins.
+    #[must_use]
+    pub fn metrics(&self) -> &LifecycleMetrics<Manifest::PluginId> {
+        &self.metrics
+    }
+
+    /// Clear the lifecycle timings of all plugins.
+    pub fn reset_metrics(&mut self) {
+        self.metrics.plugins.clear();
+    }
+
+    /// Set a function called with the timing of every plugin lifecycle call measured by the
+    /// registry, such as to export the timings to a metrics system.
+    pub fn set_metrics_exporter(
+        &mut self,
+        exporter: impl Fn(&LifecycleTiming<Manifest::PluginId>) + Send + Sync + 'static,
+    ) {
+        self.metrics.exporter = Some(Arc::new(exporter));
+    }
+
+    /// Remove the function set with [`PluginRegistry::set_metrics_exporter`].
+    pub fn clear_metrics_exporter(&mut self) {
+        self.metrics.exporter = None;
+    }
+
+    /// Record the timing of a lifecycle call of a plugin that the registry started working on at
+    /// `started`.
+    pub(super) fn record_timing(
+        &mut self,
+        plugin: Manifest::PluginId,
+        phase: LifecyclePhase,
+        own: Duration,
+        started: Instant,
+    ) {
+        self.metrics.record(LifecycleTiming {
+            plugin,
+            phase,
+            own,
+            total: started.elapsed(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LifecyclePhase, Plugin, PluginRegistry, SimplePluginManifest};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    struct SlowPlugin;
+
+    impl Plugin for SlowPlugin {
+        fn enable(&mut self, _context: &mut ()) {
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    struct TestPlugin;
+
+    impl Plugin for TestPlugin {}
+
+    #[test]
+    fn lifecycle_metrics() {
+        let mut plugins = PluginRegistry::new();
+        plugins
+            .register(
+                SimplePluginManifest::new("slow", ""),
+                Some(|| Box::new(SlowPlugin)),
+            )
+            .unwrap();
+        plugins
+            .register(
+                SimplePluginManifest::with_dependencies("app", "", vec!["slow"]),
+                Some(|| Box::new(TestPlugin)),
+            )
+            .unwrap();
+        let exported = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&exported);
+        plugins.set_metrics_exporter(move |timing| {
+            sink.lock().unwrap().push((timing.plugin, timing.phase));
+        });
+
+        plugins.enable("app", &mut ()).unwrap();
+        let app = plugins.metrics().plugin("app").unwrap();
+        let slow = plugins.metrics().plugin("slow").unwrap();
+        assert_eq!(app.enable.calls, 1);
+        assert!(app.enable.own < Duration::from_millis(20));
+        assert!(
+            app.enable.total >= Duration::from_millis(20),
+            "includes dependencies"
+        );
+        assert!(slow.enable.own >= Duration::from_millis(20));
+        assert_eq!(plugins.metrics().slowest(1)[0].0, "slow");
+        assert_eq!(
+            *exported.lock().unwrap(),
+            vec![
+                ("slow", LifecyclePhase::Load),
+                ("app", LifecyclePhase::Load),
+                ("slow", LifecyclePhase::Enable),
+                ("app", LifecyclePhase::Enable),
+            ]
+        );
+
+        let _ = plugins.unload("slow", &mut ());
+        assert_eq!(
+            plugins
+                .metrics()
+                .plugin("app")
+                .unwrap()
+                .phase(LifecyclePhase::Unload)
+                .calls,
+            1
+        );
+        plugins.reset_metrics();
+        assert_eq!(plugins.metrics().iter().count(), 0);
+    }
+}
@@ -0,0 +1,710 @@
+use crate::{HookRegistry, HookSlot, LoadPluginError, Plugin, PluginManifest, PluginRegistry};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::{Value, json};
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::Hash;
+use std::io::{self, BufRead, BufReader, Write};
+use std::marker::PhantomData;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// JSON-RPC method called on a plugin process to dispatch a call of a remote hook.
+const HOOK_METHOD: &str = "hook";
+
+/// Default time to wait for the plugin process to respond to a call.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An error occurred while communicating with the process of a [`ProcessPlugin`].
+#[derive(Debug, Error)]
+pub enum ProcessError {
+    /// The plugin process could not be started.
+    #[error("failed to start plugin process")]
+    Spawn(#[source] io::Error),
+    /// The plugin process exited, closed its standard streams, or was terminated after failing to
+    /// respond to a call. Once crashed, every later call into the plugin fails with this error.
+    #[error("plugin process crashed{}", .status.map(|s| format!(" ({s})")).unwrap_or_default())]
+    Crashed {
+        /// Exit status of the plugin process, if it exited by itself.
+        status: Option<ExitStatus>,
+    },
+    /// The plugin process did not respond to a call within the [timeout][ProcessPlugin::timeout],
+    /// and was terminated.
+    #[error("plugin process did not respond within {0:?}")]
+    Timeout(Duration),
+    /// The plugin process sent a message that is not a valid JSON-RPC response, which terminates
+    /// the plugin process, or a result that does not have the expected type.
+    #[error("invalid message from plugin process: {0}")]
+    Protocol(String),
+    /// The plugin process responded to a call with a JSON-RPC error.
+    #[error("plugin process returned error {code}: {message}")]
+    Remote {
+        /// JSON-RPC error code.
+        code: i64,
+        /// Error message provided by the plugin process.
+        message: String,
+    },
+}
+
+#[derive(Deserialize)]
+struct Response {
+    id: Option<u64>,
+    result: Option<Value>,
+    error: Option<ResponseError>,
+}
+
+#[derive(Deserialize)]
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+struct Connection {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    timeout: Duration,
+    next_id: u64,
+    crashed: Option<Option<ExitStatus>>,
+}
+
+impl Connection {
+    fn spawn(command: &mut Command, timeout: Duration) -> Result<Self, ProcessError> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(ProcessError::Spawn)?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        // Read on another thread, so that calls can stop waiting for a response after the timeout
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in stdout.lines() {
+                if line.ok().is_none_or(|line| sender.send(line).is_err()) {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            child,
+            stdin,
+            lines,
+            timeout,
+            next_id: 0,
+            crashed: None,
+        })
+    }
+
+    fn call(&mut self, method: &str, params: Value) -> Result<Value, ProcessError> {
+        if let Some(status) = self.crashed {
+            return Err(ProcessError::Crashed { status });
+        }
+        self.next_id += 1;
+        let id = self.next_id;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+        if writeln!(self.stdin, "{request}")
+            .and_then(|()| self.stdin.flush())
+            .is_err()
+        {
+            return Err(self.crash());
+        }
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let line = match self
+                .lines
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    self.abandon();
+                    return Err(ProcessError::Timeout(self.timeout));
+                }
+                Err(RecvTimeoutError::Disconnected) => return Err(self.crash()),
+            };
+            // Later responses cannot be told apart from garbage either, so give up on the process
+            let response = match serde_json::from_str::<Response>(&line) {
+                Ok(response) => response,
+                Err(error) => {
+                    self.abandon();
+                    return Err(ProcessError::Protocol(error.to_string()));
+                }
+            };
+            // Skip notifications
+            if response.id != Some(id) {
+                continue;
+            }
+            return match response.error {
+                Some(error) => Err(ProcessError::Remote {
+                    code: error.code,
+                    message: error.message,
+                }),
+                None => Ok(response.result.unwrap_or(Value::Null)),
+            };
+        }
+    }
+
+    /// Mark the plugin process crashed, collecting its exit status if it is exiting.
+    fn crash(&mut self) -> ProcessError {
+        let mut status = None;
+        for _ in 0..10 {
+            status = self.child.try_wait().ok().flatten();
+            if status.is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        if status.is_none() {
+            self.terminate();
+        }
+        self.crashed = Some(status);
+        ProcessError::Crashed { status }
+    }
+
+    /// Terminate the plugin process and mark it crashed, unless it already is.
+    fn abandon(&mut self) {
+        if self.crashed.is_none() {
+            self.terminate();
+            self.crashed = Some(None);
+        }
+    }
+
+    fn terminate(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if self.crashed.is_none() {
+            self.terminate();
+        }
+    }
+}
+
+/// A hook slot whose hooks can be provided by the process of a [`ProcessPlugin`]. Only slots
+/// implementing this trait can be dispatched to a plugin process.
+pub trait RemoteSlot: HookSlot {
+    /// Create a hook for the slot that forwards its calls to the plugin process through `hook`.
+    fn remote_hook(hook: RemoteHook) -> Box<Self::TraitObject>;
+}
+
+/// A handle for calling a hook provided by the process of a [`ProcessPlugin`], passed to
+/// [`RemoteSlot::remote_hook`]. Cloning the handle is cheap, as clones share the connection to the
+/// plugin process.
+#[derive(Clone)]
+pub struct RemoteHook {
+    connection: Arc<Mutex<Connection>>,
+    slot: &'static str,
+}
+
+impl RemoteHook {
+    /// Call the hook in the plugin process with the given arguments, sending the JSON-RPC request
+    /// `hook` with the slot's id and the arguments as its parameters. Calls into the same plugin
+    /// process are serialized.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProcessError::Crashed`] if the plugin process has crashed, or
+    /// [`ProcessError::Remote`] if it responded with an error. Returns [`ProcessError::Protocol`]
+    /// if the result could not be deserialized.
+    pub fn call<Args, R>(&self, args: Args) -> Result<R, ProcessError>
+    where
+        Args: Serialize,
+        R: DeserializeOwned,
+    {
+        let args = serde_json::to_value(args)
+            .map_err(|error| ProcessError::Protocol(error.to_string()))?;
+        let result = self
+            .connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .call(HOOK_METHOD, json!({ "slot": self.slot, "args": args }))?;
+        serde_json::from_value(result).map_err(|error| ProcessError::Protocol(error.to_string()))
+    }
+}
+
+impl Debug for RemoteHook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteHook")
+            .field("slot", &self.slot)
+            .finish_non_exhaustive()
+    }
+}
+
+type RemoteRegistration<Id> =
+    Box<dyn Fn(&mut HookRegistry<Id>, Id, RemoteHook) -> Result<(), String> + Send + Sync>;
+
+struct RemoteHookSlot<Id> {
+    slot: &'static str,
+    register: RemoteRegistration<Id>,
+}
+
+/// A [`Plugin`] running in a child process, for plugins that are untrusted or may crash. The plugin
+/// process communicates with the host by reading JSON-RPC 2.0 requests from its standard input and
+/// writing responses to its standard output, one JSON message per line.
+///
+/// The lifecycle of the plugin sends the requests `load`, `enable`, `disable` and `unload`
+/// without parameters. The process is started when loading the plugin and terminated after
+/// unloading it. Hooks for [remote slots][ProcessPlugin::remote] send the request `hook` with the
+/// parameters `slot`, the slot's id, and `args`, the arguments of the call.
+///
+/// When the plugin process exits, closes its standard streams, sends an invalid message or does
+/// not respond to a call within the [timeout][ProcessPlugin::timeout], it is terminated and all
+/// further calls into it fail with [`ProcessError::Crashed`].
+///
+/// A plugin process that cannot be started, crashes or responds with an error in a lifecycle step
+/// fails that step with [`LoadPluginError::Failed`], which marks the plugin failed and removes its
+/// hooks; see [`Plugin::take_failure`]. A crash while calling one of its hooks is reported the
+/// same way by [`PluginRegistry::handle_plugin_failures`].
+pub struct ProcessPlugin<Id = &'static str, Context = ()> {
+    id: Id,
+    command: Mutex<Command>,
+    slots: Vec<RemoteHookSlot<Id>>,
+    timeout: Duration,
+    connection: Option<Arc<Mutex<Connection>>>,
+    failure: Option<ProcessError>,
+    crash_reported: bool,
+    context: PhantomData<fn(&mut Context)>,
+}
+
+impl<Id, Context> ProcessPlugin<Id, Context>
+where
+    Id: Copy + Ord + Hash + Send + Sync + 'static,
+{
+    /// Create a process plugin for the plugin with the given plugin id, which runs `command` when
+    /// loaded. The standard input and output of the command are used for communicating with the
+    /// plugin process, while its standard error is kept as configured.
+    #[must_use]
+    pub fn new(id: Id, command: Command) -> Self {
+        Self {
+            id,
+            command: Mutex::new(command),
+            slots: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+            connection: None,
+            failure: None,
+            crash_reported: false,
+            context: PhantomData,
+        }
+    }
+
+    /// Dispatch the hook slot `Slot` to the plugin process. When the plugin loads, a hook created
+    /// with [`RemoteSlot::remote_hook`] is registered for the slot with the optional hook name.
+    #[must_use]
+    pub fn remote<Slot>(mut self, name: Option<Id>) -> Self
+    where
+        Id: Debug + Display,
+        Slot: RemoteSlot,
+    {
+        self.slots.push(RemoteHookSlot {
+            slot: Slot::id().name(),
+            register: Box::new(move |hooks, id, hook| {
+                hooks
+                    .register::<Slot>(Slot::remote_hook(hook), id, name)
+                    .map_err(|rejected| rejected.error.to_string())
+            }),
+        });
+        self
+    }
+
+    /// Set how long to wait for the plugin process to respond to a call, 30 seconds by default. A
+    /// plugin process that does not respond in time is terminated and considered crashed.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Get the plugin id the plugin was created for.
+    #[must_use]
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Determine whether the plugin process is currently running.
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.connection.is_some() && !self.is_failed()
+    }
+
+    /// Determine whether the plugin process has crashed.
+    #[must_use]
+    pub fn is_failed(&self) -> bool {
+        self.crashed().is_some()
+    }
+
+    /// Get the exit status of the plugin process if it has crashed.
+    fn crashed(&self) -> Option<Option<ExitStatus>> {
+        let connection = self.connection.as_ref()?;
+        connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .crashed
+    }
+
+    /// Start the plugin process and send it the `load` request, unless already started.
+    fn spawn(&mut self) -> Result<(), ProcessError> {
+        if self.connection.is_some() {
+            return Ok(());
+        }
+        let mut connection = Connection::spawn(
+            &mut self.command.lock().unwrap_or_else(PoisonError::into_inner),
+            self.timeout,
+        )?;
+        connection.call("load", Value::Null)?;
+        self.connection = Some(Arc::new(Mutex::new(connection)));
+        Ok(())
+    }
+
+    /// Send a lifecycle request to the plugin process, unless it crashed and was already reported.
+    fn call_lifecycle(&mut self, method: &str) {
+        if self.crash_reported {
+            return;
+        }
+        if let Some(connection) = &self.connection {
+            if let Err(error) = connection
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .call(method, Value::Null)
+            {
+                self.failure = Some(error);
+            }
+        }
+    }
+}
+
+impl<Id, Context> Plugin<Id, Context> for ProcessPlugin<Id, Context>
+where
+    Id: Copy + Ord + Hash + Send + Sync + 'static,
+    Context: 'static,
+{
+    fn load(&mut self, hooks: &mut HookRegistry<Id>, _context: &mut Context) {
+        if let Err(error) = self.spawn() {
+            self.failure = Some(error);
+            return;
+        }
+        let connection = self.connection.as_ref().unwrap();
+        for slot in &self.slots {
+            let hook = RemoteHook {
+                connection: connection.clone(),
+                slot: slot.slot,
+            };
+            if let Err(reason) = (slot.register)(hooks, self.id, hook) {
+                self.failure = Some(ProcessError::Protocol(reason));
+            }
+        }
+    }
+
+    fn unload(&mut self, _context: &mut Context) {
+        self.call_lifecycle("unload");
+        // Remaining remote hooks keep the connection alive, so terminate the process right away
+        if let Some(connection) = self.connection.take() {
+            connection
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .abandon();
+        }
+    }
+
+    fn enable(&mut self, _context: &mut Context) {
+        self.call_lifecycle("enable");
+    }
+
+    fn disable(&mut self, _context: &mut Context) {
+        self.call_lifecycle("disable");
+    }
+
+    fn take_failure(&mut self) -> Option<String> {
+        // A crash is only reported once, whether in a lifecycle step or while calling a hook
+        let failure = self.failure.take().or_else(|| {
+            let status = self.crashed().filter(|_| !self.crash_reported)?;
+            Some(ProcessError::Crashed { status })
+        })?;
+        self.crash_reported = self.is_failed();
+        Some(failure.to_string())
+    }
+}
+
+impl<Id, Context> Debug for ProcessPlugin<Id, Context>
+where
+    Id: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProcessPlugin")
+            .field("id", &self.id)
+            .field("command", &self.command)
+            .field("running", &self.connection.is_some())
+            .field("failure", &self.failure)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<Manifest, Context> PluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+    Manifest::PluginId: Send + Sync + 'static,
+    Context: 'static,
+{
+    /// Load the plugin registered with the given plugin id from a process plugin if it is not
+    /// currently loaded, the same as [`PluginRegistry::load_with`]. The plugin process is started
+    /// and sent the `load` request after the plugin's dependencies have been loaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`PluginRegistry::load_with`], and [`LoadPluginError::Failed`] if
+    /// the plugin process could not be started, crashed or responded with an error to `load`.
+    pub fn load_process(
+        &mut self,
+        id: Manifest::PluginId,
+        plugin: ProcessPlugin<Manifest::PluginId, Context>,
+        context: &mut Context,
+    ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
+        self.load_with(id, Box::new(plugin) as Box<dyn Plugin<_, _>>, context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::TempDir;
+    use crate::{
+        LoadPluginError, PluginRegistry, ProcessError, ProcessPlugin, RemoteHook, RemoteSlot,
+        SimplePluginManifest, hook_slot,
+    };
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process::Command;
+    use std::time::Duration;
+
+    type ShoutHook = dyn Fn(&str) -> Result<String, ProcessError> + Send + Sync;
+
+    macro_rules! fixture_slot {
+        ($slot:ident, $id:literal) => {
+            hook_slot!($slot: ShoutHook { id: $id });
+
+            impl RemoteSlot for $slot {
+                fn remote_hook(hook: RemoteHook) -> Box<ShoutHook> {
+                    Box::new(move |text| hook.call(text))
+                }
+            }
+        };
+    }
+
+    fixture_slot!(ShoutSlot, "biner::fixture::shout");
+    fixture_slot!(CrashSlot, "biner::fixture::crash");
+    fixture_slot!(HangSlot, "biner::fixture::hang");
+    fixture_slot!(GarbageSlot, "biner::fixture::garbage");
+
+    const FIXTURE_MAIN: &str = r#"
+use serde_json::{Value, json};
+use std::io::{BufRead, Write};
+
+fn main() {
+    let mut stdout = std::io::stdout();
+    for line in std::io::stdin().lock().lines() {
+        let request: Value = serde_json::from_str(&line.unwrap()).unwrap();
+        let params = &request["params"];
+        let result = match (request["method"].as_str().unwrap(), params["slot"].as_str()) {
+            ("load", _) if std::env::var_os("FIXTURE_FAIL_LOAD").is_some() => Err("refusing to load"),
+            ("enable", _) if std::env::var_os("FIXTURE_FAIL_ENABLE").is_some() => {
+                Err("refusing to enable")
+            }
+            ("hook", Some("biner::fixture::shout")) => {
+                Ok(json!(params["args"].as_str().unwrap().to_uppercase()))
+            }
+            ("hook", Some("biner::fixture::crash")) => std::process::exit(3),
+            ("hook", Some("biner::fixture::hang")) => {
+                std::thread::sleep(std::time::Duration::from_secs(60));
+                Ok(Value::Null)
+            }
+            ("hook", Some("biner::fixture::garbage")) => {
+                writeln!(stdout, "garbage").unwrap();
+                stdout.flush().unwrap();
+                continue;
+            }
+            ("hook", _) => Err("unknown slot"),
+            _ => Ok(Value::Null),
+        };
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }),
+            Err(message) => json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": { "code": -32000, "message": message },
+            }),
+        };
+        writeln!(stdout, "{response}").unwrap();
+        stdout.flush().unwrap();
+    }
+}
+"#;
+
+    /// Build the fixture plugin process with cargo in a temporary directory and return the
+    /// directory along with the path to the executable.
+    fn build_fixture() -> (TempDir, PathBuf) {
+        let manifest_dir = env!("CARGO_MANIFEST_DIR");
+        let dir = TempDir::new("process");
+        fs::write(
+            dir.join("Cargo.toml"),
+            r#"[package]
+name = "biner_process_fixture"
+version = "0.0.0"
+edition = "2024"
+publish = false
+
+[[bin]]
+name = "biner_process_fixture"
+path = "main.rs"
+
+[dependencies]
+serde_json = "1.0.140"
+
+[workspace]
+"#,
+        )
+        .unwrap();
+        fs::write(dir.join("main.rs"), FIXTURE_MAIN).unwrap();
+        // Resolve the same dependency versions as the host
+        let lockfile = PathBuf::from(manifest_dir).join("Cargo.lock");
+        if lockfile.exists() {
+            fs::copy(lockfile, dir.join("Cargo.lock")).unwrap();
+        }
+
+        let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned()))
+            .args(["build", "--quiet", "--manifest-path"])
+            .arg(dir.join("Cargo.toml"))
+            .env("CARGO_TARGET_DIR", dir.join("target"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let exe = dir
+            .join("target")
+            .join("debug")
+            .join(format!("biner_process_fixture{}", env::consts::EXE_SUFFIX));
+        (dir, exe)
+    }
+
+    #[test]
+    fn process_plugin_lifecycle() {
+        let (_dir, fixture) = build_fixture();
+        let mut plugins: PluginRegistry = PluginRegistry::new();
+        for id in ["shout", "refuse"] {
+            plugins
+                .register(SimplePluginManifest::new(id, ""), None)
+                .unwrap();
+        }
+
+        let plugin = ProcessPlugin::new("shout", Command::new(&fixture))
+            .remote::<ShoutSlot>(None)
+            .remote::<CrashSlot>(None);
+        plugins.load_process("shout", plugin, &mut ()).unwrap();
+        plugins.enable("shout", &mut ()).unwrap();
+        let shout = plugins.hooks().get_first::<ShoutSlot>("shout").unwrap();
+        assert_eq!(shout("hello").unwrap(), "HELLO");
+
+        // A crashed plugin process marks the plugin failed once reported to the registry
+        let crash = plugins.hooks().get_first::<CrashSlot>("shout").unwrap();
+        assert!(matches!(
+            crash("hello"),
+            Err(ProcessError::Crashed { status: Some(status) }) if status.code() == Some(3)
+        ));
+        assert!(matches!(shout("hello"), Err(ProcessError::Crashed { .. })));
+        let plugin = plugins.get_loaded::<ProcessPlugin>("shout").unwrap();
+        assert!(plugin.is_failed() && !plugin.is_running());
+        assert_eq!(plugins.handle_plugin_failures(&mut ()), ["shout"]);
+        assert!(plugins.handle_plugin_failures(&mut ()).is_empty());
+        assert!(!plugins.is_enabled("shout"));
+        assert!(!plugins.hooks().exists::<ShoutSlot>("shout"));
+        assert!(matches!(
+            plugins.failure("shout"),
+            Some(LoadPluginError::Failed {
+                plugin: "shout",
+                ..
+            })
+        ));
+
+        // Errors while loading fail loading the plugin
+        let mut command = Command::new(&fixture);
+        command.env("FIXTURE_FAIL_LOAD", "1");
+        let plugin = ProcessPlugin::new("refuse", command);
+        assert!(matches!(
+            plugins.load_process("refuse", plugin, &mut ()),
+            Err(LoadPluginError::Failed {
+                plugin: "refuse",
+                ..
+            })
+        ));
+        assert!(!plugins.is_loaded("refuse"));
+
+        plugins.unload("shout", &mut ());
+        assert!(!plugins.is_loaded("shout"));
+    }
+
+    #[test]
+    fn process_plugin_failures() {
+        let (_dir, fixture) = build_fixture();
+        let mut plugins: PluginRegistry = PluginRegistry::new();
+        for id in ["hang", "garbage", "grumpy"] {
+            plugins
+                .register(SimplePluginManifest::new(id, ""), None)
+                .unwrap();
+        }
+
+        // A plugin process that does not respond in time is terminated
+        let plugin = ProcessPlugin::new("hang", Command::new(&fixture))
+            .timeout(Duration::from_millis(200))
+            .remote::<HangSlot>(None);
+        plugins.load_process("hang", plugin, &mut ()).unwrap();
+        let hang = plugins.hooks().get_first::<HangSlot>("hang").unwrap();
+        assert!(matches!(hang("hello"), Err(ProcessError::Timeout(_))));
+        assert!(matches!(
+            hang("hello"),
+            Err(ProcessError::Crashed { status: None })
+        ));
+        assert!(
+            plugins
+                .get_loaded::<ProcessPlugin>("hang")
+                .unwrap()
+                .is_failed()
+        );
+
+        // So is a plugin process sending invalid messages
+        let plugin = ProcessPlugin::new("garbage", Command::new(&fixture))
+            .remote::<GarbageSlot>(None)
+            .remote::<ShoutSlot>(None);
+        plugins.load_process("garbage", plugin, &mut ()).unwrap();
+        let garbage = plugins.hooks().get_first::<GarbageSlot>("garbage").unwrap();
+        let shout = plugins.hooks().get_first::<ShoutSlot>("garbage").unwrap();
+        assert!(matches!(garbage("hello"), Err(ProcessError::Protocol(_))));
+        assert!(matches!(shout("hello"), Err(ProcessError::Crashed { .. })));
+        assert_eq!(plugins.handle_plugin_failures(&mut ()), ["garbage", "hang"]);
+
+        // Errors in other lifecycle steps fail the plugin
+        let mut command = Command::new(&fixture);
+        command.env("FIXTURE_FAIL_ENABLE", "1");
+        let plugin = ProcessPlugin::new("grumpy", command).remote::<ShoutSlot>(None);
+        plugins.load_process("grumpy", plugin, &mut ()).unwrap();
+        assert!(matches!(
+            plugins.enable("grumpy", &mut ()),
+            Err(LoadPluginError::Failed {
+                plugin: "grumpy",
+                ..
+            })
+        ));
+        assert!(!plugins.is_enabled("grumpy"));
+        assert!(!plugins.hooks().exists::<ShoutSlot>("grumpy"));
+    }
+}
@@ -0,0 +1,210 @@
+use super::{LoadPluginError, PluginManifest, PluginRegistry};
+use std::collections::HashSet;
+
+/// The set of plugins a user chose to enable, exported with
+/// [`PluginRegistry::enablement_profile`] so it can be persisted and restored on the next start
+/// with [`PluginRegistry::apply_profile`]. With the `serde` feature, the profile can be serialized
+/// whenever the plugin ids can.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnablementProfile<Id> {
+    /// Plugin ids of the explicitly enabled plugins, ordered by plugin id.
+    pub enabled: Vec<Id>,
+    /// Plugin ids of the plugins that were only enabled as dependencies of explicitly enabled
+    /// plugins, ordered by plugin id. These are informational only and are not enabled by
+    /// [`PluginRegistry::apply_profile`] unless still required by an explicitly enabled plugin.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub dependencies: Vec<Id>,
+}
+
+impl<Id> Default for EnablementProfile<Id> {
+    fn default() -> Self {
+        Self {
+            enabled: Vec::new(),
+            dependencies: Vec::new(),
+        }
+    }
+}
+
+/// The changes made by [`PluginRegistry::apply_profile`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileReport<Id> {
+    /// Plugin ids of the plugins that were enabled, ordered by plugin id.
+    pub enabled: Vec<Id>,
+    /// Plugin ids of the plugins that were disabled, in the order they were disabled.
+    pub disabled: Vec<Id>,
+    /// Plugin ids listed as enabled by the profile that are not registered.
+    pub unknown: Vec<Id>,
+    /// Errors from plugins of the profile that could not be enabled.
+    pub failed: Vec<LoadPluginError<Id>>,
+}
+
+impl<Id> Default for ProfileReport<Id> {
+    fn default() -> Self {
+        Self {
+            enabled: Vec::new(),
+            disabled: Vec::new(),
+            unknown: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+}
+
+impl<Manifest, Context> PluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+{
+    /// Export the enablement profile of the registry: the plugins that are explicitly enabled, and
+    /// separately the plugins that are only enabled as their dependencies.
+    #[must_use]
+    pub fn enablement_profile(&self) -> EnablementProfile<Manifest::PluginId> {
+        let mut profile = EnablementProfile::default();
+        for (&id, state) in &self.plugins {
+            if state.enabled {
+                if state.explicit_enable {
+                    profile.enabled.push(id);
+                } else {
+                    profile.dependencies.push(id);
+                }
+            }
+        }
+        profile.enabled.sort_unstable();
+        profile.dependencies.sort_unstable();
+        profile
+    }
+}
+
+impl<Manifest, Context> PluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+    Manifest::PluginId: 'static,
+    Context: 'static,
+{
+    /// Apply an enablement profile, reconciling the enabled plugins with the minimal number of
+    /// [`PluginRegistry::enable`] and [`PluginRegistry::disable`] calls. Plugins that are neither
+    /// enabled by the profile nor dependencies of those plugins are disabled, and afterwards
+    /// exactly the plugins enabled by the profile are explicitly enabled.
+    ///
+    /// Plugin ids of the profile that are not registered, such as plugins that have since been
+    /// removed, are reported in [`ProfileReport::unknown`] instead of failing the whole profile.
+    /// Likewise, plugins that fail to enable are reported in [`ProfileReport::failed`] while the
+    /// rest of the profile is still applied.
+    pub fn apply_profile(
+        &mut self,
+        profile: &EnablementProfile<Manifest::PluginId>,
+        context: &mut Context,
+    ) -> ProfileReport<Manifest::PluginId> {
+        let mut report = ProfileReport::default();
+        let before = self.enabled_plugin_ids().collect::<HashSet<_>>();
+
+        let mut explicit = Vec::new();
+        for &id in &profile.enabled {
+            if !self.exists(id) {
+                report.unknown.push(id);
+            } else if !explicit.contains(&id) {
+                explicit.push(id);
+            }
+        }
+
+        // Everything the profile requires stays enabled, everything else is disabled
+        let mut required = HashSet::new();
+        let mut pending = explicit.clone();
+        while let Some(id) = pending.pop() {
+            if required.insert(id) && self.dependency_graph.contains_node(id) {
+                pending.extend(self.dependency_graph.edges(id).map(|(_, d, _)| d));
+            }
+        }
+        let mut unrequired = before
+            .iter()
+            .copied()
+            .filter(|id| !required.contains(id))
+            .collect::<Vec<_>>();
+        unrequired.sort_unstable();
+        for id in unrequired {
+            report.disabled.extend(self.disable(id, context));
+        }
+
+        for &id in &explicit {
+            if let Err(error) = self.enable_plugin(id, None, context) {
+                report.failed.push(error);
+            }
+        }
+        for (id, state) in &mut self.plugins {
+            state.explicit_enable = state.enabled && explicit.contains(id);
+            state.explicit_load |= state.explicit_enable;
+        }
+
+        report.enabled = self
+            .enabled_plugin_ids()
+            .filter(|id| !before.contains(id))
+            .collect();
+        report.enabled.sort_unstable();
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{EnablementProfile, Plugin, PluginRegistry, SimplePluginManifest};
+
+    struct TestPlugin;
+
+    impl Plugin for TestPlugin {}
+
+    fn registry() -> PluginRegistry {
+        let mut plugins = PluginRegistry::new();
+        for (id, dependencies) in [
+            ("base", vec![]),
+            ("editor", vec!["base"]),
+            ("theme", vec![]),
+            ("spell", vec!["editor"]),
+        ] {
+            plugins
+                .register(
+                    SimplePluginManifest::with_dependencies(id, "", dependencies),
+                    Some(|| Box::new(TestPlugin)),
+                )
+                .unwrap();
+        }
+        plugins
+    }
+
+    #[test]
+    fn apply_profile() {
+        let mut plugins = registry();
+        plugins.enable("spell", &mut ()).unwrap();
+        plugins.enable("theme", &mut ()).unwrap();
+        let profile = plugins.enablement_profile();
+        assert_eq!(
+            profile,
+            EnablementProfile {
+                enabled: vec!["spell", "theme"],
+                dependencies: vec!["base", "editor"],
+            }
+        );
+
+        // Only explicitly enabled plugins are restored, along with their dependencies
+        let mut restored = registry();
+        restored.enable("base", &mut ()).unwrap();
+        let report = restored.apply_profile(&profile, &mut ());
+        assert_eq!(report.enabled, vec!["editor", "spell", "theme"]);
+        assert!(report.disabled.is_empty());
+        assert_eq!(restored.enablement_profile(), profile);
+
+        // Reconciling disables only what is no longer required and reports unknown plugins
+        let report = restored.apply_profile(
+            &EnablementProfile {
+                enabled: vec!["editor", "missing"],
+                dependencies: Vec::new(),
+            },
+            &mut (),
+        );
+        assert!(report.enabled.is_empty());
+        assert_eq!(report.disabled, vec!["spell", "theme"]);
+        assert_eq!(report.unknown, vec!["missing"]);
+        assert!(report.failed.is_empty());
+        assert!(restored.is_explicitly_enabled("editor"));
+        assert!(!restored.is_explicitly_enabled("base"));
+        assert!(restored.is_enabled("base"));
+    }
+}
@@ -0,0 +1,168 @@
+use super::{PluginManifest, PluginRegistry};
+use std::any::{Any, TypeId, type_name};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+
+/// Per-plugin storage of resources such as caches or handles, keyed by type, so hosts do not need
+/// to keep side tables keyed by plugin id. Each loaded plugin has its own resources, reachable with
+/// [`PluginRegistry::resources`] and [`PluginRegistry::resources_mut`], which are dropped when the
+/// plugin is unloaded, right after its hooks are removed. The plugin itself reaches them while
+/// loading with [`HookRegistry::plugin_resources`][crate::HookRegistry::plugin_resources].
+#[derive(Default)]
+pub struct PluginResources {
+    resources: HashMap<TypeId, (&'static str, Box<dyn Any + Send + Sync>)>,
+}
+
+impl PluginResources {
+    /// Insert a resource, returning the previous resource of the same type if any.
+    pub fn insert<T>(&mut self, resource: T) -> Option<T>
+    where
+        T: Any + Send + Sync,
+    {
+        self.resources
+            .insert(TypeId::of::<T>(), (type_name::<T>(), Box::new(resource)))
+            .map(|(_, previous)| *previous.downcast().unwrap())
+    }
+
+    /// Get a reference to the resource of type `T`, if any.
+    #[must_use]
+    pub fn get<T>(&self) -> Option<&T>
+    where
+        T: Any + Send + Sync,
+    {
+        self.resources.get(&TypeId::of::<T>())?.1.downcast_ref()
+    }
+
+    /// Get a mutable reference to the resource of type `T`, if any.
+    #[must_use]
+    pub fn get_mut<T>(&mut self) -> Option<&mut T>
+    where
+        T: Any + Send + Sync,
+    {
+        self.resources.get_mut(&TypeId::of::<T>())?.1.downcast_mut()
+    }
+
+    /// Get a mutable reference to the resource of type `T`, inserting the result of `f` if there is
+    /// none.
+    pub fn get_or_insert_with<T>(&mut self, f: impl FnOnce() -> T) -> &mut T
+    where
+        T: Any + Send + Sync,
+    {
+        self.resources
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| (type_name::<T>(), Box::new(f())))
+            .1
+            .downcast_mut()
+            .unwrap()
+    }
+
+    /// Remove and return the resource of type `T`, if any.
+    pub fn remove<T>(&mut self) -> Option<T>
+    where
+        T: Any + Send + Sync,
+    {
+        self.resources
+            .remove(&TypeId::of::<T>())
+            .map(|(_, resource)| *resource.downcast().unwrap())
+    }
+
+    /// Determine whether there is a resource of type `T`.
+    #[must_use]
+    pub fn contains<T>(&self) -> bool
+    where
+        T: Any + Send + Sync,
+    {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Get the number of resources.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    /// Determine whether there are no resources.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    /// Drop all resources.
+    pub fn clear(&mut self) {
+        self.resources.clear();
+    }
+}
+
+impl Debug for PluginResources {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_set()
+            .entries(self.resources.values().map(|(name, _)| name))
+            .finish()
+    }
+}
+
+impl<Manifest, Context> PluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+{
+    /// Get a reference to the resources of the plugin with the given id if it is currently loaded.
+    #[must_use]
+    pub fn resources(&self, id: Manifest::PluginId) -> Option<&PluginResources> {
+        self.plugins
+            .get(&id)
+            .filter(|state| state.plugin.is_some())
+            .map(|state| &state.resources)
+    }
+
+    /// Get a mutable reference to the resources of the plugin with the given id if it is currently
+    /// loaded.
+    #[must_use]
+    pub fn resources_mut(&mut self, id: Manifest::PluginId) -> Option<&mut PluginResources> {
+        self.plugins
+            .get_mut(&id)
+            .filter(|state| state.plugin.is_some())
+            .map(|state| &mut state.resources)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{HookRegistry, Plugin, PluginRegistry, SimplePluginManifest};
+    use std::sync::Arc;
+
+    struct TestPlugin;
+
+    impl Plugin for TestPlugin {
+        fn load(&mut self, hooks: &mut HookRegistry, _context: &mut ()) {
+            let resources = hooks.plugin_resources().unwrap();
+            resources.insert(String::from("warm"));
+        }
+    }
+
+    #[test]
+    fn plugin_resources() {
+        let mut plugins = PluginRegistry::new();
+        plugins
+            .register(
+                SimplePluginManifest::new("cache", ""),
+                Some(|| Box::new(TestPlugin)),
+            )
+            .unwrap();
+        assert!(plugins.resources_mut("cache").is_none());
+
+        plugins.load("cache", &mut ()).unwrap();
+        assert!(plugins.hooks_mut().plugin_resources().is_none());
+        let handle = Arc::new(());
+        let resources = plugins.resources_mut("cache").unwrap();
+        assert_eq!(resources.get::<String>().unwrap(), "warm");
+        assert_eq!(resources.insert(Arc::clone(&handle)), None);
+        resources.get_or_insert_with(Vec::<u32>::new).push(1);
+        assert_eq!(resources.get::<Vec<u32>>(), Some(&vec![1]));
+        assert_eq!(resources.len(), 3);
+        assert_eq!(Arc::strong_count(&handle), 2);
+
+        let _ = plugins.unload("cache", &mut ());
+        assert!(plugins.resources("cache").is_none());
+        assert_eq!(Arc::strong_count(&handle), 1, "resources dropped on unload");
+    }
+}
@@ -0,0 +1,581 @@
+use super::{PluginConstructor, RegisterPluginError};
+use crate::{
+    AsyncPlugin, HookRegistry, HookSlot, ManifestFile, ManifestFormat, Plugin, PluginFuture,
+    PluginRegistry,
+};
+use rhai::{AST, CallFnOptions, Dynamic, Engine, FuncArgs, Scope};
+use std::any::Any;
+use std::fmt::{Debug, Display, Formatter};
+use std::fs;
+use std::future;
+use std::hash::Hash;
+use std::io;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use thiserror::Error;
+
+/// Prefix of the lines at the start of a script holding the plugin's manifest.
+const MANIFEST_PREFIX: &str = "//!";
+
+/// An error occurred while compiling or running a plugin script.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Error)]
+pub enum ScriptError {
+    /// The script could not be compiled.
+    #[error("failed to compile plugin script: {0}")]
+    Parse(String),
+    /// Running the script or one of its functions failed.
+    #[error("plugin script function `{function}` failed: {message}")]
+    Runtime {
+        /// Name of the script function, or `main` for the top level of the script.
+        function: String,
+        /// Error reported by the script engine.
+        message: String,
+    },
+    /// A hook provided by the script was rejected by the hook registry.
+    #[error("failed to register hook of plugin script: {0}")]
+    Hook(String),
+}
+
+/// An error occurred while registering a plugin script with [`PluginRegistry::register_script`].
+#[derive(Debug, Error)]
+pub enum LoadScriptError<Id> {
+    /// The script file could not be read.
+    #[error("failed to read plugin script `{}`", .path.display())]
+    Io {
+        /// Path of the script file.
+        path: PathBuf,
+        /// The error reported while reading the file.
+        source: io::Error,
+    },
+    /// The script does not start with a manifest header, or the header is not a valid manifest.
+    #[error("invalid manifest header in plugin script `{}`: {reason}", .path.display())]
+    Manifest {
+        /// Path of the script file.
+        path: PathBuf,
+        /// Description of the problem with the header.
+        reason: String,
+    },
+    /// The script could not be compiled.
+    #[error("invalid plugin script `{}`", .path.display())]
+    Script {
+        /// Path of the script file.
+        path: PathBuf,
+        /// The error reported while compiling the script.
+        source: ScriptError,
+    },
+    /// The plugin of the script could not be registered.
+    #[error(transparent)]
+    Register(#[from] RegisterPluginError<Id>),
+}
+
+type ScriptRegistration<Id> =
+    Arc<dyn Fn(&mut HookRegistry<Id>, Id, ScriptHook) -> Result<(), String> + Send + Sync>;
+
+struct ScriptHookSlot<Id> {
+    function: &'static str,
+    register: ScriptRegistration<Id>,
+}
+
+impl<Id> Clone for ScriptHookSlot<Id> {
+    fn clone(&self) -> Self {
+        Self {
+            function: self.function,
+            register: self.register.clone(),
+        }
+    }
+}
+
+/// A hook slot whose hooks can be provided by functions of plugin scripts. Only slots implementing
+/// this trait can be exposed to scripts with [`ScriptEngine::slot`].
+pub trait ScriptSlot: HookSlot {
+    /// Create a hook for the slot that forwards its calls to the script function of `hook`.
+    fn script_hook(hook: ScriptHook) -> Box<Self::TraitObject>;
+}
+
+/// The Rhai script engine used to compile and run plugin scripts, along with the hook slots that
+/// scripts can provide hooks for.
+pub struct ScriptEngine<Id = &'static str> {
+    engine: Arc<Engine>,
+    slots: Vec<ScriptHookSlot<Id>>,
+}
+
+impl<Id> ScriptEngine<Id>
+where
+    Id: Copy + Ord + Hash + Send + Sync + 'static,
+{
+    /// Create a script engine using a default Rhai engine.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_engine(Engine::new())
+    }
+
+    /// Create a script engine using the given Rhai engine, such as one with additional functions
+    /// and types of the host registered.
+    #[must_use]
+    pub fn with_engine(engine: Engine) -> Self {
+        Self {
+            engine: Arc::new(engine),
+            slots: Vec::new(),
+        }
+    }
+
+    /// Expose the hook slot `Slot` to plugin scripts. When a script plugin loads and the script
+    /// defines a function named `function`, a hook created with [`ScriptSlot::script_hook`] is
+    /// registered for the slot.
+    #[must_use]
+    pub fn slot<Slot>(mut self, function: &'static str) -> Self
+    where
+        Id: Debug + Display,
+        Slot: ScriptSlot,
+    {
+        self.slots.push(ScriptHookSlot {
+            function,
+            register: Arc::new(|hooks, id, hook| {
+                hooks
+                    .register::<Slot>(Slot::script_hook(hook), id, None)
+                    .map_err(|rejected| rejected.error.to_string())
+            }),
+        });
+        self
+    }
+}
+
+impl<Id> Default for ScriptEngine<Id>
+where
+    Id: Copy + Ord + Hash + Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Id> Debug for ScriptEngine<Id> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScriptEngine")
+            .field(
+                "slots",
+                &self.slots.iter().map(|s| s.function).collect::<Vec<_>>(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+struct Script {
+    engine: Arc<Engine>,
+    ast: AST,
+    scope: Mutex<Scope<'static>>,
+}
+
+impl Script {
+    fn has_function(&self, function: &str, params: usize) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|f| f.name == function && f.params.len() == params)
+    }
+
+    fn call<R>(&self, function: &str, args: impl FuncArgs) -> Result<R, ScriptError>
+    where
+        R: Any + Clone + Send + Sync,
+    {
+        let options = CallFnOptions::new().eval_ast(false);
+        self.engine
+            .call_fn_with_options(
+                options,
+                &mut self.scope.lock().unwrap_or_else(PoisonError::into_inner),
+                &self.ast,
+                function,
+                args,
+            )
+            .map_err(|error| ScriptError::Runtime {
+                function: function.to_owned(),
+                message: error.to_string(),
+            })
+    }
+
+    fn call_lifecycle(&self, function: &str) -> Result<(), ScriptError> {
+        if self.has_function(function, 0) {
+            self.call::<Dynamic>(function, ()).map(drop)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A handle for calling the script function providing a hook, passed to
+/// [`ScriptSlot::script_hook`]. Cloning the handle is cheap, as clones share the script.
+#[derive(Clone)]
+pub struct ScriptHook {
+    script: Arc<Script>,
+    function: &'static str,
+}
+
+impl ScriptHook {
+    /// Get the name of the script function.
+    #[must_use]
+    pub fn function(&self) -> &'static str {
+        self.function
+    }
+
+    /// Call the script function with the given arguments. Calls into the same script are
+    /// serialized.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ScriptError::Runtime`] if the function fails or does not return a value of type
+    /// `R`.
+    pub fn call<R>(&self, args: impl FuncArgs) -> Result<R, ScriptError>
+    where
+        R: Any + Clone + Send + Sync,
+    {
+        self.script.call(self.function, args)
+    }
+}
+
+impl Debug for ScriptHook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScriptHook")
+            .field("function", &self.function)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A [`Plugin`] written as a Rhai script, registered with [`PluginRegistry::register_script`].
+///
+/// Loading the plugin runs the top level of the script, and the lifecycle of the plugin calls the
+/// script functions `load`, `enable`, `disable` and `unload`, which take no parameters, if the
+/// script defines them. Script functions named after the slots exposed by the [`ScriptEngine`]
+/// provide hooks for those slots.
+///
+/// A script error in a lifecycle step fails that step with [`LoadPluginError::Failed`], which
+/// marks the plugin failed and removes its hooks, through both the synchronous lifecycle methods of
+/// the registry and the async ones such as [`PluginRegistry::load_async`], as script plugins are
+/// also [`AsyncPlugin`]s.
+///
+/// [`LoadPluginError::Failed`]: crate::LoadPluginError::Failed
+pub struct ScriptPlugin<Id = &'static str, Context = ()> {
+    id: Id,
+    script: Arc<Script>,
+    slots: Arc<[ScriptHookSlot<Id>]>,
+    failure: Option<ScriptError>,
+    context: PhantomData<fn(&mut Context)>,
+}
+
+impl<Id, Context> ScriptPlugin<Id, Context>
+where
+    Id: Copy + Ord + Hash + Send + Sync + 'static,
+{
+    /// Get the plugin id of the script's plugin.
+    #[must_use]
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    fn try_load(&mut self, hooks: &mut HookRegistry<Id>) -> Result<(), ScriptError> {
+        {
+            let mut scope = self
+                .script
+                .scope
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            scope.clear();
+            self.script
+                .engine
+                .run_ast_with_scope(&mut scope, &self.script.ast)
+                .map_err(|error| ScriptError::Runtime {
+                    function: "main".to_owned(),
+                    message: error.to_string(),
+                })?;
+        }
+        self.script.call_lifecycle("load")?;
+        for slot in self.slots.iter() {
+            if self
+                .script
+                .ast
+                .iter_functions()
+                .any(|f| f.name == slot.function)
+            {
+                let hook = ScriptHook {
+                    script: self.script.clone(),
+                    function: slot.function,
+                };
+                (slot.register)(hooks, self.id, hook).map_err(ScriptError::Hook)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep the error of a lifecycle step for [`Plugin::take_failure`].
+    fn record(&mut self, result: Result<(), ScriptError>) {
+        if let Err(error) = result {
+            self.failure = Some(error);
+        }
+    }
+}
+
+impl<Id, Context> Plugin<Id, Context> for ScriptPlugin<Id, Context>
+where
+    Id: Copy + Ord + Hash + Send + Sync + 'static,
+    Context: 'static,
+{
+    fn load(&mut self, hooks: &mut HookRegistry<Id>, _context: &mut Context) {
+        let result = self.try_load(hooks);
+        self.record(result);
+    }
+
+    fn unload(&mut self, _context: &mut Context) {
+        let result = self.script.call_lifecycle("unload");
+        self.record(result);
+    }
+
+    fn enable(&mut self, _context: &mut Context) {
+        let result = self.script.call_lifecycle("enable");
+        self.record(result);
+    }
+
+    fn disable(&mut self, _context: &mut Context) {
+        let result = self.script.call_lifecycle("disable");
+        self.record(result);
+    }
+
+    fn take_failure(&mut self) -> Option<String> {
+        self.failure.take().map(|error| error.to_string())
+    }
+}
+
+impl<Id, Context> AsyncPlugin<Id, Context> for ScriptPlugin<Id, Context>
+where
+    Id: Copy + Ord + Hash + Send + Sync + 'static,
+    Context: 'static,
+{
+    fn load_async<'a>(
+        &'a mut self,
+        hooks: &'a mut HookRegistry<Id>,
+        _context: &'a Context,
+    ) -> PluginFuture<'a, Result<(), String>> {
+        let result = self.try_load(hooks).map_err(|error| error.to_string());
+        Box::pin(future::ready(result))
+    }
+
+    fn unload_async<'a>(&'a mut self, _context: &'a Context) -> PluginFuture<'a> {
+        let result = self.script.call_lifecycle("unload");
+        self.record(result);
+        Box::pin(future::ready(()))
+    }
+
+    fn enable_async<'a>(
+        &'a mut self,
+        _context: &'a Context,
+    ) -> PluginFuture<'a, Result<(), String>> {
+        let result = self
+            .script
+            .call_lifecycle("enable")
+            .map_err(|error| error.to_string());
+        Box::pin(future::ready(result))
+    }
+
+    fn disable_async<'a>(&'a mut self, _context: &'a Context) -> PluginFuture<'a> {
+        let result = self.script.call_lifecycle("disable");
+        self.record(result);
+        Box::pin(future::ready(()))
+    }
+}
+
+impl<Id, Context> Debug for ScriptPlugin<Id, Context>
+where
+    Id: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScriptPlugin")
+            .field("id", &self.id)
+            .field("failure", &self.failure)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Extract the manifest header from the leading `//!` lines of a script.
+fn manifest_header(source: &str) -> Option<String> {
+    let mut header = String::new();
+    for line in source.lines() {
+        let Some(line) = line.trim_start().strip_prefix(MANIFEST_PREFIX) else {
+            break;
+        };
+        header.push_str(line.strip_prefix(' ').unwrap_or(line));
+        header.push('\n');
+    }
+    (!header.is_empty()).then_some(header)
+}
+
+impl<Manifest, Context> PluginRegistry<Manifest, Context>
+where
+    Manifest: ManifestFile,
+    Manifest::PluginId: Send + Sync + 'static,
+    Context: 'static,
+{
+    /// Compile the Rhai script at `path` and register it as a plugin, returning the plugin's id.
+    /// The plugin is registered but not loaded; every time it is loaded, a new [`ScriptPlugin`]
+    /// is created from the compiled script.
+    ///
+    /// The script must start with a manifest header of lines starting with `//!`, which together
+    /// hold the plugin's manifest in TOML format as read by [`ManifestFile::parse`]:
+    ///
+    /// ```rhai
+    /// //! id = "greeter"
+    /// //! description = "Greets the user"
+    ///
+    /// fn greet(name) { `Hello, ${name}!` }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LoadScriptError::Io`] if the script could not be read, [`LoadScriptError::Manifest`]
+    /// if its manifest header is missing or invalid and [`LoadScriptError::Script`] if it could not
+    /// be compiled.
+    ///
+    /// Returns [`LoadScriptError::Register`] if the plugin could not be registered, the same as
+    /// [`PluginRegistry::register`].
+    pub fn register_script(
+        &mut self,
+        engine: &ScriptEngine<Manifest::PluginId>,
+        path: impl AsRef<Path>,
+    ) -> Result<Manifest::PluginId, LoadScriptError<Manifest::PluginId>> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|source| LoadScriptError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        let manifest = manifest_header(&source)
+            .ok_or_else(|| format!("missing `{MANIFEST_PREFIX}` manifest header"))
+            .and_then(|header| Manifest::parse(&header, ManifestFormat::Toml))
+            .map_err(|reason| LoadScriptError::Manifest {
+                path: path.to_owned(),
+                reason,
+            })?;
+        let ast = engine
+            .engine
+            .compile(&source)
+            .map_err(|error| LoadScriptError::Script {
+                path: path.to_owned(),
+                source: ScriptError::Parse(error.to_string()),
+            })?;
+
+        let id = manifest.id();
+        let rhai = engine.engine.clone();
+        let slots = Arc::<[_]>::from(engine.slots.clone());
+        let ctor = move || -> Box<dyn AsyncPlugin<Manifest::PluginId, Context>> {
+            Box::new(ScriptPlugin {
+                id,
+                script: Arc::new(Script {
+                    engine: rhai.clone(),
+                    ast: ast.clone(),
+                    scope: Mutex::new(Scope::new()),
+                }),
+                slots: slots.clone(),
+                failure: None,
+                context: PhantomData,
+            })
+        };
+        Ok(self.register_state(
+            manifest,
+            Some(PluginConstructor::SharedAsync(Arc::new(ctor))),
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::TempDir;
+    use crate::{
+        LoadPluginError, PluginRegistry, ScriptEngine, ScriptError, ScriptHook, ScriptSlot,
+        hook_slot,
+    };
+    use std::fs;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    type ShoutHook = dyn Fn(&str) -> Result<String, ScriptError> + Send + Sync;
+
+    hook_slot!(ShoutSlot: ShoutHook);
+
+    impl ScriptSlot for ShoutSlot {
+        fn script_hook(hook: ScriptHook) -> Box<ShoutHook> {
+            Box::new(move |text| hook.call((text.to_owned(),)))
+        }
+    }
+
+    /// Poll a future that is expected to complete without waiting.
+    fn now_or_never<F: Future>(future: F) -> F::Output {
+        match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future did not complete"),
+        }
+    }
+
+    const SHOUTER: &str = r#"//! id = "shouter"
+//! description = "Shouts"
+
+fn shout(text) {
+    if text == "" { throw "nothing to shout"; }
+    text.to_upper()
+}
+"#;
+
+    const BROKEN: &str = r#"//! id = "broken"
+
+fn load() { throw "refusing to load"; }
+"#;
+
+    #[test]
+    fn script_plugins() {
+        let dir = TempDir::new("script");
+        fs::write(dir.join("shouter.rhai"), SHOUTER).unwrap();
+        fs::write(dir.join("broken.rhai"), BROKEN).unwrap();
+        fs::write(dir.join("headless.rhai"), "fn shout(text) { text }").unwrap();
+
+        let engine = ScriptEngine::new().slot::<ShoutSlot>("shout");
+        let mut plugins: PluginRegistry = PluginRegistry::new();
+        let id = plugins
+            .register_script(&engine, dir.join("shouter.rhai"))
+            .unwrap();
+        assert_eq!(id, "shouter");
+        assert_eq!(
+            plugins.get_manifest("shouter").unwrap().description(),
+            "Shouts"
+        );
+        plugins.enable("shouter", &mut ()).unwrap();
+        let shout = plugins.hooks().get_first::<ShoutSlot>("shouter").unwrap();
+        assert_eq!(shout("hello"), Ok("HELLO".to_owned()));
+        assert!(matches!(shout(""), Err(ScriptError::Runtime { .. })));
+
+        // Script errors fail loading through the async lifecycle
+        plugins
+            .register_script(&engine, dir.join("broken.rhai"))
+            .unwrap();
+        let result = now_or_never(plugins.load_async("broken", &mut ()));
+        assert!(matches!(
+            result,
+            Err(LoadPluginError::Failed {
+                plugin: "broken",
+                ..
+            })
+        ));
+        assert!(plugins.retry("broken").is_some());
+
+        // ... as well as through the synchronous lifecycle
+        assert!(matches!(
+            plugins.load("broken", &mut ()),
+            Err(LoadPluginError::Failed {
+                plugin: "broken",
+                ..
+            })
+        ));
+        assert!(!plugins.is_loaded("broken"));
+        assert!(plugins.failure("broken").is_some());
+
+        assert!(
+            plugins
+                .register_script(&engine, dir.join("headless.rhai"))
+                .is_err()
+        );
+    }
+}
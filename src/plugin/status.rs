@@ -0,0 +1,206 @@
+use super::{LoadPluginError, PluginManifest, PluginRegistry, PluginState};
+
+/// The lifecycle status of a registered plugin, as returned by [`PluginRegistry::status`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PluginStatus<Id> {
+    /// The plugin is registered but not loaded.
+    Registered,
+    /// The plugin is being loaded. A plugin stays loading if loading it was interrupted by a panic
+    /// unwinding through a synchronous lifecycle method of the registry, until it is loaded again.
+    /// Dropping the future of [`PluginRegistry::load_async`] returns it to the registered status.
+    Loading,
+    /// The plugin is loaded but not enabled.
+    Loaded,
+    /// The plugin is enabled.
+    Enabled,
+    /// The plugin is being disabled. Like [`PluginStatus::Loading`], a plugin stays disabling if
+    /// disabling it was interrupted.
+    Disabling,
+    /// The plugin failed in a lifecycle method, and cannot be loaded or enabled until it is retried
+    /// with [`PluginRegistry::retry`].
+    Failed {
+        /// The failure of the plugin.
+        error: LoadPluginError<Id>,
+    },
+    /// The plugin was quarantined with [`PluginRegistry::quarantine`], and cannot be loaded or
+    /// enabled until it is retried with [`PluginRegistry::retry`].
+    Quarantined,
+}
+
+/// A lifecycle method of the registry in progress for a plugin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Transition {
+    Loading,
+    Disabling,
+}
+
+impl<Manifest, Context> PluginState<Manifest, Context>
+where
+    Manifest: PluginManifest,
+{
+    fn status(&self) -> PluginStatus<Manifest::PluginId> {
+        if self.quarantined {
+            PluginStatus::Quarantined
+        } else if let Some(error) = &self.failure {
+            PluginStatus::Failed {
+                error: error.clone(),
+            }
+        } else if let Some(transition) = self.transition {
+            match transition {
+                Transition::Loading => PluginStatus::Loading,
+                Transition::Disabling => PluginStatus::Disabling,
+            }
+        } else if self.enabled {
+            PluginStatus::Enabled
+        } else if self.plugin.is_some() {
+            PluginStatus::Loaded
+        } else {
+            PluginStatus::Registered
+        }
+    }
+
+    /// Fail if the plugin is quarantined or failed, as it cannot be loaded or enabled.
+    pub(super) fn check_usable(
+        &self,
+        id: Manifest::PluginId,
+    ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
+        if self.quarantined {
+            Err(LoadPluginError::Quarantined(id))
+        } else if self.failure.is_some() {
+            Err(LoadPluginError::PreviouslyFailed(id))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<Manifest, Context> PluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+{
+    /// Get the status of the plugin with the given plugin id, or [`None`] if it is not registered.
+    #[must_use]
+    pub fn status(&self, id: Manifest::PluginId) -> Option<PluginStatus<Manifest::PluginId>> {
+        self.plugins.get(&id).map(PluginState::status)
+    }
+
+    /// Get an iterator over the ids and statuses of all registered plugins.
+    pub fn statuses(
+        &self,
+    ) -> impl Iterator<Item = (Manifest::PluginId, PluginStatus<Manifest::PluginId>)> {
+        self.plugins.iter().map(|(&id, state)| (id, state.status()))
+    }
+
+    /// Get the failure of the plugin with the given plugin id, if it failed.
+    #[must_use]
+    pub fn failure(&self, id: Manifest::PluginId) -> Option<&LoadPluginError<Manifest::PluginId>> {
+        self.plugins.get(&id)?.failure.as_ref()
+    }
+
+    /// Get an iterator over the ids of all failed plugins.
+    pub fn failed_plugin_ids(&self) -> impl Iterator<Item = Manifest::PluginId> {
+        self.plugins
+            .iter()
+            .filter(|(_, state)| state.failure.is_some())
+            .map(|(&id, _)| id)
+    }
+
+    /// Clear the failure and quarantine of the plugin with the given plugin id so that it can be
+    /// loaded and enabled again, returning the cleared failure if any.
+    pub fn retry(&mut self, id: Manifest::PluginId) -> Option<LoadPluginError<Manifest::PluginId>> {
+        let state = self.plugins.get_mut(&id)?;
+        state.quarantined = false;
+        state.failure.take()
+    }
+}
+
+impl<Manifest, Context> PluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+    Manifest::PluginId: 'static,
+    Context: 'static,
+{
+    /// Quarantine the plugin with the given plugin id, unloading it along with all plugins that
+    /// list it as a dependency. Loading or enabling a quarantined plugin fails with
+    /// [`LoadPluginError::Quarantined`] until it is retried with [`PluginRegistry::retry`].
+    /// Returns both an iterator over all the plugin ids unloaded and an iterator over all the
+    /// plugin ids disabled.
+    pub fn quarantine(
+        &mut self,
+        id: Manifest::PluginId,
+        context: &mut Context,
+    ) -> (
+        impl IntoIterator<Item = Manifest::PluginId>,
+        impl IntoIterator<Item = Manifest::PluginId>,
+    ) {
+        let (unloaded, disabled) = self.unload(id, context);
+        let unloaded = unloaded.into_iter().collect::<Vec<_>>();
+        let disabled = disabled.into_iter().collect::<Vec<_>>();
+        if let Some(state) = self.plugins.get_mut(&id) {
+            state.quarantined = true;
+        }
+        (unloaded, disabled)
+    }
+
+    /// Determine whether the plugin with the given plugin id is quarantined.
+    #[must_use]
+    pub fn is_quarantined(&self, id: Manifest::PluginId) -> bool {
+        self.plugins.get(&id).is_some_and(|state| state.quarantined)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LoadPluginError, Plugin, PluginRegistry, PluginStatus, SimplePluginManifest};
+
+    struct TestPlugin;
+
+    impl Plugin for TestPlugin {}
+
+    #[test]
+    fn plugin_status() {
+        let mut plugins = PluginRegistry::new();
+        for (id, dependencies) in [("base", vec![]), ("app", vec!["base"]), ("idle", vec![])] {
+            plugins
+                .register(
+                    SimplePluginManifest::with_dependencies(id, "", dependencies),
+                    Some(|| Box::new(TestPlugin)),
+                )
+                .unwrap();
+        }
+        plugins.enable("app", &mut ()).unwrap();
+        plugins.load("idle", &mut ()).unwrap();
+        assert_eq!(plugins.status("app"), Some(PluginStatus::Enabled));
+        assert_eq!(plugins.status("idle"), Some(PluginStatus::Loaded));
+        assert_eq!(plugins.status("missing"), None);
+
+        let mut context = ();
+        let (unloaded, _) = plugins.quarantine("base", &mut context);
+        assert_eq!(
+            unloaded.into_iter().collect::<Vec<_>>(),
+            vec!["app", "base"]
+        );
+        assert_eq!(plugins.status("base"), Some(PluginStatus::Quarantined));
+        assert_eq!(plugins.status("app"), Some(PluginStatus::Registered));
+        assert_eq!(
+            plugins.enable("app", &mut ()),
+            Err(LoadPluginError::Quarantined("base"))
+        );
+
+        let mut statuses = plugins.statuses().collect::<Vec<_>>();
+        statuses.sort_unstable_by_key(|(id, _)| *id);
+        assert_eq!(
+            statuses,
+            vec![
+                ("app", PluginStatus::Registered),
+                ("base", PluginStatus::Quarantined),
+                ("idle", PluginStatus::Loaded),
+            ]
+        );
+
+        assert_eq!(plugins.retry("base"), None);
+        plugins.enable("app", &mut ()).unwrap();
+        assert_eq!(plugins.status("base"), Some(PluginStatus::Enabled));
+    }
+}
@@ -0,0 +1,272 @@
+use super::{LifecyclePhase, PluginFuture};
+
+/// Bound on plugin ids required by the `tracing` feature, which records plugin ids in spans with
+/// their [`Debug`] implementation. Implemented for all types implementing [`Debug`].
+#[cfg(feature = "tracing")]
+pub trait TracePluginId: std::fmt::Debug {}
+
+#[cfg(feature = "tracing")]
+impl<T> TracePluginId for T where T: std::fmt::Debug {}
+
+/// Bound on plugin ids required by the `tracing` feature, which records plugin ids in spans with
+/// their [`Debug`] implementation. Without the feature, implemented for all types.
+#[cfg(not(feature = "tracing"))]
+pub trait TracePluginId {}
+
+#[cfg(not(feature = "tracing"))]
+impl<T> TracePluginId for T {}
+
+/// An operation of the registry on a plugin, traced in its own span.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Operation {
+    Register,
+    Load,
+    LoadDependencies,
+    Enable,
+    Disable,
+    Unload,
+    Remove,
+}
+
+/// A span entered until dropped.
+#[cfg(feature = "tracing")]
+pub(super) type SpanGuard = tracing::span::EnteredSpan;
+
+/// A span entered until dropped.
+#[cfg(not(feature = "tracing"))]
+pub(super) struct SpanGuard;
+
+/// Enter the span of an operation on `plugin`, started by cascading from the same operation on
+/// `parent`, if any.
+#[cfg(feature = "tracing")]
+pub(super) fn enter_operation<Id>(
+    operation: Operation,
+    plugin: &Id,
+    parent: Option<&Id>,
+) -> SpanGuard
+where
+    Id: TracePluginId,
+{
+    use tracing::field::Empty;
+
+    let span = match operation {
+        Operation::Register => tracing::debug_span!("register", plugin = ?plugin, parent = Empty),
+        Operation::Load => tracing::debug_span!("load", plugin = ?plugin, parent = Empty),
+        Operation::LoadDependencies => {
+            tracing::debug_span!("load_dependencies", plugin = ?plugin, parent = Empty)
+        }
+        Operation::Enable => tracing::debug_span!("enable", plugin = ?plugin, parent = Empty),
+        Operation::Disable => tracing::debug_span!("disable", plugin = ?plugin, parent = Empty),
+        Operation::Unload => tracing::debug_span!("unload", plugin = ?plugin, parent = Empty),
+        Operation::Remove => tracing::debug_span!("remove", plugin = ?plugin, parent = Empty),
+    };
+    if let Some(parent) = parent {
+        span.record("parent", tracing::field::debug(parent));
+    }
+    span.entered()
+}
+
+/// Enter the span of an operation on `plugin`, started by cascading from the same operation on
+/// `parent`, if any.
+#[cfg(not(feature = "tracing"))]
+pub(super) fn enter_operation<Id>(
+    _operation: Operation,
+    _plugin: &Id,
+    _parent: Option<&Id>,
+) -> SpanGuard {
+    SpanGuard
+}
+
+/// Enter the span of a lifecycle method of `plugin`, attributing any events emitted by the plugin
+/// to it.
+#[cfg(feature = "tracing")]
+pub(super) fn enter_plugin<Id>(plugin: &Id, phase: LifecyclePhase) -> SpanGuard
+where
+    Id: TracePluginId,
+{
+    plugin_span(plugin, phase).entered()
+}
+
+/// Enter the span of a lifecycle method of `plugin`, attributing any events emitted by the plugin
+/// to it.
+#[cfg(not(feature = "tracing"))]
+pub(super) fn enter_plugin<Id>(_plugin: &Id, _phase: LifecyclePhase) -> SpanGuard {
+    SpanGuard
+}
+
+/// Run the future of an async lifecycle method of `plugin` in the plugin's span.
+#[cfg(feature = "tracing")]
+pub(super) fn instrument<'a, Id, T>(
+    plugin: &Id,
+    phase: LifecyclePhase,
+    future: PluginFuture<'a, T>,
+) -> PluginFuture<'a, T>
+where
+    Id: TracePluginId,
+    T: 'a,
+{
+    Box::pin(tracing::Instrument::instrument(
+        future,
+        plugin_span(plugin, phase),
+    ))
+}
+
+/// Run the future of an async lifecycle method of `plugin` in the plugin's span.
+#[cfg(not(feature = "tracing"))]
+pub(super) fn instrument<'a, Id, T>(
+    _plugin: &Id,
+    _phase: LifecyclePhase,
+    future: PluginFuture<'a, T>,
+) -> PluginFuture<'a, T> {
+    future
+}
+
+#[cfg(feature = "tracing")]
+fn plugin_span<Id>(plugin: &Id, phase: LifecyclePhase) -> tracing::Span
+where
+    Id: TracePluginId,
+{
+    tracing::info_span!("plugin", plugin = ?plugin, %phase)
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use crate::{Plugin, PluginRegistry, SimplePluginManifest};
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    #[derive(Default)]
+    struct Spans {
+        // Span name and recorded fields, indexed by span id
+        spans: Vec<(&'static str, Vec<(&'static str, String)>)>,
+        entered: Vec<usize>,
+        events: Vec<(String, Vec<(&'static str, String)>)>,
+    }
+
+    struct Fields<'a>(&'a mut Vec<(&'static str, String)>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.push((field.name(), format!("{value:?}")));
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct TestSubscriber(Arc<Mutex<Spans>>);
+
+    impl Subscriber for TestSubscriber {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut spans = self.0.lock().unwrap();
+            let mut fields = Vec::new();
+            span.record(&mut Fields(&mut fields));
+            spans.spans.push((span.metadata().name(), fields));
+            Id::from_u64(spans.spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.0.lock().unwrap();
+            let index = span.into_u64() as usize - 1;
+            values.record(&mut Fields(&mut spans.spans[index].1));
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut spans = self.0.lock().unwrap();
+            let mut fields = Vec::new();
+            event.record(&mut Fields(&mut fields));
+            let message = fields.remove(0).1;
+            let current = spans
+                .entered
+                .last()
+                .map_or(Vec::new(), |&index| spans.spans[index].1.clone());
+            spans.events.push((message, current));
+        }
+
+        fn enter(&self, span: &Id) {
+            self.0
+                .lock()
+                .unwrap()
+                .entered
+                .push(span.into_u64() as usize - 1);
+        }
+
+        fn exit(&self, _span: &Id) {
+            self.0.lock().unwrap().entered.pop();
+        }
+    }
+
+    struct TestPlugin;
+
+    impl Plugin for TestPlugin {
+        fn enable(&mut self, _context: &mut ()) {
+            tracing::info!("enabling");
+        }
+    }
+
+    #[test]
+    fn trace_registry_operations() {
+        let subscriber = TestSubscriber::default();
+        tracing::subscriber::with_default(subscriber.clone(), || {
+            let mut plugins = PluginRegistry::new();
+            plugins
+                .register(
+                    SimplePluginManifest::new("base", ""),
+                    Some(|| Box::new(TestPlugin)),
+                )
+                .unwrap();
+            plugins
+                .register(
+                    SimplePluginManifest::with_dependencies("app", "", vec!["base"]),
+                    Some(|| Box::new(TestPlugin)),
+                )
+                .unwrap();
+            plugins.enable("app", &mut ()).unwrap();
+        });
+
+        let spans = subscriber.0.lock().unwrap();
+        let field = |fields: &[(&str, String)], name| {
+            fields
+                .iter()
+                .find(|(field, _)| *field == name)
+                .map(|(_, value)| value.clone())
+        };
+        let (_, load_base) = spans
+            .spans
+            .iter()
+            .find(|(name, fields)| {
+                *name == "load" && field(fields, "plugin").unwrap() == "\"base\""
+            })
+            .unwrap();
+        assert_eq!(field(load_base, "parent").as_deref(), Some("\"app\""));
+        let (_, enable_app) = spans
+            .spans
+            .iter()
+            .find(|(name, fields)| {
+                *name == "enable" && field(fields, "plugin").unwrap() == "\"app\""
+            })
+            .unwrap();
+        assert_eq!(field(enable_app, "parent"), None);
+
+        // Events emitted by plugins are attributed to them
+        let plugins = spans
+            .events
+            .iter()
+            .map(|(message, fields)| (message.as_str(), field(fields, "plugin").unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            plugins,
+            vec![
+                ("enabling", "\"base\"".to_owned()),
+                ("enabling", "\"app\"".to_owned())
+            ]
+        );
+    }
+}
@@ -0,0 +1,537 @@
+use crate::{HookRegistry, HookSlot, LoadPluginError, Plugin, PluginManifest, PluginRegistry};
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, PoisonError};
+use thiserror::Error;
+use wasmtime::{
+    Config, Engine, Func, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder,
+    WasmParams, WasmResults,
+};
+
+/// Names of the exports of a WebAssembly plugin called for each step of its lifecycle.
+const LOAD_EXPORT: &str = "load";
+const UNLOAD_EXPORT: &str = "unload";
+const ENABLE_EXPORT: &str = "enable";
+const DISABLE_EXPORT: &str = "disable";
+
+/// An error occurred while compiling, instantiating or calling a WebAssembly plugin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Error)]
+pub enum WasmError {
+    /// The WebAssembly module could not be compiled.
+    #[error("failed to compile WebAssembly module: {0}")]
+    Compile(String),
+    /// The WebAssembly module could not be instantiated, such as when it imports anything or its
+    /// initial memory exceeds the memory limit.
+    #[error("failed to instantiate WebAssembly module: {0}")]
+    Instantiate(String),
+    /// An export of the module is missing or does not have the expected function signature.
+    #[error("invalid WebAssembly export `{export}`: {reason}")]
+    Export {
+        /// Name of the export.
+        export: String,
+        /// Explanation of why the export could not be used.
+        reason: String,
+    },
+    /// An exported function trapped, including running out of fuel or exceeding the memory limit.
+    #[error("WebAssembly export `{export}` trapped: {reason}")]
+    Trap {
+        /// Name of the export.
+        export: String,
+        /// Description of the trap.
+        reason: String,
+    },
+}
+
+/// The resources a WebAssembly plugin may use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct WasmLimits {
+    /// Amount of fuel available to each call into the plugin, such as each lifecycle step or each
+    /// call of a bridged hook. A call that runs out of fuel traps. Unlimited if [`None`].
+    pub fuel: Option<u64>,
+    /// Maximum size in bytes of the plugin's linear memory. Growing the memory beyond the limit
+    /// traps. Unlimited if [`None`].
+    pub memory: Option<usize>,
+}
+
+/// The runtime shared by WebAssembly plugins, which compiles their modules. Cloning the engine is
+/// cheap, as clones share the same runtime.
+#[derive(Clone)]
+pub struct WasmEngine {
+    engine: Engine,
+}
+
+impl WasmEngine {
+    /// Create a WebAssembly runtime with fuel metering enabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the runtime does not support the host platform.
+    #[must_use]
+    pub fn new() -> Self {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        Self {
+            engine: Engine::new(&config).expect("unsupported WebAssembly host platform"),
+        }
+    }
+}
+
+impl Default for WasmEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for WasmEngine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmEngine").finish_non_exhaustive()
+    }
+}
+
+struct WasmStore {
+    store: Store<StoreLimits>,
+    instance: Instance,
+    fuel: Option<u64>,
+}
+
+impl WasmStore {
+    fn refuel(&mut self) {
+        // Fuel is always enabled by the engine, so this cannot fail
+        self.store.set_fuel(self.fuel.unwrap_or(u64::MAX)).unwrap();
+    }
+
+    fn func(&mut self, export: &str) -> Result<Option<Func>, WasmError> {
+        match self.instance.get_export(&mut self.store, export) {
+            None => Ok(None),
+            Some(extern_) => extern_
+                .into_func()
+                .map(Some)
+                .ok_or_else(|| WasmError::Export {
+                    export: export.to_owned(),
+                    reason: "not a function".to_owned(),
+                }),
+        }
+    }
+
+    fn call<Params, Results>(
+        &mut self,
+        func: Func,
+        export: &str,
+        params: Params,
+    ) -> Result<Results, WasmError>
+    where
+        Params: WasmParams,
+        Results: WasmResults,
+    {
+        let func = func
+            .typed::<Params, Results>(&self.store)
+            .map_err(|error| WasmError::Export {
+                export: export.to_owned(),
+                reason: error.to_string(),
+            })?;
+        self.refuel();
+        func.call(&mut self.store, params)
+            .map_err(|error| WasmError::Trap {
+                export: export.to_owned(),
+                reason: error.root_cause().to_string(),
+            })
+    }
+
+    fn call_lifecycle(&mut self, export: &str) -> Result<(), WasmError> {
+        match self.func(export)? {
+            Some(func) => self.call::<(), ()>(func, export, ()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// An exported function of a loaded WebAssembly plugin, passed to the adapter of a bridged hook
+/// slot to call into the plugin. Cloning the function is cheap, as clones share the plugin's
+/// instance.
+#[derive(Clone)]
+pub struct WasmFunction {
+    store: Arc<Mutex<WasmStore>>,
+    func: Func,
+    export: &'static str,
+}
+
+impl WasmFunction {
+    /// Get the name of the export.
+    #[must_use]
+    pub fn export(&self) -> &'static str {
+        self.export
+    }
+
+    /// Call the exported function with a fresh allotment of the plugin's fuel. Calls into the same
+    /// plugin are serialized.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WasmError::Export`] if the function does not have the signature given by `Params`
+    /// and `Results`, or [`WasmError::Trap`] if the function traps.
+    pub fn call<Params, Results>(&self, params: Params) -> Result<Results, WasmError>
+    where
+        Params: WasmParams,
+        Results: WasmResults,
+    {
+        self.store
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .call(self.func, self.export, params)
+    }
+}
+
+impl Debug for WasmFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmFunction")
+            .field("export", &self.export)
+            .finish_non_exhaustive()
+    }
+}
+
+type Bridge<Id> =
+    Box<dyn Fn(&mut HookRegistry<Id>, Id, WasmFunction) -> Result<(), String> + Send + Sync>;
+
+struct BridgedExport<Id> {
+    export: &'static str,
+    register: Bridge<Id>,
+}
+
+/// A [`Plugin`] running a WebAssembly module sandboxed in a [`WasmEngine`]. The module may not
+/// import anything from the host, and its resources are restricted by [`WasmLimits`].
+///
+/// The lifecycle of the plugin calls the module's exported functions `load`, `enable`, `disable`
+/// and `unload`, which take no parameters and return nothing, if the module exports them. The
+/// module is instantiated when loading the plugin and discarded when unloading it. Exported
+/// functions of the module provide hooks by [bridging][WasmPlugin::bridge] them to hook slots of
+/// the host.
+///
+/// If the module cannot be instantiated, does not export a bridged function or traps in any
+/// lifecycle step, the plugin reports the error to the plugin registry with
+/// [`Plugin::take_failure`], which fails the step with [`LoadPluginError::Failed`] and moves the
+/// plugin to the [`Failed`][crate::PluginStatus::Failed] status.
+pub struct WasmPlugin<Id = &'static str, Context = ()> {
+    id: Id,
+    engine: Engine,
+    module: Module,
+    limits: WasmLimits,
+    bridges: Vec<BridgedExport<Id>>,
+    store: Option<Arc<Mutex<WasmStore>>>,
+    failure: Option<WasmError>,
+    context: PhantomData<fn(&mut Context)>,
+}
+
+impl<Id, Context> WasmPlugin<Id, Context>
+where
+    Id: Copy + Ord + Hash + Send + Sync + 'static,
+{
+    /// Compile a WebAssembly plugin for the plugin with the given plugin id from either a binary or
+    /// text format module.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WasmError::Compile`] if the module is not valid.
+    pub fn new(
+        engine: &WasmEngine,
+        id: Id,
+        module: impl AsRef<[u8]>,
+        limits: WasmLimits,
+    ) -> Result<Self, WasmError> {
+        let module = Module::new(&engine.engine, module)
+            .map_err(|error| WasmError::Compile(format!("{error:#}")))?;
+        Ok(Self {
+            id,
+            engine: engine.engine.clone(),
+            module,
+            limits,
+            bridges: Vec::new(),
+            store: None,
+            failure: None,
+            context: PhantomData,
+        })
+    }
+
+    /// Bridge the exported function `export` of the module to the hook slot `Slot`. When the
+    /// plugin loads, `adapter` wraps the exported function into a hook for the slot, which is
+    /// registered with the optional hook name.
+    #[must_use]
+    pub fn bridge<Slot>(
+        mut self,
+        export: &'static str,
+        name: Option<Id>,
+        adapter: fn(WasmFunction) -> Box<Slot::TraitObject>,
+    ) -> Self
+    where
+        Id: Debug + Display,
+        Slot: HookSlot,
+    {
+        self.bridges.push(BridgedExport {
+            export,
+            register: Box::new(move |hooks, id, function| {
+                hooks
+                    .register::<Slot>(adapter(function), id, name)
+                    .map_err(|rejected| rejected.error.to_string())
+            }),
+        });
+        self
+    }
+
+    /// Get the plugin id the plugin was created for.
+    #[must_use]
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Determine whether the module is currently instantiated.
+    #[must_use]
+    pub fn is_instantiated(&self) -> bool {
+        self.store.is_some()
+    }
+
+    /// Get an exported function of the instantiated module.
+    #[must_use]
+    pub fn function(&self, export: &'static str) -> Option<WasmFunction> {
+        let store = self.store.as_ref()?;
+        let func = store
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .func(export)
+            .ok()??;
+        Some(WasmFunction {
+            store: store.clone(),
+            func,
+            export,
+        })
+    }
+
+    /// Instantiate the module and call its `load` export, unless already instantiated.
+    fn instantiate(&mut self) -> Result<(), WasmError> {
+        if self.store.is_some() {
+            return Ok(());
+        }
+        let mut limits = StoreLimitsBuilder::new().trap_on_grow_failure(true);
+        if let Some(memory) = self.limits.memory {
+            limits = limits.memory_size(memory);
+        }
+        let mut store = Store::new(&self.engine, limits.build());
+        store.limiter(|limits| limits);
+        store
+            .set_fuel(self.limits.fuel.unwrap_or(u64::MAX))
+            .unwrap();
+        let instance = Linker::new(&self.engine)
+            .instantiate(&mut store, &self.module)
+            .map_err(|error| WasmError::Instantiate(error.root_cause().to_string()))?;
+        let mut store = WasmStore {
+            store,
+            instance,
+            fuel: self.limits.fuel,
+        };
+        for bridge in &self.bridges {
+            if store.func(bridge.export)?.is_none() {
+                return Err(WasmError::Export {
+                    export: bridge.export.to_owned(),
+                    reason: "not exported".to_owned(),
+                });
+            }
+        }
+        store.call_lifecycle(LOAD_EXPORT)?;
+        self.store = Some(Arc::new(Mutex::new(store)));
+        Ok(())
+    }
+
+    fn call_lifecycle(&mut self, export: &str) {
+        if let Some(store) = &self.store {
+            if let Err(error) = store
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .call_lifecycle(export)
+            {
+                self.failure = Some(error);
+            }
+        }
+    }
+}
+
+impl<Id, Context> Plugin<Id, Context> for WasmPlugin<Id, Context>
+where
+    Id: Copy + Ord + Hash + Send + Sync + 'static,
+    Context: 'static,
+{
+    fn load(&mut self, hooks: &mut HookRegistry<Id>, _context: &mut Context) {
+        if let Err(error) = self.instantiate() {
+            self.failure = Some(error);
+            return;
+        }
+        for bridge in &self.bridges {
+            let function = self.function(bridge.export).unwrap();
+            if let Err(reason) = (bridge.register)(hooks, self.id, function) {
+                self.failure = Some(WasmError::Export {
+                    export: bridge.export.to_owned(),
+                    reason,
+                });
+                return;
+            }
+        }
+    }
+
+    fn unload(&mut self, _context: &mut Context) {
+        self.call_lifecycle(UNLOAD_EXPORT);
+        self.store = None;
+    }
+
+    fn enable(&mut self, _context: &mut Context) {
+        self.call_lifecycle(ENABLE_EXPORT);
+    }
+
+    fn disable(&mut self, _context: &mut Context) {
+        self.call_lifecycle(DISABLE_EXPORT);
+    }
+
+    fn take_failure(&mut self) -> Option<String> {
+        self.failure.take().map(|error| error.to_string())
+    }
+}
+
+impl<Id, Context> Debug for WasmPlugin<Id, Context>
+where
+    Id: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmPlugin")
+            .field("id", &self.id)
+            .field("limits", &self.limits)
+            .field("instantiated", &self.store.is_some())
+            .field("failure", &self.failure)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<Manifest, Context> PluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+    Manifest::PluginId: Send + Sync + 'static,
+    Context: 'static,
+{
+    /// Load the plugin registered with the given plugin id from a WebAssembly plugin if it is not
+    /// currently loaded, the same as [`PluginRegistry::load_with`]. The module is instantiated and
+    /// its `load` export called after the plugin's dependencies have been loaded, and loading fails
+    /// if either of those fails, such as when the module traps.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`PluginRegistry::load_with`], and [`LoadPluginError::Failed`] if
+    /// the module could not be instantiated, traps while loading or does not export a bridged
+    /// function.
+    pub fn load_wasm(
+        &mut self,
+        id: Manifest::PluginId,
+        plugin: WasmPlugin<Manifest::PluginId, Context>,
+        context: &mut Context,
+    ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
+        self.load_with(id, Box::new(plugin) as Box<dyn Plugin<_, _>>, context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        LoadPluginError, PluginRegistry, SimplePluginManifest, WasmEngine, WasmError, WasmFunction,
+        WasmLimits, WasmPlugin, hook_slot,
+    };
+
+    type DoubleHook = dyn Fn(i32) -> Result<i32, WasmError> + Send + Sync;
+
+    hook_slot!(DoubleSlot: DoubleHook);
+
+    const MODULE: &str = r#"
+        (module
+            (global $enabled (mut i32) (i32.const 0))
+            (func (export "enable") (global.set $enabled (i32.const 1)))
+            (func (export "disable") (global.set $enabled (i32.const 0)))
+            (func (export "enabled") (result i32) (global.get $enabled))
+            (func (export "double") (param i32) (result i32)
+                (i32.mul (local.get 0) (i32.const 2)))
+            (func (export "spin") (param i32) (result i32)
+                (loop $spin (br $spin))
+                (local.get 0)))
+    "#;
+
+    fn adapter(function: WasmFunction) -> Box<DoubleHook> {
+        Box::new(move |value| function.call::<i32, i32>(value))
+    }
+
+    #[test]
+    fn wasm_plugin_lifecycle() {
+        let engine = WasmEngine::new();
+        let limits = WasmLimits {
+            fuel: Some(10_000),
+            memory: Some(65_536),
+        };
+        let mut plugins: PluginRegistry = PluginRegistry::new();
+        for id in ["double", "spin", "trap", "memory", "enable"] {
+            plugins
+                .register(SimplePluginManifest::new(id, ""), None)
+                .unwrap();
+        }
+
+        let plugin = WasmPlugin::new(&engine, "double", MODULE, limits)
+            .unwrap()
+            .bridge::<DoubleSlot>("double", None, adapter);
+        plugins.load_wasm("double", plugin, &mut ()).unwrap();
+        plugins.enable("double", &mut ()).unwrap();
+        let double = plugins.hooks().get_first::<DoubleSlot>("double").unwrap();
+        assert_eq!(double(21), Ok(42));
+        let plugin = plugins.get_loaded::<WasmPlugin>("double").unwrap();
+        assert_eq!(
+            plugin.function("enabled").unwrap().call::<(), i32>(()),
+            Ok(1)
+        );
+
+        // Exhausting the fuel of a call traps without affecting later calls
+        let plugin = WasmPlugin::new(&engine, "spin", MODULE, limits)
+            .unwrap()
+            .bridge::<DoubleSlot>("spin", None, adapter);
+        plugins.load_wasm("spin", plugin, &mut ()).unwrap();
+        let spin = plugins.hooks().get_first::<DoubleSlot>("spin").unwrap();
+        assert!(matches!(spin(1), Err(WasmError::Trap { .. })));
+        let double = plugins.hooks().get_first::<DoubleSlot>("double").unwrap();
+        assert_eq!(double(4), Ok(8));
+
+        // Traps and exceeded limits while loading fail loading the plugin
+        let trap = r#"(module (func (export "load") unreachable))"#;
+        let plugin = WasmPlugin::new(&engine, "trap", trap, limits).unwrap();
+        assert!(matches!(
+            plugins.load_wasm("trap", plugin, &mut ()),
+            Err(LoadPluginError::Failed { plugin: "trap", .. })
+        ));
+        let memory = r#"(module (memory 2))"#;
+        let plugin = WasmPlugin::new(&engine, "memory", memory, limits).unwrap();
+        assert!(plugins.load_wasm("memory", plugin, &mut ()).is_err());
+        assert!(!plugins.is_loaded("trap") && !plugins.is_loaded("memory"));
+
+        // Traps in other lifecycle steps fail the plugin as well
+        let trap = r#"(module (func (export "enable") unreachable))"#;
+        let plugin = WasmPlugin::new(&engine, "enable", trap, limits)
+            .unwrap()
+            .bridge::<DoubleSlot>("enable", None, adapter);
+        plugins.load_wasm("enable", plugin, &mut ()).unwrap();
+        assert!(plugins.hooks().exists::<DoubleSlot>("enable"));
+        assert!(matches!(
+            plugins.enable("enable", &mut ()),
+            Err(LoadPluginError::Failed {
+                plugin: "enable",
+                ..
+            })
+        ));
+        assert!(!plugins.is_enabled("enable"));
+        assert!(!plugins.hooks().exists::<DoubleSlot>("enable"));
+        assert!(matches!(
+            plugins.failure("enable"),
+            Some(LoadPluginError::Failed { .. })
+        ));
+
+        plugins.unload("double", &mut ());
+        assert!(!plugins.hooks().exists::<DoubleSlot>("double"));
+    }
+}
@@ -0,0 +1,360 @@
+use crate::{
+    FnPluginConstructor, HookSlot, HookSnapshot, LoadPluginError, PluginManifest, PluginRegistry,
+    RegisterPluginError, SimplePluginManifest, SlotHooks,
+};
+use arc_swap::ArcSwap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// A thread-safe handle to a [`PluginRegistry`] for plugin hosts that read hooks from many threads
+/// while occasionally changing plugins from another.
+///
+/// Lifecycle operations are serialized by a lock on the registry, and each one publishes an
+/// immutable [`HookSnapshot`] of the registry's shared hooks once it finishes. Readers get the
+/// latest published snapshot with [`SharedPluginRegistry::hooks`] or
+/// [`SharedPluginRegistry::slot_hooks`] without ever waiting on the lock. A snapshot shares its
+/// hooks with the registry and keeps them alive until every reader has dropped it, even if their
+/// plugin was unloaded in the meantime.
+///
+/// Only hooks registered with
+/// [`HookRegistry::register_shared`][crate::HookRegistry::register_shared] are published, so
+/// plugins register the hooks read from other threads that way, and those hooks should use
+/// interior mutability if their state changes. Hooks owned by the registry are reached through
+/// [`SharedPluginRegistry::read`] and [`SharedPluginRegistry::update`], where they can also be
+/// borrowed mutably.
+///
+/// Hooks read from a published snapshot bypass the circuit breaker and profiler of the
+/// registry's [`HookRegistry`][crate::HookRegistry]. Readers that need them should dispatch through
+/// [`SharedPluginRegistry::read`] instead, and have the host call
+/// [`PluginRegistry::handle_tripped_hooks`] through [`SharedPluginRegistry::update`].
+#[derive(Debug)]
+pub struct SharedPluginRegistry<Manifest = SimplePluginManifest, Context = ()>
+where
+    Manifest: PluginManifest,
+{
+    registry: Mutex<PluginRegistry<Manifest, Context>>,
+    hooks: ArcSwap<HookSnapshot<Manifest::PluginId>>,
+}
+
+impl<Manifest, Context> SharedPluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+{
+    /// Share a plugin registry between threads, publishing a snapshot of its current hooks.
+    #[must_use]
+    pub fn new(registry: PluginRegistry<Manifest, Context>) -> Self {
+        Self {
+            hooks: ArcSwap::from_pointee(registry.hooks().snapshot()),
+            registry: Mutex::new(registry),
+        }
+    }
+
+    /// Get the most recently published snapshot of all hooks without waiting on any lock.
+    #[must_use]
+    pub fn hooks(&self) -> Arc<HookSnapshot<Manifest::PluginId>> {
+        self.hooks.load_full()
+    }
+
+    /// Get the hooks registered to a slot in the most recently published snapshot without waiting
+    /// on any lock.
+    #[must_use]
+    pub fn slot_hooks<Slot>(&self) -> SlotHooks<Manifest::PluginId, Slot>
+    where
+        Slot: HookSlot,
+    {
+        self.hooks.load().slot()
+    }
+
+    /// Lock the registry and call `f` with a shared reference to it.
+    pub fn read<R>(&self, f: impl FnOnce(&PluginRegistry<Manifest, Context>) -> R) -> R {
+        f(&self.lock())
+    }
+
+    /// Lock the registry and call `f` with a mutable reference to it, then publish a new snapshot
+    /// of the registry's hooks.
+    ///
+    /// Within `f`, hooks owned by the registry can be borrowed mutably, such as with
+    /// [`HookRegistry::get_first_mut`][crate::HookRegistry::get_first_mut]. Shared hooks that were
+    /// already registered are shared with the published snapshot, so the mutable hook accessors
+    /// skip them, while [`HookRegistry::try_get_first_mut`][crate::HookRegistry::try_get_first_mut]
+    /// reports [`HookAccessError::Shared`][crate::HookAccessError::Shared].
+    pub fn update<R>(&self, f: impl FnOnce(&mut PluginRegistry<Manifest, Context>) -> R) -> R {
+        let mut registry = self.lock();
+        let result = f(&mut registry);
+        self.hooks.store(Arc::new(registry.hooks().snapshot()));
+        result
+    }
+
+    /// Consume the handle and return the shared plugin registry.
+    #[must_use]
+    pub fn into_inner(self) -> PluginRegistry<Manifest, Context> {
+        self.registry
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Register a plugin with [`PluginRegistry::register`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`PluginRegistry::register`].
+    pub fn register(
+        &self,
+        manifest: Manifest,
+        ctor: Option<FnPluginConstructor<Manifest::PluginId, Context>>,
+    ) -> Result<Manifest::PluginId, RegisterPluginError<Manifest::PluginId>> {
+        self.lock().register(manifest, ctor)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PluginRegistry<Manifest, Context>> {
+        self.registry.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<Manifest, Context> SharedPluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+    Manifest::PluginId: 'static,
+    Context: 'static,
+{
+    /// Remove a plugin with [`PluginRegistry::remove`] and publish a new snapshot of the hooks.
+    pub fn remove(
+        &self,
+        id: Manifest::PluginId,
+        context: &mut Context,
+    ) -> (
+        bool,
+        impl IntoIterator<Item = Manifest::PluginId>,
+        impl IntoIterator<Item = Manifest::PluginId>,
+    ) {
+        self.update(|registry| {
+            let (removed, unloaded, disabled) = registry.remove(id, context);
+            (
+                removed,
+                unloaded.into_iter().collect::<Vec<_>>(),
+                disabled.into_iter().collect::<Vec<_>>(),
+            )
+        })
+    }
+
+    /// Load a plugin with [`PluginRegistry::load`] and publish a new snapshot of the hooks.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`PluginRegistry::load`].
+    pub fn load(
+        &self,
+        id: Manifest::PluginId,
+        context: &mut Context,
+    ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
+        self.update(|registry| registry.load(id, context))
+    }
+
+    /// Unload a plugin with [`PluginRegistry::unload`] and publish a new snapshot of the hooks.
+    pub fn unload(
+        &self,
+        id: Manifest::PluginId,
+        context: &mut Context,
+    ) -> (
+        impl IntoIterator<Item = Manifest::PluginId>,
+        impl IntoIterator<Item = Manifest::PluginId>,
+    ) {
+        self.update(|registry| {
+            let (unloaded, disabled) = registry.unload(id, context);
+            (
+                unloaded.into_iter().collect::<Vec<_>>(),
+                disabled.into_iter().collect::<Vec<_>>(),
+            )
+        })
+    }
+
+    /// Enable a plugin with [`PluginRegistry::enable`] and publish a new snapshot of the hooks.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`PluginRegistry::enable`].
+    pub fn enable(
+        &self,
+        id: Manifest::PluginId,
+        context: &mut Context,
+    ) -> Result<(), LoadPluginError<Manifest::PluginId>> {
+        self.update(|registry| registry.enable(id, context))
+    }
+
+    /// Disable a plugin with [`PluginRegistry::disable`] and publish a new snapshot of the hooks.
+    pub fn disable(
+        &self,
+        id: Manifest::PluginId,
+        context: &mut Context,
+    ) -> impl IntoIterator<Item = Manifest::PluginId> {
+        self.update(|registry| {
+            registry
+                .disable(id, context)
+                .into_iter()
+                .collect::<Vec<_>>()
+        })
+    }
+}
+
+impl<Manifest, Context> From<PluginRegistry<Manifest, Context>>
+    for SharedPluginRegistry<Manifest, Context>
+where
+    Manifest: PluginManifest,
+{
+    fn from(registry: PluginRegistry<Manifest, Context>) -> Self {
+        Self::new(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        HookAccessError, HookRegistry, HookSlot, Plugin, PluginRegistry, SharedPluginRegistry,
+        SimplePluginManifest, hook_slot,
+    };
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    trait Handler: Send + Sync {
+        fn handle(&self) -> u32;
+    }
+
+    hook_slot!(HandlerSlot: dyn Handler);
+
+    trait Counter: Send + Sync {
+        fn increment(&mut self) -> u32;
+    }
+
+    impl Counter for u32 {
+        fn increment(&mut self) -> u32 {
+            *self += 1;
+            *self
+        }
+    }
+
+    hook_slot!(CounterSlot: dyn Counter);
+
+    struct Answer;
+
+    impl Handler for Answer {
+        fn handle(&self) -> u32 {
+            42
+        }
+    }
+
+    struct AnswerPlugin;
+
+    impl Plugin for AnswerPlugin {
+        fn load(&mut self, hooks: &mut HookRegistry, _context: &mut ()) {
+            assert!(
+                hooks
+                    .register_shared::<HandlerSlot>(Arc::new(Answer), "answer", None)
+                    .is_ok()
+            );
+            assert!(
+                hooks
+                    .register::<CounterSlot>(Box::new(0), "answer", None)
+                    .is_ok()
+            );
+        }
+    }
+
+    fn shared_registry() -> SharedPluginRegistry {
+        let mut plugins = PluginRegistry::new();
+        plugins
+            .register(
+                SimplePluginManifest::new("answer", ""),
+                Some(|| Box::new(AnswerPlugin)),
+            )
+            .unwrap();
+        plugins.into()
+    }
+
+    #[test]
+    fn snapshots_outlive_unload() {
+        let plugins = shared_registry();
+        assert!(plugins.slot_hooks::<HandlerSlot>().is_empty());
+
+        plugins.enable("answer", &mut ()).unwrap();
+        let snapshot = plugins.hooks();
+        let slot = plugins.slot_hooks::<HandlerSlot>();
+        assert_eq!(slot.len(), 1);
+
+        plugins.unload("answer", &mut ());
+        assert!(plugins.slot_hooks::<HandlerSlot>().is_empty());
+        assert!(plugins.hooks().get_first::<HandlerSlot>("answer").is_none());
+        assert!(plugins.read(|registry| !registry.hooks().exists::<HandlerSlot>("answer")));
+
+        // Snapshots taken before unloading remain valid
+        assert_eq!(slot.get_first("answer").unwrap().handle(), 42);
+        assert_eq!(
+            snapshot
+                .get_first::<HandlerSlot>("answer")
+                .unwrap()
+                .handle(),
+            42
+        );
+    }
+
+    #[test]
+    fn update_borrows_owned_hooks() {
+        let plugins = shared_registry();
+        plugins.enable("answer", &mut ()).unwrap();
+        assert!(plugins.slot_hooks::<CounterSlot>().is_empty());
+        plugins.update(|registry| {
+            let hooks = registry.hooks_mut();
+            for expected in 1..=2 {
+                assert_eq!(
+                    hooks
+                        .get_first_mut::<CounterSlot>("answer")
+                        .unwrap()
+                        .increment(),
+                    expected
+                );
+            }
+            assert!(hooks.get_first_mut::<HandlerSlot>("answer").is_none());
+            assert_eq!(
+                hooks.try_get_first_mut::<HandlerSlot>("answer").err(),
+                Some(HookAccessError::Shared {
+                    slot: HandlerSlot::id(),
+                    plugin: "answer",
+                    name: None,
+                })
+            );
+        });
+
+        let mut registry = plugins.into_inner();
+        assert!(
+            registry
+                .hooks_mut()
+                .get_first_mut::<HandlerSlot>("answer")
+                .is_some()
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn readers_run_during_lifecycle_changes() {
+        let plugins = shared_registry();
+        let done = AtomicBool::new(false);
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    while !done.load(Ordering::Relaxed) {
+                        for (plugin, hook) in plugins.slot_hooks::<HandlerSlot>().hooks_and_plugin()
+                        {
+                            assert_eq!(plugin, "answer");
+                            assert_eq!(hook.handle(), 42);
+                        }
+                    }
+                });
+            }
+
+            for _ in 0..100 {
+                plugins.enable("answer", &mut ()).unwrap();
+                plugins.unload("answer", &mut ());
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+    }
+}